@@ -1,11 +1,14 @@
+use gameplay::collision::PhysicsWorld;
+use gameplay::mask::MaskType;
+use gameplay::targeting::{is_in_gaze, kill_duration};
 use glam::{Mat4, Vec3};
 use rand::Rng;
+use session::HeadHistory;
 use std::collections::HashMap;
+use web_time::{Duration, Instant};
 
-use crate::collision::PhysicsWorld;
-use crate::config::*;
 use crate::network::PeerId;
-use crate::player::{MaskType, RemotePlayer, look_direction_from_angles};
+use crate::player::RemotePlayer;
 
 /// Result of running targeting for one frame.
 pub struct TargetingResult {
@@ -43,10 +46,13 @@ impl DeathMarker {
 }
 
 /// Advance targeting timers and kill enemies whose timer expires.
+/// Enemies are tested where we saw them, within the lag compensation limit.
 /// Mutates remote_players (targeted_time, is_alive).
 /// Returns which peers were killed.
+#[allow(clippy::too_many_arguments)]
 pub fn update_targeting(
     remote_players: &mut HashMap<PeerId, RemotePlayer>,
+    now: Instant,
     dt: f32,
     eye_pos: Vec3,
     yaw: f32,
@@ -54,15 +60,8 @@ pub fn update_targeting(
     mask: MaskType,
     physics: &PhysicsWorld,
 ) -> TargetingResult {
-    let can_kill = mask != MaskType::Coward;
-
-    let kill_duration = match mask {
-        MaskType::Hunter => HUNTER_KILL_DURATION,
-        _ => TARGETING_DURATION,
-    };
-
-    let look_dir = look_direction_from_angles(yaw, pitch);
-    let half_angle_rad = (TARGETING_ANGLE / 2.0).to_radians();
+    let can_kill = mask.can_kill();
+    let kill_duration = kill_duration(mask);
 
     let mut kills = Vec::new();
 
@@ -71,20 +70,8 @@ pub fn update_targeting(
             continue;
         }
 
-        let enemy_head = remote.head_position();
-        let to_enemy = enemy_head - eye_pos;
-        let distance = to_enemy.length();
-
-        if distance < 1.0 {
-            remote.targeted_time = 0.0;
-            continue;
-        }
-
-        let to_enemy_normalized = to_enemy / distance;
-        let dot = look_dir.dot(to_enemy_normalized).clamp(-1.0, 1.0);
-        let angle = dot.acos();
-
-        if angle < half_angle_rad && physics.is_visible(eye_pos, enemy_head) {
+        let head = remote.compensated_head_position(now);
+        if is_in_gaze(physics, eye_pos, yaw, pitch, head) {
             if can_kill {
                 remote.targeted_time += dt;
                 if remote.targeted_time >= kill_duration {
@@ -110,10 +97,7 @@ pub fn get_targeting_progress(
     remote_players: &HashMap<PeerId, RemotePlayer>,
     mask: MaskType,
 ) -> (f32, bool) {
-    let kill_duration = match mask {
-        MaskType::Hunter => HUNTER_KILL_DURATION,
-        _ => TARGETING_DURATION,
-    };
+    let kill_duration = kill_duration(mask);
 
     let mut max_progress = 0.0f32;
     let mut has_target = false;
@@ -133,41 +117,46 @@ pub fn get_targeting_progress(
 
 /// Get enemies currently aiming at our position.
 /// Returns Vec of (peer_id, enemy_head_position).
+///
+/// The gaze we render for an enemy is `view_latency` old, and it was aimed
+/// at where they saw us, `lag_compensation` before that; our head is rewound
+/// by both.
 pub fn get_threats(
     remote_players: &HashMap<PeerId, RemotePlayer>,
-    my_head: Vec3,
+    head_history: &HeadHistory,
+    now: Instant,
     physics: &PhysicsWorld,
 ) -> Vec<(PeerId, Vec3)> {
-    let half_angle_rad = (TARGETING_ANGLE / 2.0).to_radians();
     let mut threats = Vec::new();
 
     for (&peer_id, remote) in remote_players {
-        if !remote.is_alive {
+        if !remote.is_alive || !remote.mask.can_kill() {
             continue;
         }
 
-        if remote.mask == MaskType::Coward {
+        let rewind = threat_rewind(remote);
+        let seen_at = now
+            .checked_sub(Duration::from_secs_f32(rewind))
+            .unwrap_or(now);
+        let Some(my_head) = head_history.at(seen_at) else {
             continue;
-        }
-
-        let enemy_eye = remote.eye_position();
-        let enemy_look_dir = look_direction_from_angles(remote.yaw, remote.pitch);
-
-        let to_us = my_head - enemy_eye;
-        let distance = to_us.length();
-
-        if distance < 1.0 {
-            continue;
-        }
-
-        let to_us_normalized = to_us / distance;
-        let dot = enemy_look_dir.dot(to_us_normalized).clamp(-1.0, 1.0);
-        let angle = dot.acos();
-
-        if angle < half_angle_rad && physics.is_visible(enemy_eye, my_head) {
+        };
+        if is_in_gaze(
+            physics,
+            remote.eye_position(),
+            remote.yaw,
+            remote.pitch,
+            my_head,
+        ) {
             threats.push((peer_id, remote.head_position()));
         }
     }
 
     threats
 }
+
+/// How far back our head is checked against an enemy's rendered gaze, in
+/// seconds.
+pub fn threat_rewind(remote: &RemotePlayer) -> f32 {
+    remote.view_latency() + remote.lag_compensation()
+}
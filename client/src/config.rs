@@ -1,63 +1,39 @@
-// Player dimensions and physics
-pub const PLAYER_HEIGHT: f32 = 80.0;
-pub const PLAYER_WIDTH: f32 = 22.0;
-pub const EYE_HEIGHT: f32 = 70.0;
-pub const STEP_OVER_HEIGHT: f32 = 28.0;
-pub const GROUND_SNAP_MARGIN: f32 = 5.0;
-
-// Movement
-pub const MOVE_SPEED: f32 = 350.0;
-pub const MOUSE_SENSITIVITY: f32 = 0.0025;
-
-// Jump physics (derived: GRAVITY = 8*H/T², JUMP_VELOCITY = 4*H/T)
-pub const JUMP_HEIGHT: f32 = 70.0;
-pub const JUMP_DURATION: f32 = 0.4;
-pub const GRAVITY: f32 = 8.0 * JUMP_HEIGHT / (JUMP_DURATION * JUMP_DURATION);
-pub const JUMP_VELOCITY: f32 = 4.0 * JUMP_HEIGHT / JUMP_DURATION;
+// Player dimensions, movement, collision and targeting rules (shared with
+// the server, and with the checks we run on other players' movement)
+pub use gameplay::config::*;
 
-// Collision: path check (anti-tunnelling)
-pub const PATH_HIT_MARGIN: f32 = 2.0;
+// Input
+pub const MOUSE_SENSITIVITY: f32 = 0.0025;
 
 // Map
 pub const RESPAWN_MARGIN: f32 = 500.0;
 pub const MAX_FALL_VELOCITY: f32 = 5_000.0; // Respawn if falling faster than this
-pub const SPAWN_POINTS: &[[f32; 3]] = &[
-    [-408.5, -127.0, 2414.2],
-    [-196.2, -127.0, 2417.7],
-    [-277.4, -127.0, 2204.3],
-    [299.0, 0.0, 498.4],
-    [657.3, 0.0, 412.4],
-    [-58.9, 0.0, 347.5],
-    [-1322.2, 128.0, 2969.8],
-    [-1113.8, 5.0, 474.7],
-    [-251.3, 5.0, -373.5],
-    [1446.0, 128.0, -530.5],
-    [1536.0, 36.4, 1162.0],
-    [1583.0, 4.6, 2294.4],
-];
-
-// Targeting system
-pub const TARGETING_ANGLE: f32 = 60.0;
-pub const TARGETING_DURATION: f32 = 1.0;
-pub const DEATH_GRACE_PERIOD: f32 = 0.05; // 50ms for continued targeting after death
-
-// Mask system
-pub const COWARD_SPEED_MULTIPLIER: f32 = 1.5;
-pub const HUNTER_KILL_DURATION: f32 = 0.7;
-
-// Coward dash (directional blink in look direction)
-pub const DASH_DISTANCE: f32 = 300.0; // max distance
-pub const DASH_SPEED: f32 = 1500.0; // units/second
-pub const DASH_COOLDOWN: f32 = 0.0; // seconds between dashes
+
+// Hunter gaze cone and remote player tints
 pub const HUNTER_CONE_LENGTH: f32 = 5000.0;
 pub const HUNTER_CONE_ALPHA: f32 = 0.3;
+pub const LOST_CONNECTION_TINT: [f32; 4] = [0.3, 0.3, 0.3, 1.0]; // players we can't reach
 
 // Threat indicator (enemies looking at you)
 // Visual constants are in render/threat.wgsl (ARROW_SIZE, EDGE_MARGIN)
 pub const THREAT_ARROW_ALPHA: f32 = 0.8;
 
+// State packets sent to each peer per second, independent of frame rate
+pub const NETWORK_TICK_RATE: f32 = 30.0;
+
+// Most a target is rewound for gaze checks, so high-RTT players can't kill
+// at positions their victims left long ago
+pub const MAX_LAG_COMPENSATION: f32 = 0.2; // seconds
+
+// Implausible moves in a round before a peer is flagged and reported; a
+// single one may just be a glitch
+pub const SUSPICION_THRESHOLD: u32 = 3;
+
 // AFK timeout
 pub const AFK_TIMEOUT_SECONDS: f32 = 600.0; // 10 minutes
 
+// How long a notice from the server operators stays on screen
+pub const SERVER_NOTICE_SECONDS: f32 = 8.0;
+
 // Debug options
 pub const DEBUG_MANNEQUINS: bool = true;
@@ -1,3 +1,5 @@
+use protocol::ChannelKind;
+use session::NetworkSimulator;
 use std::time::Duration;
 use web_time::Instant;
 
@@ -156,6 +158,8 @@ impl DebugOverlay {
         player_pos: glam::Vec3,
         player_vel: glam::Vec3,
         physics_debug: &PhysicsDebugInfo,
+        lag_compensation: &LagCompensationInfo,
+        net_sim: Option<&NetworkSimulator>,
     ) {
         const UPDATE_INTERVAL_SECS: f32 = 0.1;
 
@@ -201,6 +205,32 @@ impl DebugOverlay {
             ),
         );
 
+        // Lag compensation (largest correction across remote players)
+        set_element_text(
+            "dbg-lag-comp",
+            &format!(
+                "aim +{:.0}ms, threats {:.0}ms",
+                lag_compensation.targeting * 1000.0,
+                lag_compensation.threats * 1000.0
+            ),
+        );
+
+        // Simulated network conditions, per channel
+        for (channel, id) in [
+            (ChannelKind::State, "dbg-netsim-state"),
+            (ChannelKind::Events, "dbg-netsim-events"),
+        ] {
+            let text = match net_sim {
+                Some(sim) => format!(
+                    "{} ({} lost)",
+                    sim.conditions().channel(channel),
+                    sim.dropped(channel)
+                ),
+                None => "off".to_string(),
+            };
+            set_element_text(id, &text);
+        }
+
         // Position
         set_element_text(
             "local-pos",
@@ -241,6 +271,16 @@ impl DebugOverlay {
     }
 }
 
+/// How far gaze checks are rewinding remote players, in seconds
+#[derive(Default)]
+pub struct LagCompensationInfo {
+    /// Largest shift of a gaze target away from its rendered pose; only
+    /// non-zero when the rewind is capped
+    pub targeting: f32,
+    /// Largest rewind of our head when checking enemy gazes
+    pub threats: f32,
+}
+
 /// Physics debug info passed to the overlay
 pub struct PhysicsDebugInfo {
     pub on_ground: bool,
@@ -248,8 +288,8 @@ pub struct PhysicsDebugInfo {
     pub wall_distances: [Option<f32>; 4], // +X, -X, +Z, -Z
 }
 
-impl From<crate::collision::CollisionDebug> for PhysicsDebugInfo {
-    fn from(debug: crate::collision::CollisionDebug) -> Self {
+impl From<gameplay::collision::CollisionDebug> for PhysicsDebugInfo {
+    fn from(debug: gameplay::collision::CollisionDebug) -> Self {
         Self {
             on_ground: debug.on_ground,
             ground_distance: debug.ground_distance,
@@ -1,19 +1,25 @@
+use gameplay::collision::PhysicsWorld;
+use gameplay::map::CollisionMesh;
+use gameplay::mask::MaskType;
 use glam::Vec3;
 use rand::Rng;
+use session::HeadHistory;
+use std::cell::RefCell;
 use std::collections::HashMap;
-use web_time::Instant;
+use std::rc::Rc;
+use web_time::{Duration, Instant};
 
-use crate::collision::PhysicsWorld;
+use crate::assets::EMBEDDED_MAP;
 use crate::combat;
 use crate::config::*;
-use crate::debug::PhysicsDebugInfo;
+use crate::debug::{LagCompensationInfo, PhysicsDebugInfo};
 use crate::game_ui;
 use crate::input::InputState;
-use crate::mesh::Mesh;
 use crate::network::{
-    GamePhase, NetworkClient, NetworkEvent, PeerId, fetch_peer_stats, update_peer_stats_display,
+    GamePhase, KillRejectReason, MovementViolation, NetworkClient, NetworkEvent, PeerId, PeerStats,
+    StatsHistory, Topology, fetch_peer_stats, update_peer_stats_display,
 };
-use crate::player::{MaskType, Player, RemotePlayer};
+use crate::player::{Player, RemotePlayer};
 use winit::keyboard::KeyCode;
 
 // Re-exports so render/mod.rs and main.rs don't need import changes
@@ -37,9 +43,17 @@ pub struct GameState {
     pub is_dead: bool,
     pub phase: GamePhase,
     pub phase_timer: f32,
+    /// When the current phase ends; `phase_timer` counts down to it
+    phase_ends_at: Instant,
+    /// Seconds left before the current server notice is hidden
+    pub notice_timer: f32,
+    /// Joined while a round was being played; watching until the next one starts
+    pub spectating: bool,
     pub winner_id: Option<PeerId>,
     last_update: Instant,
     pending_kills: Vec<PeerId>,
+    /// Peers whose movement we flagged, to report to the server
+    pending_reports: Vec<(PeerId, MovementViolation)>,
     pending_death_sounds: u32,
     local_peer_id: Option<PeerId>,
     just_died: bool,
@@ -54,21 +68,29 @@ pub struct GameState {
     pub local_name: Option<String>,
     /// Local player's total kills
     pub local_kills: u32,
-    /// Last time peer stats were updated
-    last_stats_update: Instant,
     /// Kill feed for the current round: (killer_name, victim_name)
     kill_feed: Vec<(String, String)>,
+    /// Where our head has been, for lag-compensated threat checks
+    head_history: HeadHistory,
+    /// Peer RTTs from the last stats refresh, filled in asynchronously
+    peer_rtts: Rc<RefCell<HashMap<PeerId, f64>>>,
+    /// Recent stats refreshes, for the trends in the peer panel
+    stats_history: Rc<RefCell<StatsHistory>>,
 }
 
 impl GameState {
-    pub fn new(mesh: &Mesh, debug_mannequins: bool) -> Self {
+    pub fn new(debug_mannequins: bool) -> Self {
         let spawn_idx = rand::rng().random_range(0..SPAWN_POINTS.len());
         let initial_spawn = Self::get_spawn_point(spawn_idx);
 
         let player = Player::new(initial_spawn);
 
-        let (collision_vertices, collision_indices, bounds) = Self::extract_collision_data(mesh);
-        let physics = PhysicsWorld::new(&collision_vertices, &collision_indices)
+        // Same collision geometry the server uses to validate kills
+        let collision =
+            CollisionMesh::from_glb(EMBEDDED_MAP).expect("Failed to load map collision");
+        let bounds = collision.bounds;
+        let physics = collision
+            .physics_world()
             .expect("Failed to create physics world");
 
         let mut remote_players = HashMap::new();
@@ -96,9 +118,13 @@ impl GameState {
             is_dead: false,
             phase: GamePhase::WaitingForPlayers,
             phase_timer: 0.0,
+            phase_ends_at: Instant::now(),
+            notice_timer: 0.0,
+            spectating: false,
             winner_id: None,
             last_update: Instant::now(),
             pending_kills: Vec::new(),
+            pending_reports: Vec::new(),
             pending_death_sounds: 0,
             local_peer_id: None,
             just_died: false,
@@ -108,8 +134,10 @@ impl GameState {
             death_state: None,
             local_name: None,
             local_kills: 0,
-            last_stats_update: Instant::now(),
             kill_feed: Vec::new(),
+            head_history: HeadHistory::new(),
+            peer_rtts: Rc::new(RefCell::new(HashMap::new())),
+            stats_history: Rc::new(RefCell::new(StatsHistory::default())),
         }
     }
 
@@ -122,34 +150,6 @@ impl GameState {
     // Map / spawn helpers
     // -----------------------------------------------------------------------
 
-    fn extract_collision_data(mesh: &Mesh) -> (Vec<Vec3>, Vec<[u32; 3]>, (Vec3, Vec3)) {
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
-
-        for submesh in &mesh.submeshes {
-            let base_idx = vertices.len() as u32;
-            for v in &submesh.vertices {
-                vertices.push(Vec3::from_array(v.position));
-            }
-            for chunk in submesh.indices.chunks(3) {
-                if chunk.len() == 3 {
-                    indices.push([
-                        base_idx + chunk[0],
-                        base_idx + chunk[1],
-                        base_idx + chunk[2],
-                    ]);
-                }
-            }
-        }
-
-        let (bounds_min, bounds_max) = vertices.iter().fold(
-            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
-            |(min, max), v| (min.min(*v), max.max(*v)),
-        );
-
-        (vertices, indices, (bounds_min, bounds_max))
-    }
-
     fn get_spawn_point(idx: usize) -> Vec3 {
         let p = SPAWN_POINTS[idx];
         Vec3::new(p[0], p[1], p[2])
@@ -174,15 +174,34 @@ impl GameState {
         self.last_update = now;
         self.time += dt;
 
-        if self.phase_timer > 0.0 {
-            self.phase_timer = (self.phase_timer - dt).max(0.0);
+        // Counted against the deadline rather than summed from frame times,
+        // which are capped and would fall behind after a stall
+        self.phase_timer = self
+            .phase_ends_at
+            .saturating_duration_since(now)
+            .as_secs_f32();
+        if self.notice_timer > 0.0 {
+            self.notice_timer -= dt;
+            if self.notice_timer <= 0.0 {
+                game_ui::hide_notice();
+            }
         }
 
         self.update_hud_display();
         self.update_mask_input(input);
 
-        let is_spectator =
-            self.is_dead || self.phase == GamePhase::Victory || self.phase == GamePhase::Spectating;
+        for remote in self.remote_players.values_mut() {
+            remote.interpolate(now);
+        }
+
+        // Gaze kills speed up in sudden death to force a result
+        let targeting_dt = if self.phase == GamePhase::SuddenDeath {
+            dt * SUDDEN_DEATH_TARGETING_SPEED
+        } else {
+            dt
+        };
+
+        let is_spectator = self.is_dead || self.phase == GamePhase::Victory || self.spectating;
         if is_spectator {
             self.player.spectator_update(dt, input);
 
@@ -193,7 +212,8 @@ impl GameState {
             {
                 let result = combat::update_targeting(
                     &mut self.remote_players,
-                    dt,
+                    now,
+                    targeting_dt,
                     death.position,
                     death.yaw,
                     death.pitch,
@@ -232,14 +252,16 @@ impl GameState {
         self.player.set_on_ground(on_ground, None);
 
         self.check_respawn();
+        self.head_history.record(now, self.player.eye_position());
 
         // Targeting via combat module
-        if (self.phase == GamePhase::Playing || self.phase == GamePhase::WaitingForPlayers)
+        if (self.phase.is_round_active() || self.phase == GamePhase::WaitingForPlayers)
             && !self.is_dead
         {
             let result = combat::update_targeting(
                 &mut self.remote_players,
-                dt,
+                now,
+                targeting_dt,
                 self.player.eye_position(),
                 self.player.yaw,
                 self.player.pitch,
@@ -314,7 +336,30 @@ impl GameState {
 
     /// Take pending kills to be sent over network
     pub fn take_pending_kills(&mut self) -> Vec<PeerId> {
-        std::mem::take(&mut self.pending_kills)
+        let (mannequin_kills, kills): (Vec<PeerId>, Vec<PeerId>) =
+            std::mem::take(&mut self.pending_kills)
+                .into_iter()
+                .partition(|&id| is_debug_mannequin(id));
+
+        // The server doesn't know about debug mannequins, so resolve those locally
+        if let Some(local_id) = self.local_peer_id {
+            for victim_id in mannequin_kills {
+                self.handle_network_event(
+                    NetworkEvent::PlayerKilled {
+                        killer_id: local_id,
+                        victim_id,
+                    },
+                    Some(local_id),
+                );
+            }
+        }
+
+        kills
+    }
+
+    /// Take reports of peers whose movement broke the rules
+    pub fn take_pending_reports(&mut self) -> Vec<(PeerId, MovementViolation)> {
+        std::mem::take(&mut self.pending_reports)
     }
 
     /// Take count of deaths that need sound effects
@@ -333,8 +378,7 @@ impl GameState {
     }
 
     pub fn get_targeting_info(&self) -> (f32, bool) {
-        let can_target =
-            self.phase == GamePhase::Playing || self.phase == GamePhase::WaitingForPlayers;
+        let can_target = self.phase.is_round_active() || self.phase == GamePhase::WaitingForPlayers;
         if !can_target || self.is_dead {
             return (0.0, false);
         }
@@ -347,11 +391,22 @@ impl GameState {
         }
         combat::get_threats(
             &self.remote_players,
-            self.player.eye_position(),
+            &self.head_history,
+            Instant::now(),
             &self.physics,
         )
     }
 
+    /// The largest lag corrections currently applied, for the debug overlay
+    pub fn get_lag_compensation_info(&self) -> LagCompensationInfo {
+        let mut info = LagCompensationInfo::default();
+        for remote in self.remote_players.values().filter(|r| r.is_alive) {
+            info.targeting = info.targeting.max(remote.targeting_offset());
+            info.threats = info.threats.max(combat::threat_rewind(remote));
+        }
+        info
+    }
+
     /// Get physics debug info for the debug overlay
     pub fn get_physics_debug(&self) -> PhysicsDebugInfo {
         self.physics.get_debug_info(self.player.position).into()
@@ -367,6 +422,7 @@ impl GameState {
                 id,
                 phase,
                 phase_time_remaining,
+                rules,
             } => {
                 log::info!(
                     "Connected with ID: {}, phase: {:?}, time: {}",
@@ -374,15 +430,63 @@ impl GameState {
                     phase,
                     phase_time_remaining
                 );
+                if self.local_peer_id.is_some() {
+                    // Our old slot expired while we were away, so we are a
+                    // new client to everyone; peers get announced again
+                    self.remote_players.clear();
+                }
                 self.local_peer_id = Some(id);
                 game_ui::update_peer_id(id);
-                let actual_phase = if phase == GamePhase::Playing {
+                game_ui::show_rules(
+                    rules.min_players,
+                    rules.grace_period,
+                    rules.victory_duration,
+                );
+                if phase.is_round_active() {
                     log::info!("Joined mid-game, entering spectator mode");
-                    GamePhase::Spectating
-                } else {
-                    phase
-                };
-                self.set_phase(actual_phase, phase_time_remaining);
+                    self.spectating = true;
+                }
+                self.set_phase(phase, phase_time_remaining, false);
+                self.update_player_count_display();
+            }
+            NetworkEvent::Reconnected {
+                phase,
+                phase_time_remaining,
+                alive,
+                kills,
+                peers,
+            } => {
+                log::info!(
+                    "Reconnected, phase: {:?}, time: {}, alive: {}, {} peers",
+                    phase,
+                    phase_time_remaining,
+                    alive,
+                    peers.len()
+                );
+                // Keep names and scores of peers who stayed; pick up new ones
+                self.remote_players.retain(|&id, _| {
+                    if is_debug_mannequin(id) {
+                        peers.is_empty()
+                    } else {
+                        peers.contains(&id)
+                    }
+                });
+                for id in peers {
+                    self.remote_players
+                        .entry(id)
+                        .or_insert_with(RemotePlayer::new);
+                }
+
+                // A kill confirmed just before the drop may never have reached us
+                self.local_kills = self.local_kills.max(kills);
+
+                // The round may have turned over while we were away
+                if alive && self.is_dead && phase.is_round_active() {
+                    self.reset_round();
+                } else if !alive && !self.is_dead && phase.is_round_active() {
+                    self.spectating = true;
+                }
+                self.set_phase(phase, phase_time_remaining, false);
                 self.update_player_count_display();
             }
             NetworkEvent::PeerJoined { id } => {
@@ -391,9 +495,10 @@ impl GameState {
                     id,
                     self.remote_players.keys().collect::<Vec<_>>()
                 );
-                self.remote_players.remove(&u64::MAX);
-                self.remote_players.remove(&(u64::MAX - 1));
-                let remote = RemotePlayer::new();
+                self.remote_players.retain(|&id, _| !is_debug_mannequin(id));
+                let mut remote = RemotePlayer::new();
+                // Like us, players joining mid-round spectate until the next one
+                remote.is_alive = !self.phase.is_round_active();
                 log::info!(
                     "Created RemotePlayer for peer {} at pos=[{:.1}, {:.1}, {:.1}], is_alive={}",
                     id,
@@ -410,36 +515,61 @@ impl GameState {
                 self.remote_players.remove(&id);
                 self.update_player_count_display();
             }
+            NetworkEvent::PeerConnectionLost { id } => {
+                log::warn!("Lost connection to peer {}", id);
+                if let Some(remote) = self.remote_players.get_mut(&id) {
+                    remote.connection_lost = true;
+                }
+            }
+            NetworkEvent::PeerConnectionRestored { id } => {
+                log::info!("Connection to peer {} restored", id);
+                if let Some(remote) = self.remote_players.get_mut(&id) {
+                    remote.connection_lost = false;
+                }
+            }
             NetworkEvent::GamePhaseChanged {
                 phase,
                 time_remaining,
+                draw,
             } => {
                 log::info!(
                     "Game phase changed to {:?}, time: {}",
                     phase,
                     time_remaining
                 );
-                let actual_phase =
-                    if self.phase == GamePhase::Spectating && phase == GamePhase::Playing {
-                        GamePhase::Spectating
-                    } else {
-                        phase
-                    };
-                self.set_phase(actual_phase, time_remaining);
+                self.set_phase(phase, time_remaining, draw);
                 self.update_player_count_display();
             }
             NetworkEvent::PlayerState {
                 id,
+                seq,
+                sent_at,
                 position,
                 yaw,
                 pitch,
                 mask,
             } => {
                 if let Some(remote) = self.remote_players.get_mut(&id) {
-                    remote.update_position(position, 0.05);
-                    remote.yaw = yaw;
-                    remote.pitch = pitch;
-                    remote.mask = MaskType::from_u8(mask);
+                    if remote.push_snapshot(seq, sent_at, position, yaw, pitch) {
+                        remote.mask = MaskType::from_u8(mask);
+
+                        // Dead players and spectators move freely, so only
+                        // players in the round are held to the rules
+                        if remote.is_alive && self.phase.is_round_active() {
+                            if let Some(reason) =
+                                remote.check_movement(&self.physics, position, sent_at, mask)
+                            {
+                                log::warn!(
+                                    "Flagging peer {} for implausible movement ({:?})",
+                                    id,
+                                    reason
+                                );
+                                self.pending_reports.push((id, reason));
+                            }
+                        } else {
+                            remote.movement.reset();
+                        }
+                    }
                 } else {
                     log::warn!(
                         "PlayerState for unknown peer {}, known peers: {:?}",
@@ -489,6 +619,44 @@ impl GameState {
                 self.pending_death_sounds += 1;
                 self.update_player_count_display();
             }
+            NetworkEvent::KillRejected { victim_id, reason } => {
+                log::warn!("Server rejected our kill of {} ({:?})", victim_id, reason);
+                // Combat marked the victim dead optimistically; bring them back
+                if let Some(remote) = self.remote_players.get_mut(&victim_id) {
+                    remote.is_alive = true;
+                    remote.targeted_time = 0.0;
+                }
+                self.update_player_count_display();
+
+                let victim_name = self.resolve_player_name(victim_id, local_peer_id);
+                let explanation = match reason {
+                    KillRejectReason::KilledFirst => format!(
+                        "Your kill on {} was overturned: their stare finished first",
+                        victim_name
+                    ),
+                    KillRejectReason::KillerDead => format!(
+                        "Your kill on {} came too late: you were already dead",
+                        victim_name
+                    ),
+                    KillRejectReason::Invalid => return,
+                };
+                if self.is_dead {
+                    game_ui::show_death_detail(&explanation);
+                } else {
+                    game_ui::show_notice(&explanation);
+                    self.notice_timer = SERVER_NOTICE_SECONDS;
+                }
+            }
+            NetworkEvent::ServerNotice { message } => {
+                game_ui::show_notice(&message);
+                self.notice_timer = SERVER_NOTICE_SECONDS;
+            }
+            NetworkEvent::Kicked { reason } => {
+                log::warn!("Kicked from the server: {}", reason);
+                self.remote_players.clear();
+                self.update_player_count_display();
+                game_ui::show_kicked(&reason);
+            }
             NetworkEvent::PeerIntroduction { id, name } => {
                 log::info!("Peer {} is named '{}'", id, name);
                 if let Some(remote) = self.remote_players.get_mut(&id) {
@@ -532,10 +700,25 @@ impl GameState {
     // Phase transitions
     // -----------------------------------------------------------------------
 
-    fn set_phase(&mut self, phase: GamePhase, time_remaining: f32) {
+    fn set_phase(&mut self, phase: GamePhase, time_remaining: f32, draw: bool) {
         let old_phase = self.phase;
         self.phase = phase;
         self.phase_timer = time_remaining;
+        self.phase_ends_at = Instant::now() + Duration::from_secs_f32(time_remaining);
+
+        // Mid-round joiners only spectate until the round they walked into ends
+        if !phase.is_round_active() {
+            self.spectating = false;
+        }
+
+        // A zero duration means the round has no time limit
+        match phase {
+            GamePhase::Playing if time_remaining > 0.0 => {
+                game_ui::show_round_timer(time_remaining.ceil() as u32, false)
+            }
+            GamePhase::SuddenDeath => game_ui::show_round_timer(time_remaining.ceil() as u32, true),
+            _ => game_ui::hide_round_timer(),
+        }
 
         match phase {
             GamePhase::WaitingForPlayers => {
@@ -551,19 +734,19 @@ impl GameState {
                 game_ui::hide_waiting();
                 game_ui::show_countdown_timer(time_remaining.ceil() as u32);
             }
-            GamePhase::Playing => {
+            GamePhase::Playing | GamePhase::SuddenDeath if self.spectating => {
+                game_ui::hide_countdown();
+                game_ui::hide_round_end();
+                game_ui::hide_death();
+                game_ui::show_spectating();
+            }
+            GamePhase::Playing | GamePhase::SuddenDeath => {
                 game_ui::hide_countdown();
                 game_ui::hide_waiting();
                 game_ui::hide_spectating();
             }
             GamePhase::Victory => {
-                self.enter_victory();
-            }
-            GamePhase::Spectating => {
-                game_ui::hide_countdown();
-                game_ui::hide_round_end();
-                game_ui::hide_death();
-                game_ui::show_spectating();
+                self.enter_victory(draw);
             }
         }
     }
@@ -578,14 +761,17 @@ impl GameState {
         for remote in self.remote_players.values_mut() {
             remote.is_alive = true;
             remote.targeted_time = 0.0;
+            remote.violations = 0;
         }
         game_ui::hide_death();
         game_ui::hide_round_end();
         game_ui::hide_spectating();
     }
 
-    fn enter_victory(&mut self) {
-        if !self.is_dead {
+    fn enter_victory(&mut self, draw: bool) {
+        if draw {
+            self.winner_id = None;
+        } else if !self.is_dead {
             self.winner_id = self.local_peer_id;
         } else {
             self.winner_id = self
@@ -627,9 +813,10 @@ impl GameState {
             survivor_name = Some(local_name);
         }
 
-        scores.sort_by(|a, b| b.kills.cmp(&a.kills));
+        scores.sort_by_key(|entry| std::cmp::Reverse(entry.kills));
 
         let outcome = game_ui::RoundOutcome {
+            draw,
             local_survived,
             survivor_name,
             scores,
@@ -649,13 +836,16 @@ impl GameState {
             GamePhase::GracePeriod => {
                 game_ui::update_countdown_timer(self.phase_timer.ceil() as u32)
             }
+            GamePhase::Playing | GamePhase::SuddenDeath => {
+                game_ui::update_round_timer(self.phase_timer.ceil() as u32)
+            }
             GamePhase::Victory => game_ui::update_round_end_timer(self.phase_timer.ceil() as u32),
             _ => {}
         }
     }
 
     fn update_player_count_display(&self) {
-        let is_real_player = |id: u64| id != u64::MAX && id != u64::MAX - 1;
+        let is_real_player = |id: PeerId| !is_debug_mannequin(id);
 
         let remote_alive = self
             .remote_players
@@ -668,7 +858,7 @@ impl GameState {
             .filter(|(id, p)| is_real_player(**id) && !p.is_alive)
             .count();
 
-        let is_spectating = self.phase == GamePhase::Spectating;
+        let is_spectating = self.spectating;
         let local_alive = if !self.is_dead && !is_spectating {
             1
         } else {
@@ -684,39 +874,82 @@ impl GameState {
         );
     }
 
-    /// Update peer connection stats if enough time has passed
-    pub fn update_peer_stats(&mut self, network: &NetworkClient) {
-        const STATS_UPDATE_INTERVAL_SECS: f32 = 2.0;
+    /// Update peer connection stats when the network scheduler says so
+    pub fn update_peer_stats(&mut self, network: &mut NetworkClient) {
+        for (peer_id, &rtt_ms) in self.peer_rtts.borrow().iter() {
+            if let Some(remote) = self.remote_players.get_mut(peer_id) {
+                remote.rtt_ms = Some(rtt_ms);
+            }
+        }
 
-        if self.last_stats_update.elapsed().as_secs_f32() >= STATS_UPDATE_INTERVAL_SECS {
-            self.last_stats_update = Instant::now();
+        if network.stats_due() {
+            if network.topology() == Topology::Star {
+                self.update_star_peer_stats(network);
+                return;
+            }
 
             let peer_connections = network.get_peer_connections();
             if peer_connections.is_empty() {
-                update_peer_stats_display(&[]);
+                self.stats_history.borrow_mut().record(&[]);
+                update_peer_stats_display(&[], &self.stats_history.borrow());
                 return;
             }
 
             let peer_data: Vec<_> = peer_connections
                 .into_iter()
                 .map(|(peer_id, pc)| {
-                    let name = self
-                        .remote_players
-                        .get(&peer_id)
-                        .and_then(|p| p.name.clone());
-                    (peer_id, name, pc)
+                    let remote = self.remote_players.get(&peer_id);
+                    let name = remote.and_then(|p| p.name.clone());
+                    let suspicion = remote.and_then(|p| p.suspicion);
+                    let reception = network.state_reception(peer_id);
+                    (peer_id, name, suspicion, reception, pc)
                 })
                 .collect();
 
+            let peer_rtts = self.peer_rtts.clone();
+            let stats_history = self.stats_history.clone();
             wasm_bindgen_futures::spawn_local(async move {
                 let mut all_stats = Vec::new();
-                for (peer_id, name, pc) in peer_data {
-                    if let Some(stats) = fetch_peer_stats(peer_id, name, pc).await {
+                for (peer_id, name, suspicion, reception, pc) in peer_data {
+                    if let Some(mut stats) = fetch_peer_stats(peer_id, name, pc).await {
+                        stats.suspicion = suspicion;
+                        stats.state_reception = reception;
                         all_stats.push(stats);
                     }
                 }
-                update_peer_stats_display(&all_stats);
+                *peer_rtts.borrow_mut() = all_stats
+                    .iter()
+                    .filter_map(|stats| Some((stats.peer_id, stats.rtt_ms?)))
+                    .collect();
+                let mut stats_history = stats_history.borrow_mut();
+                stats_history.record(&all_stats);
+                update_peer_stats_display(&all_stats, &stats_history);
             });
         }
     }
+
+    /// Show what we know about peers in a star room, where there are no
+    /// peer connections to ask: only how well their state reaches us.
+    fn update_star_peer_stats(&self, network: &mut NetworkClient) {
+        let mut all_stats: Vec<PeerStats> = self
+            .remote_players
+            .iter()
+            .filter(|(id, _)| !is_debug_mannequin(**id))
+            .map(|(&peer_id, remote)| {
+                let mut stats = PeerStats::through_server(peer_id, remote.name.clone());
+                stats.suspicion = remote.suspicion;
+                stats.state_reception = network.state_reception(peer_id);
+                stats
+            })
+            .collect();
+        all_stats.sort_by_key(|stats| stats.peer_id);
+        let mut stats_history = self.stats_history.borrow_mut();
+        stats_history.record(&all_stats);
+        update_peer_stats_display(&all_stats, &stats_history);
+    }
+}
+
+/// Debug mannequins use the two highest peer IDs, which the server never hands out.
+fn is_debug_mannequin(id: PeerId) -> bool {
+    id >= u64::MAX - 1
 }
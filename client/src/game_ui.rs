@@ -1,9 +1,9 @@
 use base64::Engine;
+use gameplay::mask::MaskType;
 use glam::Vec3;
 
 use crate::assets::{COWARD_IMAGE, GHOST_IMAGE, HUNTER_IMAGE};
 use crate::network::PeerId;
-use crate::player::MaskType;
 
 // ---------------------------------------------------------------------------
 // Core helpers
@@ -67,9 +67,29 @@ pub fn hide_round_end() {
 // Data-driven overlays
 // ---------------------------------------------------------------------------
 
+pub fn show_notice(message: &str) {
+    set_text("server-notice", message);
+    set_visible("server-notice", true);
+}
+pub fn hide_notice() {
+    set_visible("server-notice", false);
+}
+
+pub fn show_kicked(reason: &str) {
+    set_text("kicked-reason", reason);
+    set_visible("kicked-overlay", true);
+}
+
 pub fn show_death(killer_name: Option<&str>) {
     set_visible("death-overlay", true);
     set_text("killer-id", killer_name.unwrap_or("Unknown"));
+    set_visible("death-detail", false);
+}
+
+/// Explain under the death screen what happened to our own kill.
+pub fn show_death_detail(detail: &str) {
+    set_text("death-detail", detail);
+    set_visible("death-detail", true);
 }
 
 pub fn show_countdown_timer(seconds: u32) {
@@ -81,16 +101,62 @@ pub fn update_countdown_timer(seconds: u32) {
     set_text("countdown-timer", &seconds.to_string());
 }
 
+/// Show the round clock; in sudden death it turns into the draw countdown.
+pub fn show_round_timer(seconds: u32, sudden_death: bool) {
+    if let Some(doc) = doc()
+        && let Some(el) = doc.get_element_by_id("round-timer")
+    {
+        let class = if sudden_death { "sudden-death" } else { "" };
+        let _ = el.set_attribute("class", class);
+        let _ = el.set_attribute("style", "display: block;");
+    }
+    set_text(
+        "round-timer-label",
+        if sudden_death {
+            "SUDDEN DEATH"
+        } else {
+            "Round"
+        },
+    );
+    update_round_timer(seconds);
+}
+
+pub fn update_round_timer(seconds: u32) {
+    set_text(
+        "round-timer-value",
+        &format!("{}:{:02}", seconds / 60, seconds % 60),
+    );
+}
+
+pub fn hide_round_timer() {
+    set_visible("round-timer", false);
+}
+
 pub fn update_round_end_timer(seconds: u32) {
     set_text("victory-countdown", &seconds.to_string());
 }
 
+/// Fill the overlays' static text with the server's round rules.
+pub fn show_rules(min_players: u32, grace_period: f32, victory_duration: f32) {
+    set_text(
+        "waiting-hint",
+        &format!("Need at least {} players to start...", min_players),
+    );
+    set_text("countdown-timer", &(grace_period.ceil() as u32).to_string());
+    set_text(
+        "victory-countdown",
+        &(victory_duration.ceil() as u32).to_string(),
+    );
+}
+
 // ---------------------------------------------------------------------------
 // Complex rendering
 // ---------------------------------------------------------------------------
 
 /// Data for the end-of-round overlay.
 pub struct RoundOutcome {
+    /// Sudden death ran out with several players alive.
+    pub draw: bool,
     pub local_survived: bool,
     pub survivor_name: Option<String>,
     pub scores: Vec<ScoreEntry>,
@@ -107,39 +173,53 @@ pub struct ScoreEntry {
 pub fn show_round_end(outcome: &RoundOutcome) {
     let Some(doc) = doc() else { return };
 
+    let color = if outcome.draw {
+        "#f9e2af"
+    } else if outcome.local_survived {
+        "#a6e3a1"
+    } else {
+        "#f38ba8"
+    };
+
     if let Some(overlay) = doc.get_element_by_id("victory-overlay") {
-        let border_color = if outcome.local_survived {
-            "#a6e3a1"
-        } else {
-            "#f38ba8"
-        };
         let _ = overlay.set_attribute(
             "style",
-            &format!("display: block; border-color: {};", border_color),
+            &format!("display: block; border-color: {};", color),
         );
     }
     if let Some(title) = doc.get_element_by_id("victory-title") {
-        let _ = title.set_attribute(
-            "style",
-            if outcome.local_survived {
-                "color: #a6e3a1;"
-            } else {
-                "color: #f38ba8;"
-            },
-        );
-        title.set_text_content(Some(if outcome.local_survived {
+        let _ = title.set_attribute("style", &format!("color: {};", color));
+        title.set_text_content(Some(if outcome.draw {
+            "DRAW"
+        } else if outcome.local_survived {
             "YOU SURVIVED!"
         } else {
             "YOU DIED"
         }));
     }
     if let Some(subtitle) = doc.get_element_by_id("victory-subtitle") {
-        match &outcome.survivor_name {
-            Some(name) if !outcome.local_survived => {
-                subtitle.set_text_content(Some(&format!("{} survived", name)));
+        let text = if outcome.draw {
+            let survivors: Vec<&str> = outcome
+                .scores
+                .iter()
+                .filter(|entry| entry.is_survivor)
+                .map(|entry| entry.name.as_str())
+                .collect();
+            Some(format!("Time ran out: {} survived", survivors.join(", ")))
+        } else if outcome.local_survived {
+            None
+        } else {
+            outcome
+                .survivor_name
+                .as_ref()
+                .map(|name| format!("{} survived", name))
+        };
+        match text {
+            Some(text) => {
+                subtitle.set_text_content(Some(&text));
                 let _ = subtitle.set_attribute("style", "display: block;");
             }
-            _ => {
+            None => {
                 let _ = subtitle.set_attribute("style", "display: none;");
             }
         }
@@ -9,7 +9,6 @@ use winit::{
 
 mod assets;
 mod audio;
-mod collision;
 mod combat;
 mod config;
 mod debug;
@@ -25,7 +24,7 @@ mod render;
 
 use assets::EMBEDDED_MAP;
 use audio::Audio;
-use config::{AFK_TIMEOUT_SECONDS, DEBUG_MANNEQUINS};
+use config::{AFK_TIMEOUT_SECONDS, DEBUG_MANNEQUINS, NETWORK_TICK_RATE};
 use debug::DebugOverlay;
 use game::{GameState, init_mask_images};
 use glb::load_mesh_from_bytes;
@@ -114,7 +113,7 @@ impl ApplicationHandler for App {
 
         wasm_bindgen_futures::spawn_local(async move {
             let renderer = Renderer::new(window_clone.clone(), &map_mesh).await;
-            let game = GameState::new(&map_mesh, DEBUG_MANNEQUINS);
+            let game = GameState::new(DEBUG_MANNEQUINS);
             let input = InputState::new();
 
             let audio = Audio::new();
@@ -265,12 +264,16 @@ impl ApplicationHandler for App {
                                 network.send_kill(victim_id);
                             }
 
+                            for (peer_id, reason) in state.game.take_pending_reports() {
+                                network.report_peer(peer_id, reason);
+                            }
+
                             if state.game.take_death_notification() {
                                 network.notify_death();
                             }
 
-                            if network.is_connected() && !state.game.is_dead {
-                                network.send_player_state(
+                            if !state.game.is_dead {
+                                network.update_player_state(
                                     state.game.player.position,
                                     state.game.player.yaw,
                                     state.game.player.pitch,
@@ -278,7 +281,7 @@ impl ApplicationHandler for App {
                                 );
                             }
 
-                            network.send_introduction();
+                            network.tick();
 
                             state.game.update_peer_stats(network);
                         }
@@ -299,10 +302,17 @@ impl ApplicationHandler for App {
 
                         // --- Debug display update ---
                         let physics_debug = state.game.get_physics_debug();
+                        let lag_compensation = state.game.get_lag_compensation_info();
+                        let net_sim = state
+                            .network
+                            .as_ref()
+                            .and_then(|network| network.network_simulator());
                         state.debug.update_display(
                             state.game.player.position,
                             state.game.player.velocity,
                             &physics_debug,
+                            &lag_compensation,
+                            net_sim,
                         );
 
                         state.input.end_frame();
@@ -425,7 +435,7 @@ fn start_game() {
         if let Some(state) = s.borrow_mut().as_mut() {
             state.player_name = Some(name.clone());
             state.game.set_local_name(name.clone());
-            if let Ok(network) = NetworkClient::new(name) {
+            if let Ok(network) = NetworkClient::new(name, NETWORK_TICK_RATE) {
                 state.network = Some(network);
             }
         }
@@ -1,26 +1,18 @@
-//! ICE server configuration and TURN credential fetching.
+//! Server URLs and settings taken from the page location.
 //!
-//! Handles discovery and configuration of STUN/TURN servers:
-//! - Self-hosted STUN server (based on current hostname)
-//! - Self-hosted TURN server (credentials from /turn-credentials)
-//! - Google STUN servers (fallback)
-//! - Metered.ca TURN servers (fallback)
+//! The STUN/TURN servers to use come from the game server's `/ice-servers`
+//! list, which its operator configures; `?ice=` asks it for a different ICE
+//! policy when debugging connectivity.
 
-use super::protocol::PeerId;
+use std::cell::RefCell;
+
+use super::protocol::{IcePolicy, IceServer, IceServerList, PeerId};
+use super::ui::{NetLogLevel, net_log};
+use session::NetworkConditions;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::Url;
-
-/// ICE server configuration.
-#[derive(Debug, Clone)]
-pub struct IceServer {
-    pub urls: Vec<String>,
-    pub username: Option<String>,
-    pub credential: Option<String>,
-}
-
-/// Metered.ca TURN server API endpoint.
-const METERED_API_URL: &str = "https://ggj26.metered.live/api/v1/turn/credentials?apiKey=eb7440a97d22d69b25dfe8b64bbb3c79642f";
+use web_time::{Duration, Instant};
 
 /// Get the signaling server WebSocket URL based on current page location.
 pub fn signaling_server_url() -> String {
@@ -38,178 +30,114 @@ pub fn signaling_server_url() -> String {
     "wss://localhost/ws".to_string()
 }
 
-/// Get base ICE servers (STUN only, no credentials needed).
-pub fn base_ice_servers() -> Vec<IceServer> {
-    let mut servers = vec![
-        // Google STUN servers for NAT discovery
-        IceServer {
-            urls: vec![
-                "stun:stun.l.google.com:19302".to_string(),
-                "stun:stun1.l.google.com:19302".to_string(),
-            ],
-            username: None,
-            credential: None,
-        },
-    ];
-
-    // Add server's own STUN if not localhost
-    if let Some(window) = web_sys::window() {
-        let location = window.location();
-        if let Ok(href) = location.href()
-            && let Ok(url) = Url::new(&href)
-        {
-            let hostname = url.hostname();
-            if !hostname.is_empty() && hostname != "localhost" {
-                servers.insert(
-                    0,
-                    IceServer {
-                        urls: vec![format!("stun:{}:3478", hostname)],
-                        username: None,
-                        credential: None,
-                    },
-                );
-            }
+/// Get the room code from the `?room=` query parameter, if present.
+pub fn room_code_from_url() -> Option<String> {
+    let href = web_sys::window()?.location().href().ok()?;
+    let url = Url::new(&href).ok()?;
+    url.search_params()
+        .get("room")
+        .filter(|room| !room.trim().is_empty())
+}
+
+/// Get network conditions to simulate from the `?netsim=` query parameter
+/// (`delay:120,jitter:40,state.drop:10`; see `NetworkConditions`), if set.
+pub fn network_conditions_from_url() -> Option<NetworkConditions> {
+    let href = web_sys::window()?.location().href().ok()?;
+    let url = Url::new(&href).ok()?;
+    let value = url.search_params().get("netsim")?;
+    match value.parse() {
+        Ok(conditions) => Some(conditions),
+        Err(e) => {
+            log::warn!("Ignoring ?netsim={}: {}", value, e);
+            None
         }
     }
-    servers
 }
 
-/// Fetch TURN credentials from our own server.
-pub async fn fetch_self_hosted_turn() -> Option<IceServer> {
-    let window = web_sys::window()?;
-    let location = window.location();
-    let href = location.href().ok()?;
+/// Get the ICE policy to ask the server for from the `?ice=` query
+/// parameter (`all`, `relay-only` or `stun-only`), if set.
+pub fn ice_policy_from_url() -> Option<IcePolicy> {
+    let href = web_sys::window()?.location().href().ok()?;
     let url = Url::new(&href).ok()?;
-    let hostname = url.hostname();
+    let value = url.search_params().get("ice")?;
+    match value.parse() {
+        Ok(policy) => Some(policy),
+        Err(e) => {
+            log::warn!("Ignoring ?ice={}: {}", value, e);
+            None
+        }
+    }
+}
 
-    if hostname.is_empty() || hostname == "localhost" {
-        return None;
+thread_local! {
+    /// The last list the server gave us, and when.
+    static CACHED_LIST: RefCell<Option<(IceServerList, Instant)>> = const { RefCell::new(None) };
+}
+
+/// Get the ICE servers to connect to a peer with, as published by the
+/// server at `/ice-servers`.
+///
+/// The list is reused for half its TTL, so that the credentials in it stay
+/// valid well into the life of the connection. If the server can't be
+/// reached, the list is empty and only direct connections work.
+pub async fn fetch_ice_servers(peer_id: PeerId) -> IceServerList {
+    let cached = CACHED_LIST.with_borrow(|cached| {
+        cached
+            .as_ref()
+            .filter(|(list, fetched_at)| fetched_at.elapsed() < Duration::from_secs(list.ttl) / 2)
+            .map(|(list, _)| list.clone())
+    });
+    if let Some(list) = cached {
+        return list;
     }
 
-    let protocol = url.protocol();
-    let creds_url = format!("{}//{}/turn-credentials", protocol, url.host());
-
-    match JsFuture::from(window.fetch_with_str(&creds_url)).await {
-        Ok(resp) => {
-            let resp: web_sys::Response = resp.unchecked_into();
-            if resp.ok()
-                && let Ok(json) = JsFuture::from(resp.json().unwrap()).await
-                && js_sys::Array::is_array(&json)
-            {
-                let arr = js_sys::Array::from(&json);
-                let first = arr.get(0);
-                if !first.is_undefined()
-                    && let Some(urls) = js_sys::Reflect::get(&first, &"urls".into())
-                        .ok()
-                        .and_then(|v| v.as_string())
-                {
-                    let username = js_sys::Reflect::get(&first, &"username".into())
-                        .ok()
-                        .and_then(|v| v.as_string());
-                    let credential = js_sys::Reflect::get(&first, &"credential".into())
-                        .ok()
-                        .and_then(|v| v.as_string());
-
-                    // Replace {host} placeholder with actual hostname
-                    let urls = urls.replace("{host}", &hostname);
-
-                    let server = IceServer {
-                        urls: vec![urls],
-                        username,
-                        credential,
-                    };
-                    log::info!("Fetched self-hosted TURN: {:?}", server.urls);
-                    return Some(server);
-                }
-            }
+    match fetch_server_list().await {
+        Ok(list) => {
+            net_log(
+                NetLogLevel::Success,
+                &format!(
+                    "Peer {}: Got {} ICE servers ({})",
+                    peer_id,
+                    list.ice_servers.len(),
+                    list.policy
+                ),
+            );
+            CACHED_LIST.set(Some((list.clone(), Instant::now())));
+            list
         }
         Err(e) => {
-            log::debug!("Self-hosted TURN fetch failed: {:?}", e);
+            log::warn!("ICE server list fetch failed: {}", e);
+            net_log(
+                NetLogLevel::Error,
+                &format!("Peer {}: No ICE servers, direct connections only", peer_id),
+            );
+            IceServerList::default()
         }
     }
-    None
 }
 
-/// Fetch all available TURN servers (self-hosted + Metered.ca fallback).
-pub async fn fetch_turn_servers(peer_id: PeerId) -> Vec<IceServer> {
-    let mut servers = base_ice_servers();
-
-    super::ui::net_log(
-        super::ui::NetLogLevel::Info,
-        &format!("Peer {}: Fetching TURN credentials...", peer_id),
-    );
-
-    // First try self-hosted TURN
-    if let Some(self_hosted) = fetch_self_hosted_turn().await {
-        servers.push(self_hosted);
-        super::ui::net_log(
-            super::ui::NetLogLevel::Success,
-            &format!("Peer {}: Got self-hosted TURN", peer_id),
-        );
+async fn fetch_server_list() -> Result<IceServerList, String> {
+    let window = web_sys::window().ok_or("no window")?;
+    let href = window.location().href().map_err(|e| format!("{:?}", e))?;
+    let url = Url::new(&href).map_err(|e| format!("{:?}", e))?;
+    let mut list_url = format!("{}//{}/ice-servers", url.protocol(), url.host());
+    if let Some(policy) = ice_policy_from_url() {
+        list_url.push_str(&format!("?policy={}", policy));
     }
 
-    // Then fetch TURN credentials from Metered.ca API as fallback
-    if let Some(window) = web_sys::window() {
-        match JsFuture::from(window.fetch_with_str(METERED_API_URL)).await {
-            Ok(resp) => {
-                let resp: web_sys::Response = resp.unchecked_into();
-                if resp.ok() {
-                    if let Ok(json) = JsFuture::from(resp.json().unwrap()).await
-                        && js_sys::Array::is_array(&json)
-                    {
-                        let arr = js_sys::Array::from(&json);
-                        let mut turn_count = 0;
-                        for i in 0..arr.length() {
-                            let server = arr.get(i);
-                            if let Some(urls) = js_sys::Reflect::get(&server, &"urls".into())
-                                .ok()
-                                .and_then(|v| v.as_string())
-                            {
-                                let username = js_sys::Reflect::get(&server, &"username".into())
-                                    .ok()
-                                    .and_then(|v| v.as_string());
-                                let credential =
-                                    js_sys::Reflect::get(&server, &"credential".into())
-                                        .ok()
-                                        .and_then(|v| v.as_string());
-
-                                log::info!(
-                                    "Fetched TURN server: {} (has creds: {})",
-                                    urls,
-                                    username.is_some()
-                                );
-
-                                if urls.starts_with("turn") {
-                                    turn_count += 1;
-                                }
-
-                                servers.push(IceServer {
-                                    urls: vec![urls],
-                                    username,
-                                    credential,
-                                });
-                            }
-                        }
-                        super::ui::net_log(
-                            super::ui::NetLogLevel::Success,
-                            &format!("Peer {}: Got {} fallback TURN servers", peer_id, turn_count),
-                        );
-                    }
-                } else {
-                    let msg = format!("TURN API error: {}", resp.status());
-                    log::warn!("{}", msg);
-                    super::ui::net_log(super::ui::NetLogLevel::Error, &msg);
-                }
-            }
-            Err(e) => {
-                let msg = format!("TURN fetch failed: {:?}", e);
-                log::warn!("{}", msg);
-                super::ui::net_log(super::ui::NetLogLevel::Error, "TURN fetch failed");
-            }
-        }
+    let resp = JsFuture::from(window.fetch_with_str(&list_url))
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+    let resp: web_sys::Response = resp.unchecked_into();
+    if !resp.ok() {
+        return Err(format!("HTTP {}", resp.status()));
     }
-
-    servers
+    let text = JsFuture::from(resp.text().map_err(|e| format!("{:?}", e))?)
+        .await
+        .map_err(|e| format!("{:?}", e))?
+        .as_string()
+        .ok_or("response is not text")?;
+    serde_json::from_str(&text).map_err(|e| e.to_string())
 }
 
 /// Convert ICE servers to a JS array for RtcConfiguration.
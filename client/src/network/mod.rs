@@ -8,8 +8,8 @@
 //! ```text
 //! ┌─────────────────────────────────────────┐
 //! │  Game Code (game.rs)                    │
-//! │  - Calls network.broadcast_state()      │
-//! │  - Polls network.poll()                 │
+//! │  - Calls network.update_player_state()  │
+//! │  - Polls network.poll_events()          │
 //! └───────────────────┬─────────────────────┘
 //!                     │
 //! ┌───────────────────▼─────────────────────┐
@@ -19,12 +19,14 @@
 //! └───────────────────┬─────────────────────┘
 //!                     │
 //! ┌───────────────────▼─────────────────────┐
-//! │  Session (session.rs)                   │
+//! │  Session (session crate)                │
 //! │  - Manages peer collection              │
 //! │  - Handles signaling protocol           │
 //! └───────────────────┬─────────────────────┘
 //!                     │
 //! ┌───────────────────▼─────────────────────┐
+//! │  Signaling (signaling.rs)               │
+//! │  - WebSocket to the server              │
 //! │  Transport (transport.rs)               │
 //! │  - WebRTC peer connections              │
 //! │  - Data channels                        │
@@ -41,34 +43,46 @@
 //! for event in network.poll() {
 //!     match event {
 //!         NetworkEvent::PlayerState { id, position, .. } => { /* update remote player */ }
-//!         NetworkEvent::PlayerKilled { killer_id, victim_id } => { /* handle kill */ }
+//!         NetworkEvent::PlayerKilled { killer_id, victim_id } => { /* server-confirmed kill */ }
 //!         // ...
 //!     }
 //! }
 //!
-//! // Send state
-//! network.broadcast_state(position, yaw, pitch, mask);
+//! // Hand over our latest state, then let the scheduler send what's due
+//! network.update_player_state(position, yaw, pitch, mask);
+//! network.tick();
 //! network.send_kill(victim_id);
 //! ```
 
 mod ice;
 mod protocol;
-mod queue;
-mod session;
+mod schedule;
 mod signaling;
 mod stats;
 mod transport;
 mod ui;
 
+use std::collections::HashMap;
+
 use glam::Vec3;
 use web_sys::RtcPeerConnection;
+use web_time::{Duration, Instant};
 
-use protocol::{ChannelKind, GameMessage, StateUpdate};
-use session::{Session, SessionEvent};
+use self::protocol::{ChannelKind, GameMessage, StatePacket};
+use schedule::Interval;
+use session::{
+    NetworkSimulator, Payload, PeerTransport, ReceptionQuality, Session, SessionEvent,
+    StateReception,
+};
+use signaling::SignalingClient;
+use transport::WebRtcConnector;
+use ui::{NetLogLevel, net_log};
 
 // Re-export public types
-pub use protocol::{GamePhase, PeerId};
-pub use stats::{fetch_peer_stats, update_peer_stats_display};
+pub use self::protocol::{
+    GamePhase, GameRules, KillRejectReason, MovementViolation, PeerId, Topology,
+};
+pub use stats::{PeerStats, StatsHistory, fetch_peer_stats, update_peer_stats_display};
 
 /// Events emitted by the network layer for game code to handle.
 #[derive(Clone, Debug)]
@@ -78,31 +92,66 @@ pub enum NetworkEvent {
         id: PeerId,
         phase: GamePhase,
         phase_time_remaining: f32,
+        rules: GameRules,
+    },
+    /// Reclaimed our slot after the server connection dropped.
+    ///
+    /// `peers` lists everyone currently in the room, including peers that
+    /// joined while we were away; anyone else has left.
+    Reconnected {
+        phase: GamePhase,
+        phase_time_remaining: f32,
+        alive: bool,
+        kills: u32,
+        peers: Vec<PeerId>,
     },
     /// A peer joined the game.
     PeerJoined { id: PeerId },
     /// A peer left the game.
     PeerLeft { id: PeerId },
+    /// We can't reach a peer any more; their state stops updating until
+    /// `PeerConnectionRestored`.
+    PeerConnectionLost { id: PeerId },
+    /// A peer we had lost is reachable again.
+    PeerConnectionRestored { id: PeerId },
     /// Game phase changed (from server).
     GamePhaseChanged {
         phase: GamePhase,
+        /// Seconds until the phase ends by the server clock, as of now; 0 if
+        /// it has no time limit.
         time_remaining: f32,
+        /// The round ended in a draw (only set when entering `Victory`).
+        draw: bool,
     },
     /// Received player state update from a peer.
     PlayerState {
         id: PeerId,
+        /// The sender's packet counter, wrapping.
+        seq: u16,
+        /// When the sender sampled this state, in seconds on the server clock
+        /// as the sender estimates it.
+        sent_at: f64,
         position: Vec3,
         yaw: f32,
         pitch: f32,
         mask: u8,
     },
-    /// A player was killed.
+    /// A player was killed (confirmed by the server).
     PlayerKilled {
         killer_id: PeerId,
         victim_id: PeerId,
     },
+    /// The server rejected our claim to have killed this player.
+    KillRejected {
+        victim_id: PeerId,
+        reason: KillRejectReason,
+    },
     /// A peer introduced themselves with their name.
     PeerIntroduction { id: PeerId, name: String },
+    /// A notice from the server operators.
+    ServerNotice { message: String },
+    /// The server removed us from the game; we won't reconnect.
+    Kicked { reason: String },
 }
 
 /// Main network client for game code.
@@ -112,28 +161,69 @@ pub enum NetworkEvent {
 /// - Sending game events (kills, introductions)
 /// - Receiving events from peers and the server
 pub struct NetworkClient {
-    session: Session,
+    session: Session<SignalingClient, WebRtcConnector>,
     player_name: String,
-    /// Counts down each frame; when it hits 0 we re-broadcast our introduction.
-    introduction_timer: u32,
+    /// Our state from the current frame, sent on the next state tick.
+    pending_state: Option<LocalState>,
+    /// Sequence number of the next state packet.
+    state_seq: u16,
+    /// State packets to peers, at the configured tick rate.
+    state_tick: Interval,
+    /// Pose samples to the server for kill validation.
+    pose_tick: Interval,
+    /// Re-broadcasts of our introduction, for peers whose channel opened late.
+    introduction_tick: Interval,
+    /// Connection stats refreshes for the peer panel.
+    stats_tick: Interval,
+    /// Loss and jitter of the state packets from each peer.
+    state_reception: HashMap<PeerId, StateReception>,
+}
+
+/// Our player's state as handed over by the game.
+#[derive(Clone, Copy)]
+struct LocalState {
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+    mask: u8,
 }
 
+/// How often pose samples are sent to the server for kill validation.
+const POSE_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often our introduction is re-broadcast.
+const INTRODUCTION_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often peer connection stats are refreshed.
+const STATS_INTERVAL: Duration = Duration::from_secs(2);
+
 impl NetworkClient {
     /// Create a new network client and connect to the signaling server.
-    pub fn new(player_name: String) -> Result<Self, wasm_bindgen::JsValue> {
-        let session = Session::new(player_name.clone())?;
+    ///
+    /// `tick_rate` is how many state packets per second go to each peer,
+    /// whatever the frame rate.
+    pub fn new(player_name: String, tick_rate: f32) -> Result<Self, wasm_bindgen::JsValue> {
+        let signaling = SignalingClient::connect(player_name.clone())?;
+        let mut session = Session::new(signaling, WebRtcConnector::default()).with_net_log(net_log);
+        if let Some(conditions) = ice::network_conditions_from_url() {
+            net_log(NetLogLevel::Warning, "Simulating a bad network (?netsim)");
+            session = session.with_network_conditions(conditions);
+        }
         Ok(Self {
             session,
             player_name,
-            introduction_timer: 0,
+            pending_state: None,
+            state_seq: 0,
+            state_tick: Interval::from_rate(tick_rate),
+            pose_tick: Interval::new(POSE_SAMPLE_INTERVAL),
+            introduction_tick: Interval::new(INTRODUCTION_INTERVAL),
+            stats_tick: Interval::new(STATS_INTERVAL),
+            state_reception: HashMap::new(),
         })
     }
 
     /// Poll for network events. Call this each frame.
     pub fn poll_events(&mut self) -> Vec<NetworkEvent> {
-        // Process pending async operations
-        self.session.process_pending();
-
         // Poll session for events
         let session_events = self.session.poll();
 
@@ -141,8 +231,11 @@ impl NetworkClient {
         let mut events = Vec::new();
         for event in session_events {
             // Re-broadcast our introduction when a new peer joins
-            if matches!(&event, SessionEvent::PeerJoined { .. }) {
-                self.introduction_timer = 0;
+            if matches!(
+                &event,
+                SessionEvent::PeerJoined { .. } | SessionEvent::Reconnected { .. }
+            ) {
+                self.introduction_tick.trigger();
             }
             if let Some(net_event) = self.translate_event(event) {
                 events.push(net_event);
@@ -152,26 +245,64 @@ impl NetworkClient {
     }
 
     /// Translate a session event to a network event.
-    fn translate_event(&self, event: SessionEvent) -> Option<NetworkEvent> {
+    fn translate_event(&mut self, event: SessionEvent) -> Option<NetworkEvent> {
         match event {
             SessionEvent::Connected {
                 local_id,
                 phase,
                 phase_time_remaining,
+                rules,
             } => Some(NetworkEvent::Connected {
                 id: local_id,
                 phase,
                 phase_time_remaining,
+                rules,
+            }),
+            SessionEvent::Reconnected {
+                phase,
+                phase_time_remaining,
+                alive,
+                kills,
+                peers,
+            } => Some(NetworkEvent::Reconnected {
+                phase,
+                phase_time_remaining,
+                alive,
+                kills,
+                peers,
             }),
             SessionEvent::PeerJoined { peer_id } => Some(NetworkEvent::PeerJoined { id: peer_id }),
-            SessionEvent::PeerLeft { peer_id } => Some(NetworkEvent::PeerLeft { id: peer_id }),
+            SessionEvent::PeerLeft { peer_id } => {
+                self.state_reception.remove(&peer_id);
+                Some(NetworkEvent::PeerLeft { id: peer_id })
+            }
+            SessionEvent::PeerConnectionLost { peer_id } => {
+                Some(NetworkEvent::PeerConnectionLost { id: peer_id })
+            }
+            SessionEvent::PeerConnectionRestored { peer_id } => {
+                Some(NetworkEvent::PeerConnectionRestored { id: peer_id })
+            }
             SessionEvent::PhaseChanged {
                 phase,
                 time_remaining,
+                draw,
             } => Some(NetworkEvent::GamePhaseChanged {
                 phase,
                 time_remaining,
+                draw,
+            }),
+            SessionEvent::PlayerKilled {
+                killer_id,
+                victim_id,
+            } => Some(NetworkEvent::PlayerKilled {
+                killer_id,
+                victim_id,
             }),
+            SessionEvent::KillRejected { victim_id, reason } => {
+                Some(NetworkEvent::KillRejected { victim_id, reason })
+            }
+            SessionEvent::Notice { message } => Some(NetworkEvent::ServerNotice { message }),
+            SessionEvent::Kicked { reason } => Some(NetworkEvent::Kicked { reason }),
             SessionEvent::PeerMessage {
                 from,
                 channel,
@@ -182,36 +313,45 @@ impl NetworkClient {
 
     /// Parse a message received from a peer.
     fn parse_peer_message(
-        &self,
+        &mut self,
         from: PeerId,
         channel: ChannelKind,
-        data: &str,
+        data: &Payload,
     ) -> Option<NetworkEvent> {
-        match channel {
-            ChannelKind::State => {
-                // Parse state update
-                match serde_json::from_str::<StateUpdate>(data) {
-                    Ok(state) => Some(NetworkEvent::PlayerState {
-                        id: from,
-                        position: state.position(),
-                        yaw: state.yaw,
-                        pitch: state.pitch,
-                        mask: state.mask,
-                    }),
+        match (channel, data) {
+            (ChannelKind::State, Payload::Binary(bytes)) => {
+                // Decode state packet
+                match StatePacket::decode(bytes) {
+                    Ok(state) => {
+                        let stride = self.session.state_stride();
+                        self.state_reception
+                            .entry(from)
+                            .or_insert_with(|| StateReception::new(stride))
+                            .record(
+                                state.seq,
+                                state.sent_at_ms as f64,
+                                self.session.server_time(),
+                            );
+                        Some(NetworkEvent::PlayerState {
+                            id: from,
+                            seq: state.seq,
+                            sent_at: state.sent_at_ms as f64 / 1000.0,
+                            position: Vec3::from_array(state.position),
+                            yaw: state.yaw,
+                            pitch: state.pitch,
+                            mask: state.mask,
+                        })
+                    }
                     Err(e) => {
-                        log::warn!("Failed to parse state from peer {}: {}", from, e);
+                        log::warn!("Failed to decode state from peer {}: {}", from, e);
                         None
                     }
                 }
             }
-            ChannelKind::Events => {
+            (ChannelKind::Events, Payload::Text(text)) => {
                 // Parse game event
-                match serde_json::from_str::<GameMessage>(data) {
+                match serde_json::from_str::<GameMessage>(text) {
                     Ok(msg) => match msg {
-                        GameMessage::Kill { victim_id } => Some(NetworkEvent::PlayerKilled {
-                            killer_id: from,
-                            victim_id,
-                        }),
                         GameMessage::Introduction { name } => {
                             Some(NetworkEvent::PeerIntroduction { id: from, name })
                         }
@@ -222,51 +362,122 @@ impl NetworkClient {
                     }
                 }
             }
+            (channel, _) => {
+                // Most likely a peer running an older build
+                log::warn!(
+                    "Unexpected {:?} payload format from peer {}, ignoring",
+                    channel,
+                    from
+                );
+                None
+            }
         }
     }
 
-    /// Broadcast our player name introduction to all connected peers.
+    /// Hand over our state for this frame.
     ///
-    /// Called each frame; internally throttles to re-send every ~2 seconds
-    /// (120 frames) so that newly-connected peers whose data channels were
-    /// not yet open will still receive it shortly after connecting.
-    pub fn send_introduction(&mut self) {
+    /// Nothing is sent here; `tick` sends the newest state when the next
+    /// network tick is due. Skip this while there is nothing to send (e.g.
+    /// while dead) and no state goes out.
+    pub fn update_player_state(&mut self, position: Vec3, yaw: f32, pitch: f32, mask: u8) {
+        self.pending_state = Some(LocalState {
+            position,
+            yaw,
+            pitch,
+            mask,
+        });
+    }
+
+    /// Run the periodic sends that are due. Call this once per frame, after
+    /// `update_player_state`.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        let state = self.pending_state.take();
+
         if !self.session.is_connected() {
             return;
         }
-        if self.introduction_timer == 0 {
+
+        if let Some(state) = state {
+            // State goes out on the unreliable channel as a compact binary
+            // packet; a throttled copy goes to the server, which needs recent
+            // poses to validate kills
+            if self.state_tick.poll(now) {
+                self.send_state_packet(state);
+            }
+            if self.pose_tick.poll(now) {
+                self.session.send_pose(
+                    state.position.to_array(),
+                    state.yaw,
+                    state.pitch,
+                    state.mask,
+                );
+            }
+        }
+
+        // Re-sent periodically so that peers whose data channels were not yet
+        // open still learn our name shortly after connecting
+        if self.introduction_tick.poll(now) {
             let msg = GameMessage::Introduction {
                 name: self.player_name.clone(),
             };
             if let Ok(json) = serde_json::to_string(&msg) {
-                self.session.broadcast(ChannelKind::Events, &json);
+                self.session
+                    .broadcast(ChannelKind::Events, &Payload::Text(json));
             }
-            self.introduction_timer = 120;
-        } else {
-            self.introduction_timer -= 1;
         }
     }
 
-    /// Broadcast player state to all connected peers.
-    ///
-    /// This is sent on the unreliable channel for low latency.
-    pub fn send_player_state(&self, position: Vec3, yaw: f32, pitch: f32, mask: u8) {
-        let state = StateUpdate::new(position, yaw, pitch, mask);
-        if let Ok(json) = serde_json::to_string(&state) {
-            self.session.broadcast(ChannelKind::State, &json);
-        }
+    fn send_state_packet(&mut self, state: LocalState) {
+        let packet = StatePacket {
+            seq: self.state_seq,
+            // Wraps every 49 days of server uptime, costing remote players a
+            // moment of interpolation
+            sent_at_ms: self.session.server_time() as u32,
+            position: state.position.to_array(),
+            yaw: state.yaw,
+            pitch: state.pitch,
+            mask: state.mask,
+        };
+        self.state_seq = self.state_seq.wrapping_add(1);
+        self.session.broadcast(
+            ChannelKind::State,
+            &Payload::Binary(packet.encode().to_vec()),
+        );
+    }
+
+    /// Whether it is time to refresh the peer connection stats.
+    pub fn stats_due(&mut self) -> bool {
+        self.stats_tick.poll(Instant::now())
     }
 
-    /// Send a kill notification to all peers **and** deliver it back to
-    /// ourselves so `handle_network_event` processes every kill uniformly.
+    /// Loss of state packets from a peer since the last call, and their
+    /// current jitter.
+    pub fn state_reception(&mut self, peer_id: PeerId) -> ReceptionQuality {
+        self.state_reception
+            .get_mut(&peer_id)
+            .map(StateReception::report)
+            .unwrap_or_default()
+    }
+
+    /// Claim a kill with the server.
     ///
-    /// This is sent on the reliable channel to ensure delivery.
+    /// Every client, including ours, learns about the kill from the server's
+    /// `PlayerKilled` broadcast once it is confirmed, so `handle_network_event`
+    /// processes every kill uniformly.
     pub fn send_kill(&self, victim_id: PeerId) {
-        let msg = GameMessage::Kill { victim_id };
-        if let Ok(json) = serde_json::to_string(&msg) {
-            self.session
-                .broadcast_including_self(ChannelKind::Events, &json);
-        }
+        self.session
+            .claim_kill(victim_id, self.session.server_time());
+    }
+
+    /// Report a peer whose movement keeps breaking the rules.
+    pub fn report_peer(&self, peer_id: PeerId, reason: MovementViolation) {
+        self.session.report_peer(peer_id, reason);
+    }
+
+    /// The network simulator, if `?netsim` is simulating bad conditions.
+    pub fn network_simulator(&self) -> Option<&NetworkSimulator> {
+        self.session.network_simulator()
     }
 
     /// Get our local peer ID (None if not yet connected).
@@ -285,12 +496,22 @@ impl NetworkClient {
     }
 
     /// Disconnect from the network.
-    pub fn disconnect(&self) {
+    pub fn disconnect(&mut self) {
         self.session.disconnect();
     }
 
+    /// How game traffic gets to peers; in a star there are no peer
+    /// connections.
+    pub fn topology(&self) -> Topology {
+        self.session.topology()
+    }
+
     /// Get peer connections for stats collection.
     pub fn get_peer_connections(&self) -> Vec<(PeerId, RtcPeerConnection)> {
-        self.session.get_peer_connections()
+        self.session
+            .peers()
+            .filter(|(_, peer)| peer.channel_ready(ChannelKind::State))
+            .map(|(id, peer)| (id, peer.rtc_peer_connection().clone()))
+            .collect()
     }
 }
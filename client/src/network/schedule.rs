@@ -0,0 +1,48 @@
+//! Wall-clock timers for periodic network sends.
+//!
+//! The game loop runs at the display's refresh rate, which can be anything
+//! from 30 to 240+ Hz, so everything the network layer sends periodically is
+//! driven by these instead of by frame counts.
+
+use web_time::{Duration, Instant};
+
+/// A repeating timer, polled once per frame.
+pub struct Interval {
+    period: Duration,
+    next: Instant,
+}
+
+impl Interval {
+    /// Create an interval that fires on its first poll.
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            next: Instant::now(),
+        }
+    }
+
+    /// Create an interval firing `rate` times per second.
+    pub fn from_rate(rate: f32) -> Self {
+        Self::new(Duration::from_secs_f32(1.0 / rate.max(1.0)))
+    }
+
+    /// Whether the interval is due; if so, schedules the next firing.
+    ///
+    /// Fires at most once per call. Ticks keep a fixed phase, but after a
+    /// stall (e.g. a background tab) missed ticks are skipped, not replayed.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        if now < self.next {
+            return false;
+        }
+        self.next += self.period;
+        if self.next <= now {
+            self.next = now + self.period;
+        }
+        true
+    }
+
+    /// Fire on the next poll.
+    pub fn trigger(&mut self) {
+        self.next = Instant::now();
+    }
+}
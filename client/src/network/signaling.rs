@@ -2,161 +2,244 @@
 //!
 //! Handles communication with the signaling server for WebRTC connection establishment.
 //! This includes sending/receiving SDP offers, answers, and ICE candidates.
+//!
+//! If the socket drops, the client reconnects with exponential backoff and
+//! presents the resume token from its last `welcome` to reclaim its slot.
+//!
+//! While connected it also pings the server now and then to keep its
+//! estimate of the server clock current.
 
+use session::{ClockSync, EventQueue, Signaling, SignalingEvent};
 use std::rc::Rc;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::prelude::*;
 use web_sys::{MessageEvent, WebSocket};
+use web_time::{Duration, Instant};
 
-use super::ice::signaling_server_url;
-use super::protocol::{PeerId, SignalCommand, SignalMessage};
-use super::queue::EventQueue;
+use super::ice::{room_code_from_url, signaling_server_url};
+use super::protocol::{ClientMessage, Resume, ServerMessage};
+use super::schedule::Interval;
 use super::ui::{NetLogLevel, net_log};
 
-/// Events from the signaling server.
-#[derive(Debug, Clone)]
-pub enum SignalingEvent {
-    /// Connection to signaling server opened.
-    Connected,
-    /// Connection to signaling server closed.
-    Disconnected,
-    /// Error on signaling connection.
-    Error,
-    /// Received a message from the signaling server.
-    Message(SignalMessage),
-}
+/// Delay before the first reconnect attempt; doubles with each failure.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(8);
+
+/// How often the server clock estimate is refreshed.
+const CLOCK_SYNC_INTERVAL: Duration = Duration::from_secs(5);
 
 /// WebSocket signaling client.
 pub struct SignalingClient {
     ws: WebSocket,
     incoming: Rc<EventQueue<SignalingEvent>>,
+    name: String,
+    /// Credentials from the last `welcome`, presented when we reconnect.
+    resume: Option<Resume>,
+    /// Failed attempts since the last `welcome`, for backoff.
+    reconnect_attempts: u32,
+    /// When to open the next socket after losing the connection.
+    reconnect_at: Option<Instant>,
+    /// Set once we leave on purpose, so the drop isn't retried.
+    left: bool,
+    clock: ClockSync,
+    /// Pings for clock sync, sent while we're in a room.
+    ping_tick: Interval,
 }
 
 impl SignalingClient {
     /// Connect to the signaling server.
-    pub fn connect() -> Result<Self, JsValue> {
-        let server_url = signaling_server_url();
-        log::info!("Connecting to signaling server: {}", server_url);
+    pub fn connect(name: String) -> Result<Self, JsValue> {
+        let incoming = Rc::new(EventQueue::new());
+        let ws = open_socket(&incoming)?;
+
+        Ok(Self {
+            ws,
+            incoming,
+            name,
+            resume: None,
+            reconnect_attempts: 0,
+            reconnect_at: None,
+            left: false,
+            clock: ClockSync::new(),
+            ping_tick: Interval::new(CLOCK_SYNC_INTERVAL),
+        })
+    }
 
-        let ws = WebSocket::new(&server_url)?;
-        ws.set_binary_type(web_sys::BinaryType::Arraybuffer);
+    /// Join (or rejoin) our room once the socket is open.
+    fn send_join(&self) {
+        let cmd = ClientMessage::Join {
+            room: room_code_from_url(),
+            name: Some(self.name.clone()),
+            resume: self.resume.clone(),
+        };
+        self.send_command(&cmd);
+    }
 
-        let incoming = Rc::new(EventQueue::new());
+    fn schedule_reconnect(&mut self) {
+        let delay = RECONNECT_BASE_DELAY
+            .saturating_mul(1 << self.reconnect_attempts.min(16))
+            .min(RECONNECT_MAX_DELAY);
+        self.reconnect_attempts += 1;
+        self.reconnect_at = Some(Instant::now() + delay);
+
+        log::info!(
+            "Reconnecting to signaling server in {:?} (attempt {})",
+            delay,
+            self.reconnect_attempts
+        );
+        net_log(
+            NetLogLevel::Warning,
+            &format!(
+                "Reconnecting in {:.1}s (attempt {})",
+                delay.as_secs_f32(),
+                self.reconnect_attempts
+            ),
+        );
+    }
 
-        // Set up onopen handler
-        let ws_clone = ws.clone();
-        let incoming_clone = incoming.clone();
-        let onopen = Closure::wrap(Box::new(move |_: JsValue| {
-            log::info!("Connected to signaling server");
-            net_log(NetLogLevel::Success, "Connected to signaling server");
-            incoming_clone.push(SignalingEvent::Connected);
-
-            // Send join message
-            let cmd = SignalCommand::Join;
-            if let Ok(json) = serde_json::to_string(&cmd) {
-                let _ = ws_clone.send_with_str(&json);
-            }
-        }) as Box<dyn FnMut(JsValue)>);
-        ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
-        onopen.forget();
-
-        // Set up onmessage handler
-        let incoming_clone = incoming.clone();
-        let onmessage = Closure::wrap(Box::new(move |ev: MessageEvent| {
-            if let Some(text) = ev.data().as_string() {
-                match serde_json::from_str::<SignalMessage>(&text) {
-                    Ok(msg) => {
-                        incoming_clone.push(SignalingEvent::Message(msg));
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to parse server message: {} ({})", text, e);
+    /// Send a command to the signaling server.
+    fn send_command(&self, cmd: &ClientMessage) {
+        if let Ok(json) = serde_json::to_string(cmd) {
+            let _ = self.ws.send_with_str(&json);
+        }
+    }
+}
+
+impl Signaling for SignalingClient {
+    fn poll_events(&mut self) -> Vec<SignalingEvent> {
+        let events = self.incoming.drain();
+
+        for event in &events {
+            match event {
+                SignalingEvent::Connected => self.send_join(),
+                SignalingEvent::Disconnected if !self.left => self.schedule_reconnect(),
+                SignalingEvent::Message(ServerMessage::Welcome {
+                    client_id,
+                    resume_token,
+                    server_time,
+                    resumed,
+                    ..
+                }) => {
+                    self.resume = Some(Resume {
+                        client_id: *client_id,
+                        token: resume_token.clone(),
+                    });
+                    self.reconnect_attempts = 0;
+                    if !resumed {
+                        // Possibly a restarted server, whose clock starts over
+                        self.clock.reset();
                     }
+                    self.clock.observe(*server_time);
+                    self.ping_tick.trigger();
                 }
+                SignalingEvent::Message(ServerMessage::Pong {
+                    client_time,
+                    server_time,
+                }) => self.clock.handle_pong(*client_time, *server_time),
+                SignalingEvent::Message(ServerMessage::Kicked { .. }) => {
+                    // The server closes the socket next; coming back is not allowed
+                    self.left = true;
+                    self.reconnect_at = None;
+                }
+                _ => {}
             }
-        }) as Box<dyn FnMut(MessageEvent)>);
-        ws.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
-        onmessage.forget();
-
-        // Set up onerror handler
-        let incoming_clone = incoming.clone();
-        let onerror = Closure::wrap(Box::new(move |_: JsValue| {
-            log::error!("WebSocket error");
-            net_log(NetLogLevel::Error, "WebSocket error - server unreachable?");
-            incoming_clone.push(SignalingEvent::Error);
-        }) as Box<dyn FnMut(JsValue)>);
-        ws.set_onerror(Some(onerror.as_ref().unchecked_ref()));
-        onerror.forget();
-
-        // Set up onclose handler
-        let incoming_clone = incoming.clone();
-        let onclose = Closure::wrap(Box::new(move |_: JsValue| {
-            log::info!("WebSocket closed");
-            net_log(NetLogLevel::Warning, "Disconnected from server");
-            incoming_clone.push(SignalingEvent::Disconnected);
-        }) as Box<dyn FnMut(JsValue)>);
-        ws.set_onclose(Some(onclose.as_ref().unchecked_ref()));
-        onclose.forget();
-
-        Ok(Self { ws, incoming })
-    }
+        }
 
-    /// Poll for incoming signaling events.
-    pub fn poll_events(&self) -> Vec<SignalingEvent> {
-        self.incoming.drain()
-    }
+        let in_room = self.resume.is_some() && self.ws.ready_state() == WebSocket::OPEN;
+        if in_room && self.ping_tick.poll(Instant::now()) {
+            let cmd = ClientMessage::Ping {
+                client_time: self.clock.local_millis(),
+            };
+            self.send_command(&cmd);
+        }
 
-    /// Send an SDP offer to a peer.
-    pub fn send_offer(&self, target_id: PeerId, sdp: &str) {
-        let cmd = SignalCommand::Offer {
-            target_id,
-            sdp: sdp.to_string(),
-        };
-        self.send_command(&cmd);
-    }
+        if self.reconnect_at.is_some_and(|at| Instant::now() >= at) {
+            self.reconnect_at = None;
+            match open_socket(&self.incoming) {
+                Ok(ws) => self.ws = ws,
+                Err(e) => {
+                    log::error!("Failed to reopen signaling socket: {:?}", e);
+                    self.schedule_reconnect();
+                }
+            }
+        }
 
-    /// Send an SDP answer to a peer.
-    pub fn send_answer(&self, target_id: PeerId, sdp: &str) {
-        let cmd = SignalCommand::Answer {
-            target_id,
-            sdp: sdp.to_string(),
-        };
-        self.send_command(&cmd);
+        events
     }
 
-    /// Send an ICE candidate to a peer.
-    pub fn send_ice_candidate(
-        &self,
-        target_id: PeerId,
-        candidate: String,
-        sdp_mid: Option<String>,
-        sdp_m_line_index: Option<u16>,
-    ) {
-        let cmd = SignalCommand::IceCandidate {
-            target_id,
-            candidate,
-            sdp_mid,
-            sdp_m_line_index,
-        };
-        self.send_command(&cmd);
+    fn send(&self, msg: &ClientMessage) {
+        self.send_command(msg);
     }
 
-    /// Notify server that we died.
-    pub fn send_player_died(&self) {
-        let cmd = SignalCommand::PlayerDied;
-        self.send_command(&cmd);
+    fn server_time(&self) -> f64 {
+        self.clock.server_millis()
     }
 
     /// Send leave message and close connection.
-    pub fn disconnect(&self) {
-        let cmd = SignalCommand::Leave;
+    fn disconnect(&mut self) {
+        self.left = true;
+        self.reconnect_at = None;
+        let cmd = ClientMessage::Leave;
         self.send_command(&cmd);
         let _ = self.ws.close();
     }
+}
 
-    /// Send a command to the signaling server.
-    fn send_command(&self, cmd: &SignalCommand) {
-        if let Ok(json) = serde_json::to_string(cmd) {
-            let _ = self.ws.send_with_str(&json);
+/// Open a socket to the signaling server, feeding its callbacks into `incoming`.
+fn open_socket(incoming: &Rc<EventQueue<SignalingEvent>>) -> Result<WebSocket, JsValue> {
+    let server_url = signaling_server_url();
+    log::info!("Connecting to signaling server: {}", server_url);
+
+    let ws = WebSocket::new(&server_url)?;
+    ws.set_binary_type(web_sys::BinaryType::Arraybuffer);
+
+    // Set up onopen handler; the join is sent from `poll_events`, which
+    // knows our resume credentials
+    let incoming_clone = incoming.clone();
+    let onopen = Closure::wrap(Box::new(move |_: JsValue| {
+        log::info!("Connected to signaling server");
+        net_log(NetLogLevel::Success, "Connected to signaling server");
+        incoming_clone.push(SignalingEvent::Connected);
+    }) as Box<dyn FnMut(JsValue)>);
+    ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+    onopen.forget();
+
+    // Set up onmessage handler
+    let incoming_clone = incoming.clone();
+    let onmessage = Closure::wrap(Box::new(move |ev: MessageEvent| {
+        if let Some(text) = ev.data().as_string() {
+            match serde_json::from_str::<ServerMessage>(&text) {
+                Ok(msg) => {
+                    incoming_clone.push(SignalingEvent::Message(msg));
+                }
+                Err(e) => {
+                    log::warn!("Failed to parse server message: {} ({})", text, e);
+                }
+            }
         }
-    }
+    }) as Box<dyn FnMut(MessageEvent)>);
+    ws.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+
+    // Set up onerror handler
+    let incoming_clone = incoming.clone();
+    let onerror = Closure::wrap(Box::new(move |_: JsValue| {
+        log::error!("WebSocket error");
+        net_log(NetLogLevel::Error, "WebSocket error - server unreachable?");
+        incoming_clone.push(SignalingEvent::Error);
+    }) as Box<dyn FnMut(JsValue)>);
+    ws.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+    onerror.forget();
+
+    // Set up onclose handler
+    let incoming_clone = incoming.clone();
+    let onclose = Closure::wrap(Box::new(move |_: JsValue| {
+        log::info!("WebSocket closed");
+        net_log(NetLogLevel::Warning, "Disconnected from server");
+        incoming_clone.push(SignalingEvent::Disconnected);
+    }) as Box<dyn FnMut(JsValue)>);
+    ws.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+    onclose.forget();
+
+    Ok(ws)
 }
@@ -1,12 +1,23 @@
 //! Connection statistics and display.
 //!
-//! Provides functions to collect and display WebRTC connection statistics.
+//! Provides functions to collect and display WebRTC connection statistics,
+//! along with a short history of them so trends show up in the peer panel.
 
-use wasm_bindgen::JsCast;
+use std::collections::{HashMap, VecDeque};
+
+use session::ReceptionQuality;
+use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::RtcPeerConnection;
+use web_sys::{Element, RtcPeerConnection};
+use web_time::Instant;
+
+use super::protocol::{MovementViolation, PeerId};
 
-use super::protocol::PeerId;
+/// Refreshes kept per peer: a minute's worth at the stats interval.
+const HISTORY_LEN: usize = 30;
+
+/// Bar heights for the trend lines, lowest first.
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
 
 /// Connection type based on ICE candidate type (RFC 8445).
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -19,6 +30,8 @@ pub enum ConnectionType {
     Prflx(String),
     /// Relay candidate - via TURN relay server.
     Relay(String),
+    /// No peer connection: the game server passes traffic on (star topology).
+    Server,
     /// Unknown/connecting.
     Unknown,
 }
@@ -31,6 +44,143 @@ pub struct PeerStats {
     pub connection_type: ConnectionType,
     /// Round-trip time in milliseconds, if available.
     pub rtt_ms: Option<f64>,
+    /// Why we flagged this peer's movement as implausible, if we did.
+    pub suspicion: Option<MovementViolation>,
+    /// Protocol of the selected candidate pair ("udp" or "tcp"), and how we
+    /// reach the TURN server when relayed.
+    pub transport: Option<String>,
+    /// The browser's estimate of the bandwidth available for sending to
+    /// this peer, in bits per second.
+    pub available_outgoing_bitrate: Option<f64>,
+    /// Totals on the unreliable state channel.
+    pub state_traffic: ChannelTraffic,
+    /// Totals on the reliable events channel.
+    pub events_traffic: ChannelTraffic,
+    /// Loss and jitter of state packets since the last refresh.
+    pub state_reception: ReceptionQuality,
+    /// When these stats were read.
+    pub fetched_at: Instant,
+}
+
+impl PeerStats {
+    /// Stats for a peer we only reach through the game server, which leaves
+    /// just what we measure ourselves.
+    pub fn through_server(peer_id: PeerId, name: Option<String>) -> Self {
+        Self {
+            peer_id,
+            name,
+            connection_type: ConnectionType::Server,
+            rtt_ms: None,
+            suspicion: None,
+            transport: None,
+            available_outgoing_bitrate: None,
+            state_traffic: ChannelTraffic::default(),
+            events_traffic: ChannelTraffic::default(),
+            state_reception: ReceptionQuality::default(),
+            fetched_at: Instant::now(),
+        }
+    }
+}
+
+/// Totals for one data channel since it opened, as counted by the browser.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChannelTraffic {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+}
+
+/// Per-second rates on one data channel between two refreshes.
+#[derive(Clone, Copy, Default)]
+struct ChannelRates {
+    bytes_sent: f64,
+    bytes_received: f64,
+    messages_sent: f64,
+    messages_received: f64,
+}
+
+impl ChannelRates {
+    fn between(from: &ChannelTraffic, to: &ChannelTraffic, seconds: f64) -> Self {
+        let rate = |from: u64, to: u64| to.saturating_sub(from) as f64 / seconds;
+        Self {
+            bytes_sent: rate(from.bytes_sent, to.bytes_sent),
+            bytes_received: rate(from.bytes_received, to.bytes_received),
+            messages_sent: rate(from.messages_sent, to.messages_sent),
+            messages_received: rate(from.messages_received, to.messages_received),
+        }
+    }
+}
+
+/// One refresh of a peer's stats, as kept in the history.
+#[derive(Clone, Copy)]
+struct Sample {
+    rtt_ms: Option<f64>,
+    loss: Option<f64>,
+    /// Rates since the previous refresh; `None` for the first one.
+    state: Option<ChannelRates>,
+    events: Option<ChannelRates>,
+}
+
+impl Sample {
+    /// Bytes per second received over both channels.
+    fn bytes_received(&self) -> Option<f64> {
+        Some(self.state?.bytes_received + self.events?.bytes_received)
+    }
+}
+
+#[derive(Default)]
+struct PeerHistory {
+    /// The stats the latest sample was worked out from.
+    last: Option<(Instant, ChannelTraffic, ChannelTraffic)>,
+    samples: VecDeque<Sample>,
+}
+
+/// The last few stats refreshes of each peer.
+#[derive(Default)]
+pub struct StatsHistory {
+    peers: HashMap<PeerId, PeerHistory>,
+}
+
+impl StatsHistory {
+    /// Add a refresh, forgetting peers that are no longer in it.
+    pub fn record(&mut self, stats: &[PeerStats]) {
+        self.peers
+            .retain(|peer_id, _| stats.iter().any(|stat| stat.peer_id == *peer_id));
+        for stat in stats {
+            let history = self.peers.entry(stat.peer_id).or_default();
+            let rates = history.last.and_then(|(at, state, events)| {
+                let seconds = stat.fetched_at.duration_since(at).as_secs_f64();
+                (seconds > 0.0).then(|| {
+                    (
+                        ChannelRates::between(&state, &stat.state_traffic, seconds),
+                        ChannelRates::between(&events, &stat.events_traffic, seconds),
+                    )
+                })
+            });
+            history.last = Some((stat.fetched_at, stat.state_traffic, stat.events_traffic));
+            if history.samples.len() == HISTORY_LEN {
+                history.samples.pop_front();
+            }
+            history.samples.push_back(Sample {
+                rtt_ms: stat.rtt_ms,
+                loss: stat.state_reception.loss,
+                state: rates.map(|(state, _)| state),
+                events: rates.map(|(_, events)| events),
+            });
+        }
+    }
+
+    fn samples(&self, peer_id: PeerId) -> impl Iterator<Item = &Sample> + Clone {
+        self.peers
+            .get(&peer_id)
+            .into_iter()
+            .flat_map(|history| history.samples.iter())
+    }
+
+    fn latest(&self, peer_id: PeerId) -> Option<&Sample> {
+        self.peers.get(&peer_id)?.samples.back()
+    }
 }
 
 /// Fetch stats from a single peer connection and parse the RTCStatsReport.
@@ -55,14 +205,16 @@ pub async fn fetch_peer_stats(
     let mut connection_type = ConnectionType::Unknown;
     let mut rtt_ms: Option<f64> = None;
     let mut local_candidate_id: Option<String> = None;
+    let mut transport: Option<String> = None;
+    let mut available_outgoing_bitrate: Option<f64> = None;
+    let mut state_traffic = ChannelTraffic::default();
+    let mut events_traffic = ChannelTraffic::default();
 
-    // First pass: find the succeeded candidate-pair
+    // First pass: find the succeeded candidate-pair and the data channels
     stats_map.for_each(&mut |value, _key| {
-        if let Ok(obj) = js_sys::Reflect::get(&value, &"type".into())
-            && let Some(type_str) = obj.as_string()
-            && type_str == "candidate-pair"
-            && let Ok(state) = js_sys::Reflect::get(&value, &"state".into())
-            && state.as_string().as_deref() == Some("succeeded")
+        let stats_type = string_field(&value, "type");
+        if stats_type.as_deref() == Some("candidate-pair")
+            && string_field(&value, "state").as_deref() == Some("succeeded")
         {
             // Get RTT
             if let Ok(rtt) = js_sys::Reflect::get(&value, &"currentRoundTripTime".into())
@@ -70,10 +222,24 @@ pub async fn fetch_peer_stats(
             {
                 rtt_ms = Some(rtt_secs * 1000.0);
             }
+            available_outgoing_bitrate = number_field(&value, "availableOutgoingBitrate");
             // Get local candidate ID to look up candidate type
             if let Ok(local_id) = js_sys::Reflect::get(&value, &"localCandidateId".into()) {
                 local_candidate_id = local_id.as_string();
             }
+        } else if stats_type.as_deref() == Some("data-channel") {
+            let traffic = match string_field(&value, "label").as_deref() {
+                Some("state") => &mut state_traffic,
+                Some("events") => &mut events_traffic,
+                _ => return,
+            };
+            let count = |name| number_field(&value, name).unwrap_or(0.0) as u64;
+            *traffic = ChannelTraffic {
+                bytes_sent: count("bytesSent"),
+                bytes_received: count("bytesReceived"),
+                messages_sent: count("messagesSent"),
+                messages_received: count("messagesReceived"),
+            };
         }
     });
 
@@ -84,6 +250,12 @@ pub async fn fetch_peer_stats(
                 && let Ok(candidate_type) = js_sys::Reflect::get(&value, &"candidateType".into())
                 && let Some(type_str) = candidate_type.as_string()
             {
+                transport = string_field(&value, "protocol").map(|protocol| {
+                    match string_field(&value, "relayProtocol") {
+                        Some(relay) => format!("{}, relay over {}", protocol, relay),
+                        None => protocol,
+                    }
+                });
                 connection_type = match type_str.as_str() {
                     "host" => ConnectionType::Host,
                     "srflx" => {
@@ -127,11 +299,26 @@ pub async fn fetch_peer_stats(
         name,
         connection_type,
         rtt_ms,
+        suspicion: None,
+        transport,
+        available_outgoing_bitrate,
+        state_traffic,
+        events_traffic,
+        state_reception: ReceptionQuality::default(),
+        fetched_at: Instant::now(),
     })
 }
 
+fn string_field(value: &JsValue, name: &str) -> Option<String> {
+    js_sys::Reflect::get(value, &name.into()).ok()?.as_string()
+}
+
+fn number_field(value: &JsValue, name: &str) -> Option<f64> {
+    js_sys::Reflect::get(value, &name.into()).ok()?.as_f64()
+}
+
 /// Update the peer stats display panel in the UI.
-pub fn update_peer_stats_display(stats: &[PeerStats]) {
+pub fn update_peer_stats_display(stats: &[PeerStats], history: &StatsHistory) {
     let Some(container) = web_sys::window()
         .and_then(|w| w.document())
         .and_then(|d| d.get_element_by_id("peer-stats-list"))
@@ -189,6 +376,7 @@ pub fn update_peer_stats_display(stats: &[PeerStats]) {
             ConnectionType::Srflx(_) => ("srflx", "peer-type type-srflx"),
             ConnectionType::Prflx(_) => ("prflx", "peer-type type-prflx"),
             ConnectionType::Relay(_) => ("relay", "peer-type type-relay"),
+            ConnectionType::Server => ("server", "peer-type type-server"),
             ConnectionType::Unknown => ("...", "peer-type"),
         };
         let _ = type_name_span.set_attribute("class", type_class);
@@ -197,7 +385,7 @@ pub fn update_peer_stats_display(stats: &[PeerStats]) {
 
         // Server URL span (if applicable)
         if let Some(display_url) = match &stat.connection_type {
-            ConnectionType::Host | ConnectionType::Unknown => None,
+            ConnectionType::Host | ConnectionType::Server | ConnectionType::Unknown => None,
             ConnectionType::Srflx(url) | ConnectionType::Prflx(url) => {
                 // Extract just the hostname:port from stun:hostname:port
                 if url != "unknown" {
@@ -243,8 +431,120 @@ pub fn update_peer_stats_display(stats: &[PeerStats]) {
         rtt_span.set_text_content(Some(&rtt_text));
 
         let _ = row.append_child(&id_span);
+        // Flag for implausible movement
+        if let Some(reason) = stat.suspicion
+            && let Some(flag_span) = create_element("span", "peer-flag")
+        {
+            let _ = flag_span.set_attribute("title", "Movement breaks the game rules");
+            flag_span.set_text_content(Some(&format!("⚠ {}", suspicion_label(reason))));
+            let _ = row.append_child(&flag_span);
+        }
         let _ = row.append_child(&type_span);
         let _ = row.append_child(&rtt_span);
         let _ = container.append_child(&row);
+
+        for line in detail_lines(stat, history) {
+            if let Some(div) = create_element("div", "peer-detail") {
+                div.set_text_content(Some(&line));
+                let _ = container.append_child(&div);
+            }
+        }
+    }
+}
+
+/// Traffic, link quality and trends for a peer, one line each.
+fn detail_lines(stat: &PeerStats, history: &StatsHistory) -> Vec<String> {
+    let latest = history.latest(stat.peer_id);
+    let channel_line = |name: &str, rates: Option<ChannelRates>| match rates {
+        Some(rates) => format!(
+            "{} ↓{:.0}/s {} ↑{:.0}/s {}",
+            name,
+            rates.messages_received,
+            format_byte_rate(rates.bytes_received),
+            rates.messages_sent,
+            format_byte_rate(rates.bytes_sent),
+        ),
+        None => format!("{} -", name),
+    };
+
+    let quality = stat.state_reception;
+    let mut link = vec![
+        match quality.loss {
+            Some(loss) => format!("loss {:.1}%", loss * 100.0),
+            None => "loss -".to_string(),
+        },
+        match quality.jitter_ms {
+            Some(jitter) => format!("jitter {:.0}ms", jitter),
+            None => "jitter -".to_string(),
+        },
+    ];
+    link.extend(stat.transport.clone());
+    link.extend(
+        stat.available_outgoing_bitrate
+            .map(|bitrate| format!("out {}", format_bitrate(bitrate))),
+    );
+
+    let samples = || history.samples(stat.peer_id);
+    let trend = format!(
+        "rtt {} loss {} in {}",
+        sparkline(samples().map(|s| s.rtt_ms), 50.0),
+        sparkline(samples().map(|s| s.loss), 0.05),
+        sparkline(samples().map(Sample::bytes_received), 1000.0),
+    );
+
+    // Without a peer connection the browser has no channel counters
+    if stat.connection_type == ConnectionType::Server {
+        return vec![link.join(" · "), trend];
+    }
+    vec![
+        channel_line("state", latest.and_then(|s| s.state)),
+        channel_line("events", latest.and_then(|s| s.events)),
+        link.join(" · "),
+        trend,
+    ]
+}
+
+/// Draw `values` as bars scaled to the largest of them, or to `floor` if
+/// that's larger, so that noise on a quiet link stays flat. Gaps are blank.
+fn sparkline(values: impl Iterator<Item = Option<f64>> + Clone, floor: f64) -> String {
+    let top = values.clone().flatten().fold(floor, f64::max);
+    let last = (SPARK_LEVELS.len() - 1) as f64;
+    values
+        .map(|value| match value {
+            Some(value) => SPARK_LEVELS[(value / top * last).round().clamp(0.0, last) as usize],
+            None => ' ',
+        })
+        .collect()
+}
+
+fn format_byte_rate(bytes_per_sec: f64) -> String {
+    if bytes_per_sec < 1000.0 {
+        format!("{:.0}B/s", bytes_per_sec)
+    } else {
+        format!("{:.1}kB/s", bytes_per_sec / 1000.0)
+    }
+}
+
+fn format_bitrate(bits_per_sec: f64) -> String {
+    if bits_per_sec < 1_000_000.0 {
+        format!("{:.0}kbps", bits_per_sec / 1000.0)
+    } else {
+        format!("{:.1}Mbps", bits_per_sec / 1_000_000.0)
+    }
+}
+
+fn create_element(tag: &str, class: &str) -> Option<Element> {
+    let element = web_sys::window()?.document()?.create_element(tag).ok()?;
+    let _ = element.set_attribute("class", class);
+    Some(element)
+}
+
+/// Short description of a movement flag for the peer stats panel.
+fn suspicion_label(reason: MovementViolation) -> &'static str {
+    match reason {
+        MovementViolation::InvalidMask => "bad mask",
+        MovementViolation::TooFast => "too fast",
+        MovementViolation::Flying => "flying",
+        MovementViolation::ThroughWall => "noclip",
     }
 }
@@ -1,95 +1,115 @@
 //! WebRTC transport layer.
 //!
-//! Provides abstractions over WebRTC peer connections and data channels.
-//! This layer knows nothing about game messages - it just sends and receives strings.
+//! Implements the session's peer transport over WebRTC peer connections and
+//! data channels. This layer knows nothing about game messages - it just
+//! sends and receives strings and byte buffers.
+//!
+//! Either end of a connection may renegotiate it (ICE restarts), so offers can
+//! cross. They are resolved with the "perfect negotiation" pattern: one peer is
+//! polite and rolls back its own offer to take the other's, the impolite one
+//! ignores the colliding offer and waits for the answer to its own.
 
-use std::cell::RefCell;
+use session::{
+    EventQueue, IceCandidateData, IceState, Payload, PeerConnector, PeerEvent, PeerTransport,
+};
+use std::cell::{Cell, RefCell};
+use std::future::Future;
 use std::rc::Rc;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
     MessageEvent, RtcConfiguration, RtcDataChannel, RtcDataChannelEvent, RtcDataChannelInit,
-    RtcPeerConnection, RtcPeerConnectionIceEvent, RtcSdpType, RtcSessionDescriptionInit,
+    RtcDataChannelType, RtcIceCandidate, RtcIceCandidateInit, RtcIceTransportPolicy,
+    RtcOfferOptions, RtcPeerConnection, RtcPeerConnectionIceEvent, RtcSdpType,
+    RtcSessionDescriptionInit, RtcSignalingState,
 };
 
-use super::ice::{fetch_turn_servers, to_js_ice_servers};
-use super::protocol::{ChannelKind, PeerId};
-use super::queue::EventQueue;
+use super::ice::{fetch_ice_servers, to_js_ice_servers};
+use super::protocol::{ChannelKind, IcePolicy, PeerId};
 use super::ui::{NetLogLevel, net_log};
 
-// Thread-local storage for channels received via ondatachannel callback.
-// This allows immediate storage like the old design, avoiding event queue timing issues.
-thread_local! {
-    pub static RECEIVED_CHANNELS: RefCell<Vec<ReceivedChannel>> = const { RefCell::new(Vec::new()) };
+/// Remote candidates that arrived before the remote description was applied.
+///
+/// The browser rejects candidates until it knows the remote description, and
+/// the answerer starts sending them right after its answer, so they routinely
+/// overtake it. They are held here and applied, in arrival order, once
+/// `set_remote_description` has resolved.
+#[derive(Default)]
+struct PendingCandidates {
+    queued: Vec<IceCandidateData>,
+    /// Set once the first remote description is in place; from then on
+    /// candidates are applied as they arrive.
+    ready: bool,
 }
 
-/// A channel received from a remote peer that needs to be stored.
-pub struct ReceivedChannel {
-    pub peer_id: PeerId,
-    pub kind: ChannelKind,
-    pub channel: RtcDataChannel,
+/// Perfect negotiation bookkeeping, shared with the tasks that make offers
+/// and apply candidates.
+#[derive(Default)]
+struct Negotiation {
+    /// From starting to create an offer until it is applied locally; an
+    /// offer from the peer in that window collides with ours.
+    making_offer: Cell<bool>,
+    /// The last offer from the peer collided with ours and was ignored, so
+    /// its candidates are expected not to apply.
+    ignore_offer: Cell<bool>,
 }
 
-/// Events from a WebRTC peer connection.
-#[derive(Debug, Clone)]
-pub enum PeerEvent {
-    /// Data channel is now open and ready.
-    ChannelOpened(ChannelKind),
-    /// Received a message on a channel.
-    Message { channel: ChannelKind, data: String },
-    /// ICE connection state changed.
-    IceStateChanged(IceState),
-    /// Local ICE candidate generated (needs to be sent to peer via signaling).
-    LocalIceCandidate {
-        candidate: String,
-        sdp_mid: Option<String>,
-        sdp_m_line_index: Option<u16>,
-    },
-    /// ICE gathering complete.
-    IceGatheringComplete,
+/// The data channels of a connection. We create them when we make the first
+/// offer, otherwise the peer's arrive through `ondatachannel`.
+#[derive(Default)]
+struct Channels {
+    state: Option<RtcDataChannel>,
+    events: Option<RtcDataChannel>,
 }
 
-/// ICE connection state.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum IceState {
-    New,
-    Checking,
-    Connected,
-    Completed,
-    Failed,
-    Disconnected,
-    Closed,
-}
+impl Channels {
+    fn get(&self, kind: ChannelKind) -> Option<&RtcDataChannel> {
+        match kind {
+            ChannelKind::State => self.state.as_ref(),
+            ChannelKind::Events => self.events.as_ref(),
+        }
+    }
 
-/// Pending ICE candidate data (received before remote description is set).
-#[derive(Clone, Debug)]
-pub struct IceCandidateData {
-    pub candidate: String,
-    pub sdp_mid: Option<String>,
-    pub sdp_m_line_index: Option<u16>,
+    fn set(&mut self, kind: ChannelKind, dc: RtcDataChannel) {
+        match kind {
+            ChannelKind::State => self.state = Some(dc),
+            ChannelKind::Events => self.events = Some(dc),
+        }
+    }
 }
 
 /// A WebRTC peer connection with two data channels.
 pub struct WebRtcPeer {
     peer_id: PeerId,
+    /// Whether we give way when offers collide.
+    polite: bool,
     pc: RtcPeerConnection,
-    state_channel: Option<RtcDataChannel>,
-    events_channel: Option<RtcDataChannel>,
+    /// Shared with the handler for channels the peer creates.
+    channels: Rc<RefCell<Channels>>,
     incoming: Rc<EventQueue<PeerEvent>>,
+    /// Shared with the tasks that apply remote descriptions.
+    candidates: Rc<RefCell<PendingCandidates>>,
+    negotiation: Rc<Negotiation>,
 }
 
 impl WebRtcPeer {
-    /// Create a new peer connection (without data channels yet).
-    pub async fn new(peer_id: PeerId) -> Result<Self, JsValue> {
-        let servers = fetch_turn_servers(peer_id).await;
-        let ice_servers_array = to_js_ice_servers(&servers)?;
+    /// Create a new peer connection (without data channels yet). `polite`
+    /// decides which side gives way when offers collide; the two peers must
+    /// disagree on it.
+    pub async fn new(peer_id: PeerId, polite: bool) -> Result<Self, JsValue> {
+        let list = fetch_ice_servers(peer_id).await;
+        let ice_servers_array = to_js_ice_servers(&list.ice_servers)?;
 
         let config = RtcConfiguration::new();
         config.set_ice_servers(&ice_servers_array);
+        // Dropping the STUN servers alone would still leave host candidates
+        if list.policy == IcePolicy::RelayOnly {
+            config.set_ice_transport_policy(RtcIceTransportPolicy::Relay);
+        }
 
         // Log configured ICE servers
-        for server in &servers {
+        for server in &list.ice_servers {
             log::info!(
                 "ICE server: {:?} (has credentials: {})",
                 server.urls,
@@ -97,12 +117,22 @@ impl WebRtcPeer {
             );
         }
         log::info!(
-            "Configured {} ICE servers for peer {}",
+            "Configured {} ICE servers for peer {} ({})",
             ice_servers_array.length(),
-            peer_id
+            peer_id,
+            list.policy
         );
 
-        let pc = RtcPeerConnection::new_with_configuration(&config)?;
+        Self::with_configuration(peer_id, polite, &config)
+    }
+
+    /// Create a peer connection with the given ICE configuration.
+    fn with_configuration(
+        peer_id: PeerId,
+        polite: bool,
+        config: &RtcConfiguration,
+    ) -> Result<Self, JsValue> {
+        let pc = RtcPeerConnection::new_with_configuration(config)?;
         let incoming = Rc::new(EventQueue::new());
 
         // Set up ICE candidate handler
@@ -192,6 +222,8 @@ impl WebRtcPeer {
         onice.forget();
 
         // Set up data channel handler (for incoming channels from remote peer)
+        let channels = Rc::new(RefCell::new(Channels::default()));
+        let channels_clone = channels.clone();
         let incoming_clone = incoming.clone();
         let ondc = Closure::wrap(Box::new(move |ev: JsValue| {
             let ev: RtcDataChannelEvent = ev.unchecked_into();
@@ -215,55 +247,22 @@ impl WebRtcPeer {
 
             Self::setup_channel_callbacks(&dc, channel_kind, peer_id, &incoming_clone);
 
-            // Store immediately in thread-local (like old design)
-            RECEIVED_CHANNELS.with(|rc| {
-                rc.borrow_mut().push(ReceivedChannel {
-                    peer_id,
-                    kind: channel_kind,
-                    channel: dc,
-                });
-            });
-            log::info!(
-                "Queued received {:?} channel for peer {}",
-                channel_kind,
-                peer_id
-            );
+            channels_clone.borrow_mut().set(channel_kind, dc);
         }) as Box<dyn FnMut(JsValue)>);
         pc.set_ondatachannel(Some(ondc.as_ref().unchecked_ref()));
         ondc.forget();
 
         Ok(Self {
             peer_id,
+            polite,
             pc,
-            state_channel: None,
-            events_channel: None,
+            channels,
             incoming,
+            candidates: Rc::default(),
+            negotiation: Rc::default(),
         })
     }
 
-    /// Create data channels (call this when we're the initiator).
-    pub fn create_data_channels(&mut self) {
-        // Create unreliable channel for position updates
-        let state_init = RtcDataChannelInit::new();
-        state_init.set_ordered(false);
-        state_init.set_max_retransmits(0);
-        let state_dc = self
-            .pc
-            .create_data_channel_with_data_channel_dict("state", &state_init);
-        Self::setup_channel_callbacks(&state_dc, ChannelKind::State, self.peer_id, &self.incoming);
-        self.state_channel = Some(state_dc);
-
-        // Create reliable channel for game events
-        let events_dc = self.pc.create_data_channel("events");
-        Self::setup_channel_callbacks(
-            &events_dc,
-            ChannelKind::Events,
-            self.peer_id,
-            &self.incoming,
-        );
-        self.events_channel = Some(events_dc);
-    }
-
     /// Set up callbacks for a data channel.
     fn setup_channel_callbacks(
         dc: &RtcDataChannel,
@@ -271,6 +270,8 @@ impl WebRtcPeer {
         peer_id: PeerId,
         incoming: &Rc<EventQueue<PeerEvent>>,
     ) {
+        dc.set_binary_type(RtcDataChannelType::Arraybuffer);
+
         let incoming_clone = incoming.clone();
         let onopen = Closure::wrap(Box::new(move |_: JsValue| {
             log::info!("{:?} channel open with peer {}", kind, peer_id);
@@ -286,109 +287,331 @@ impl WebRtcPeer {
         let incoming_clone = incoming.clone();
         let onmsg = Closure::wrap(Box::new(move |ev: JsValue| {
             let ev: MessageEvent = ev.unchecked_into();
-            if let Some(data) = ev.data().as_string() {
-                log::debug!("Received {:?} message from peer {}", kind, peer_id);
-                incoming_clone.push(PeerEvent::Message {
-                    channel: kind,
-                    data,
-                });
+            let raw = ev.data();
+            let data = if let Some(text) = raw.as_string() {
+                Payload::Text(text)
+            } else if let Some(buffer) = raw.dyn_ref::<js_sys::ArrayBuffer>() {
+                Payload::Binary(js_sys::Uint8Array::new(buffer).to_vec())
             } else {
                 log::warn!(
-                    "{:?} channel: received non-string data from peer {}",
+                    "{:?} channel: received unsupported data from peer {}",
                     kind,
                     peer_id
                 );
-            }
+                return;
+            };
+            log::debug!("Received {:?} message from peer {}", kind, peer_id);
+            incoming_clone.push(PeerEvent::Message {
+                channel: kind,
+                data,
+            });
         }) as Box<dyn FnMut(JsValue)>);
         dc.set_onmessage(Some(onmsg.as_ref().unchecked_ref()));
         onmsg.forget();
     }
 
-    /// Poll for incoming events from this peer.
-    pub fn poll_events(&self) -> Vec<PeerEvent> {
-        self.incoming.drain()
+    /// Apply an offer the browser is creating, then hand it out as a
+    /// `PeerEvent::LocalOffer`.
+    fn send_offer(&self, created: js_sys::Promise, ice_restart: bool) {
+        self.negotiation.making_offer.set(true);
+        let pc = self.pc.clone();
+        let negotiation = self.negotiation.clone();
+        let incoming = self.incoming.clone();
+        let peer_id = self.peer_id;
+        wasm_bindgen_futures::spawn_local(async move {
+            match make_offer(&pc, &negotiation, created).await {
+                Ok(sdp) => {
+                    log::info!("Created offer for peer {}", peer_id);
+                    incoming.push(PeerEvent::LocalOffer { sdp, ice_restart });
+                }
+                Err(e) => log::error!("Failed to create offer for peer {}: {:?}", peer_id, e),
+            }
+        });
     }
 
-    /// Store a received data channel (when we're the responder).
-    pub fn store_channel(&mut self, kind: ChannelKind, dc: RtcDataChannel) {
-        match kind {
-            ChannelKind::State => self.state_channel = Some(dc),
-            ChannelKind::Events => self.events_channel = Some(dc),
+    /// Set a remote SDP offer or answer.
+    ///
+    /// The description is handed to the browser immediately; candidates that
+    /// arrive while it is being applied are queued and added afterwards.
+    fn set_remote_description(
+        &self,
+        kind: RtcSdpType,
+        sdp: &str,
+    ) -> impl Future<Output = Result<(), JsValue>> + 'static {
+        let desc = RtcSessionDescriptionInit::new(kind);
+        desc.set_sdp(sdp);
+        let applied = JsFuture::from(self.pc.set_remote_description(&desc));
+        let pc = self.pc.clone();
+        let candidates = self.candidates.clone();
+        let negotiation = self.negotiation.clone();
+        let peer_id = self.peer_id;
+        async move {
+            applied.await?;
+            let queued = {
+                let mut candidates = candidates.borrow_mut();
+                candidates.ready = true;
+                std::mem::take(&mut candidates.queued)
+            };
+            if !queued.is_empty() {
+                log::info!(
+                    "Applying {} queued ICE candidates for peer {}",
+                    queued.len(),
+                    peer_id
+                );
+                wasm_bindgen_futures::spawn_local(async move {
+                    for ice in &queued {
+                        apply_ice_candidate(&pc, &negotiation, ice).await;
+                    }
+                });
+            }
+            Ok(())
         }
     }
 
-    /// Send data on a channel.
-    pub fn send(&self, kind: ChannelKind, data: &str) -> Result<(), &'static str> {
-        let channel = match kind {
-            ChannelKind::State => &self.state_channel,
-            ChannelKind::Events => &self.events_channel,
-        };
+    /// Number of remote candidates waiting for the remote description.
+    #[cfg(test)]
+    fn queued_candidates(&self) -> usize {
+        self.candidates.borrow().queued.len()
+    }
+
+    /// Get the underlying RtcPeerConnection (for stats).
+    pub fn rtc_peer_connection(&self) -> &RtcPeerConnection {
+        &self.pc
+    }
+}
+
+impl PeerTransport for WebRtcPeer {
+    fn create_data_channels(&mut self) {
+        // Create unreliable channel for position updates
+        let state_init = RtcDataChannelInit::new();
+        state_init.set_ordered(false);
+        state_init.set_max_retransmits(0);
+        let state_dc = self
+            .pc
+            .create_data_channel_with_data_channel_dict("state", &state_init);
+        Self::setup_channel_callbacks(&state_dc, ChannelKind::State, self.peer_id, &self.incoming);
+
+        // Create reliable channel for game events
+        let events_dc = self.pc.create_data_channel("events");
+        Self::setup_channel_callbacks(
+            &events_dc,
+            ChannelKind::Events,
+            self.peer_id,
+            &self.incoming,
+        );
+
+        let mut channels = self.channels.borrow_mut();
+        channels.set(ChannelKind::State, state_dc);
+        channels.set(ChannelKind::Events, events_dc);
+    }
+
+    fn create_offer(&self) {
+        self.send_offer(self.pc.create_offer(), false);
+    }
+
+    fn restart_ice(&self) {
+        let options = RtcOfferOptions::new();
+        options.set_ice_restart(true);
+        self.send_offer(self.pc.create_offer_with_rtc_offer_options(&options), true);
+    }
+
+    fn accept_offer(&self, sdp: &str) -> bool {
+        let signaling_state = self.pc.signaling_state();
+        let collision =
+            self.negotiation.making_offer.get() || signaling_state != RtcSignalingState::Stable;
+        let ignore = collision && !self.polite;
+        self.negotiation.ignore_offer.set(ignore);
+        if ignore {
+            log::info!(
+                "Ignoring offer from peer {} that collides with ours",
+                self.peer_id
+            );
+            return false;
+        }
+
+        // Queued in order behind each other by the browser
+        let rolled_back = (signaling_state == RtcSignalingState::HaveLocalOffer).then(|| {
+            log::info!(
+                "Rolling back our offer to take peer {}'s instead",
+                self.peer_id
+            );
+            let rollback = RtcSessionDescriptionInit::new(RtcSdpType::Rollback);
+            JsFuture::from(self.pc.set_local_description(&rollback))
+        });
+        let applied = self.set_remote_description(RtcSdpType::Offer, sdp);
+
+        let pc = self.pc.clone();
+        let incoming = self.incoming.clone();
+        let peer_id = self.peer_id;
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Some(rolled_back) = rolled_back
+                && let Err(e) = rolled_back.await
+            {
+                log::warn!("Failed to roll back offer to peer {}: {:?}", peer_id, e);
+            }
+            let answer = match applied.await {
+                Ok(()) => set_local_description(&pc, pc.create_answer(), RtcSdpType::Answer).await,
+                Err(e) => Err(e),
+            };
+            match answer {
+                Ok(sdp) => incoming.push(PeerEvent::LocalAnswer(sdp)),
+                Err(e) => log::warn!("Failed to answer offer from peer {}: {:?}", peer_id, e),
+            }
+        });
+        true
+    }
 
-        if let Some(dc) = channel {
-            if dc.ready_state() == web_sys::RtcDataChannelState::Open {
-                let _ = dc.send_with_str(data);
-                Ok(())
+    fn accept_answer(&self, sdp: &str) {
+        let applied = self.set_remote_description(RtcSdpType::Answer, sdp);
+        let pc = self.pc.clone();
+        let peer_id = self.peer_id;
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = applied.await {
+                log::warn!("Failed to set remote answer from peer {}: {:?}", peer_id, e);
             } else {
-                Err("channel not open")
+                log::info!(
+                    "Set remote description for peer {}, ice state: {:?}",
+                    peer_id,
+                    pc.ice_connection_state()
+                );
             }
-        } else {
-            Err("channel not created")
+        });
+    }
+
+    fn add_ice_candidate(&self, ice: IceCandidateData) {
+        let mut candidates = self.candidates.borrow_mut();
+        if !candidates.ready {
+            log::debug!(
+                "Queueing ICE candidate from peer {} (no remote description yet)",
+                self.peer_id
+            );
+            candidates.queued.push(ice);
+            return;
         }
+        let pc = self.pc.clone();
+        let negotiation = self.negotiation.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            apply_ice_candidate(&pc, &negotiation, &ice).await;
+        });
     }
 
-    /// Check if a channel is ready for sending.
-    pub fn channel_ready(&self, kind: ChannelKind) -> bool {
-        let channel = match kind {
-            ChannelKind::State => &self.state_channel,
-            ChannelKind::Events => &self.events_channel,
+    fn has_remote_description(&self) -> bool {
+        self.candidates.borrow().ready
+    }
+
+    fn send(&self, kind: ChannelKind, data: &Payload) -> Result<(), &'static str> {
+        let channels = self.channels.borrow();
+        let Some(dc) = channels.get(kind) else {
+            return Err("channel not created");
         };
+        if dc.ready_state() != web_sys::RtcDataChannelState::Open {
+            return Err("channel not open");
+        }
+        let _ = match data {
+            Payload::Text(text) => dc.send_with_str(text),
+            Payload::Binary(bytes) => dc.send_with_u8_array(bytes),
+        };
+        Ok(())
+    }
 
-        channel
-            .as_ref()
+    fn channel_ready(&self, kind: ChannelKind) -> bool {
+        self.channels
+            .borrow()
+            .get(kind)
             .is_some_and(|dc| dc.ready_state() == web_sys::RtcDataChannelState::Open)
     }
 
-    /// Create an SDP offer.
-    pub async fn create_offer(&self) -> Result<String, JsValue> {
-        let offer = JsFuture::from(self.pc.create_offer()).await?;
-        let sdp = get_sdp(&offer);
-        let init = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
-        init.set_sdp(&sdp);
-        JsFuture::from(self.pc.set_local_description(&init)).await?;
-        Ok(sdp)
+    fn poll_events(&self) -> Vec<PeerEvent> {
+        self.incoming.drain()
     }
 
-    /// Create an SDP answer (after receiving an offer).
-    pub async fn create_answer(&self) -> Result<String, JsValue> {
-        let answer = JsFuture::from(self.pc.create_answer()).await?;
-        let sdp = get_sdp(&answer);
-        let init = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
-        init.set_sdp(&sdp);
-        JsFuture::from(self.pc.set_local_description(&init)).await?;
-        Ok(sdp)
+    fn close(&self) {
+        self.pc.close();
     }
+}
 
-    /// Set the remote SDP offer.
-    pub async fn set_remote_offer(&self, sdp: &str) -> Result<(), JsValue> {
-        let desc = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
-        desc.set_sdp(sdp);
-        JsFuture::from(self.pc.set_remote_description(&desc)).await?;
-        Ok(())
-    }
+/// Creates `WebRtcPeer`s. Each one fetches TURN credentials first, so they
+/// are handed over once that's done.
+#[derive(Default)]
+pub struct WebRtcConnector {
+    created: Rc<EventQueue<(PeerId, WebRtcPeer)>>,
+}
 
-    /// Get the underlying RtcPeerConnection (for stats).
-    pub fn rtc_peer_connection(&self) -> &RtcPeerConnection {
-        &self.pc
+impl PeerConnector for WebRtcConnector {
+    type Peer = WebRtcPeer;
+
+    fn connect(&mut self, peer_id: PeerId, polite: bool) {
+        let created = self.created.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match WebRtcPeer::new(peer_id, polite).await {
+                Ok(peer) => created.push((peer_id, peer)),
+                Err(e) => {
+                    log::error!("Failed to create peer connection for {}: {:?}", peer_id, e);
+                }
+            }
+        });
     }
 
-    /// Close the connection.
-    pub fn close(&self) {
-        self.pc.close();
+    fn poll_created(&mut self) -> Vec<(PeerId, WebRtcPeer)> {
+        self.created.drain()
     }
+}
+
+/// Apply a locally created offer, flagging the negotiation as making an offer
+/// meanwhile.
+async fn make_offer(
+    pc: &RtcPeerConnection,
+    negotiation: &Negotiation,
+    created: js_sys::Promise,
+) -> Result<String, JsValue> {
+    negotiation.making_offer.set(true);
+    let result = set_local_description(pc, created, RtcSdpType::Offer).await;
+    negotiation.making_offer.set(false);
+    result
+}
+
+/// Wait for a locally created offer or answer and apply it, returning its SDP.
+async fn set_local_description(
+    pc: &RtcPeerConnection,
+    created: js_sys::Promise,
+    kind: RtcSdpType,
+) -> Result<String, JsValue> {
+    let description = JsFuture::from(created).await?;
+    let sdp = get_sdp(&description);
+    let init = RtcSessionDescriptionInit::new(kind);
+    init.set_sdp(&sdp);
+    JsFuture::from(pc.set_local_description(&init)).await?;
+    Ok(sdp)
+}
 
-    /// Check if we have a remote description set.
-    pub fn has_remote_description(&self) -> bool {
-        self.pc.remote_description().is_some()
+/// Hand a remote ICE candidate to the browser.
+async fn apply_ice_candidate(
+    pc: &RtcPeerConnection,
+    negotiation: &Negotiation,
+    ice: &IceCandidateData,
+) {
+    let init = RtcIceCandidateInit::new(&ice.candidate);
+    if let Some(ref mid) = ice.sdp_mid {
+        init.set_sdp_mid(Some(mid));
+    }
+    if let Some(idx) = ice.sdp_m_line_index {
+        init.set_sdp_m_line_index(Some(idx));
+    }
+    let candidate = match RtcIceCandidate::new(&init) {
+        Ok(candidate) => candidate,
+        Err(e) => {
+            log::warn!("Malformed ICE candidate {:?}: {:?}", ice.candidate, e);
+            return;
+        }
+    };
+    if let Err(e) =
+        JsFuture::from(pc.add_ice_candidate_with_opt_rtc_ice_candidate(Some(&candidate))).await
+    {
+        if negotiation.ignore_offer.get() {
+            log::debug!("Dropped ICE candidate for an ignored offer: {:?}", e);
+        } else {
+            log::warn!("Failed to add ICE candidate {:?}: {:?}", ice.candidate, e);
+        }
     }
 }
 
@@ -399,3 +622,163 @@ fn get_sdp(js: &JsValue) -> String {
         .and_then(|v| v.as_string())
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    async fn sleep(ms: i32) {
+        let promise = js_sys::Promise::new(&mut |resolve, _| {
+            web_sys::window()
+                .unwrap()
+                .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms)
+                .unwrap();
+        });
+        JsFuture::from(promise).await.unwrap();
+    }
+
+    /// Poll `peer` until `pick` accepts one of its events; the events it
+    /// passes on are dropped.
+    async fn wait_for<T>(peer: &WebRtcPeer, mut pick: impl FnMut(PeerEvent) -> Option<T>) -> T {
+        for _ in 0..100 {
+            for event in peer.poll_events() {
+                if let Some(picked) = pick(event) {
+                    return picked;
+                }
+            }
+            sleep(50).await;
+        }
+        panic!("peer {} never produced the expected event", peer.peer_id);
+    }
+
+    async fn local_offer(peer: &WebRtcPeer) -> String {
+        wait_for(peer, |event| match event {
+            PeerEvent::LocalOffer { sdp, .. } => Some(sdp),
+            _ => None,
+        })
+        .await
+    }
+
+    async fn local_answer(peer: &WebRtcPeer) -> String {
+        wait_for(peer, |event| match event {
+            PeerEvent::LocalAnswer(sdp) => Some(sdp),
+            _ => None,
+        })
+        .await
+    }
+
+    /// The offer of `peer` and its local candidates, once it has finished
+    /// gathering.
+    async fn offer_and_candidates(peer: &WebRtcPeer) -> (String, Vec<IceCandidateData>) {
+        let mut offer = None;
+        let mut candidates = Vec::new();
+        wait_for(peer, |event| match event {
+            PeerEvent::LocalOffer { sdp, .. } => {
+                offer = Some(sdp);
+                None
+            }
+            PeerEvent::LocalIceCandidate {
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            } => {
+                candidates.push(IceCandidateData {
+                    candidate,
+                    sdp_mid,
+                    sdp_m_line_index,
+                });
+                None
+            }
+            PeerEvent::IceGatheringComplete => Some(()),
+            _ => None,
+        })
+        .await;
+        (offer.expect("offer comes before candidates"), candidates)
+    }
+
+    fn remote_candidate_lines(peer: &WebRtcPeer) -> usize {
+        peer.pc
+            .remote_description()
+            .map_or(0, |desc| desc.sdp().matches("a=candidate:").count())
+    }
+
+    #[wasm_bindgen_test]
+    async fn candidates_before_during_and_after_negotiation_are_all_applied() {
+        let config = RtcConfiguration::new();
+        let mut offerer = WebRtcPeer::with_configuration(1, false, &config).unwrap();
+        let answerer = WebRtcPeer::with_configuration(2, true, &config).unwrap();
+        offerer.create_data_channels();
+
+        offerer.create_offer();
+        let (offer, candidates) = offer_and_candidates(&offerer).await;
+        assert!(candidates.len() >= 3, "need a few host candidates");
+        // The offer went out before gathering, so it carries none itself
+        assert_eq!(offer.matches("a=candidate:").count(), 0);
+
+        // Before: the answerer hasn't seen the offer yet
+        answerer.add_ice_candidate(candidates[0].clone());
+        assert_eq!(answerer.queued_candidates(), 1);
+
+        // During: the offer is being applied
+        assert!(answerer.accept_offer(&offer));
+        answerer.add_ice_candidate(candidates[1].clone());
+        assert_eq!(answerer.queued_candidates(), 2);
+        let answer = local_answer(&answerer).await;
+        assert_eq!(answerer.queued_candidates(), 0);
+
+        // After: applied straight away
+        for ice in &candidates[2..] {
+            answerer.add_ice_candidate(ice.clone());
+        }
+        assert_eq!(answerer.queued_candidates(), 0);
+
+        offerer.accept_answer(&answer);
+
+        for _ in 0..40 {
+            if remote_candidate_lines(&answerer) == candidates.len() {
+                break;
+            }
+            sleep(50).await;
+        }
+        assert_eq!(remote_candidate_lines(&answerer), candidates.len());
+
+        offerer.close();
+        answerer.close();
+    }
+
+    #[wasm_bindgen_test]
+    async fn colliding_offers_are_resolved_by_the_polite_peer() {
+        let config = RtcConfiguration::new();
+        let mut impolite = WebRtcPeer::with_configuration(2, false, &config).unwrap();
+        let mut polite = WebRtcPeer::with_configuration(1, true, &config).unwrap();
+        impolite.create_data_channels();
+        polite.create_data_channels();
+
+        impolite.create_offer();
+        polite.create_offer();
+        let impolite_offer = local_offer(&impolite).await;
+        let polite_offer = local_offer(&polite).await;
+
+        // Both offers are in flight; the impolite side keeps its own
+        assert!(!impolite.accept_offer(&polite_offer));
+        assert!(polite.accept_offer(&impolite_offer));
+
+        let answer = local_answer(&polite).await;
+        impolite.accept_answer(&answer);
+
+        for _ in 0..40 {
+            if impolite.pc.signaling_state() == RtcSignalingState::Stable {
+                break;
+            }
+            sleep(50).await;
+        }
+        assert_eq!(impolite.pc.signaling_state(), RtcSignalingState::Stable);
+        assert_eq!(polite.pc.signaling_state(), RtcSignalingState::Stable);
+
+        impolite.close();
+        polite.close();
+    }
+}
@@ -2,14 +2,7 @@
 //!
 //! Provides functions to display network connection status in the HTML UI.
 
-/// Log level for network status messages.
-#[derive(Clone, Copy, Debug)]
-pub enum NetLogLevel {
-    Info,
-    Success,
-    Warning,
-    Error,
-}
+pub use session::NetLogLevel;
 
 /// Add a line to the network status log in the UI.
 pub fn net_log(level: NetLogLevel, msg: &str) {
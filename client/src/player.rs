@@ -1,43 +1,15 @@
+use gameplay::collision::PhysicsWorld;
+use gameplay::mask::MaskType;
+use gameplay::plausibility::{MovementCheck, Violation};
+use gameplay::targeting::look_direction_from_angles;
 use glam::{Mat4, Vec3};
+use session::SnapshotBuffer;
+use web_time::Instant;
 use winit::keyboard::KeyCode;
 
 use crate::config::*;
 use crate::input::InputState;
-
-#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
-#[repr(u8)]
-pub enum MaskType {
-    #[default]
-    Ghost = 1,
-    Coward = 2,
-    Hunter = 3,
-}
-
-impl MaskType {
-    pub fn from_u8(v: u8) -> Self {
-        match v {
-            2 => Self::Coward,
-            3 => Self::Hunter,
-            _ => Self::Ghost,
-        }
-    }
-
-    pub fn next(self) -> Self {
-        match self {
-            Self::Ghost => Self::Coward,
-            Self::Coward => Self::Hunter,
-            Self::Hunter => Self::Ghost,
-        }
-    }
-
-    pub fn prev(self) -> Self {
-        match self {
-            Self::Ghost => Self::Hunter,
-            Self::Coward => Self::Ghost,
-            Self::Hunter => Self::Coward,
-        }
-    }
-}
+use crate::network::MovementViolation;
 
 pub struct Player {
     pub position: Vec3,
@@ -251,18 +223,7 @@ impl Player {
     }
 }
 
-/// Centralized direction calculations from yaw/pitch angles.
-/// Yaw: 0 = looking towards -Z, increases clockwise (looking down from above).
-/// Pitch: 0 = horizontal, positive = looking up, negative = looking down.
-pub fn look_direction_from_angles(yaw: f32, pitch: f32) -> Vec3 {
-    Vec3::new(
-        yaw.sin() * pitch.cos(),
-        pitch.sin(),
-        -yaw.cos() * pitch.cos(),
-    )
-    .normalize()
-}
-
+/// Horizontal direction calculations from yaw (see `look_direction_from_angles`).
 pub fn forward_direction_from_yaw(yaw: f32) -> Vec3 {
     Vec3::new(yaw.sin(), 0.0, -yaw.cos())
 }
@@ -281,7 +242,17 @@ pub struct RemotePlayer {
     pub velocity: Vec3,
     pub name: Option<String>,
     pub kills: u32,
-    prev_position: Vec3,
+    /// Round-trip time to this peer from the last connection stats, if known.
+    pub rtt_ms: Option<f64>,
+    /// We can't currently reach this player, so their state is stale.
+    pub connection_lost: bool,
+    /// Checks their state updates against the movement rules.
+    pub movement: MovementCheck,
+    /// Moves this round that broke the rules.
+    pub violations: u32,
+    /// Why we flagged this player as a likely cheat, once we have.
+    pub suspicion: Option<MovementViolation>,
+    snapshots: SnapshotBuffer,
 }
 
 impl RemotePlayer {
@@ -296,16 +267,100 @@ impl RemotePlayer {
             velocity: Vec3::ZERO,
             name: None,
             kills: 0,
-            prev_position: Vec3::ZERO,
+            rtt_ms: None,
+            connection_lost: false,
+            movement: MovementCheck::default(),
+            violations: 0,
+            suspicion: None,
+            snapshots: SnapshotBuffer::new(),
         }
     }
 
-    pub fn update_position(&mut self, new_position: Vec3, dt: f32) {
-        if dt > 0.0 {
-            self.velocity = (new_position - self.prev_position) / dt;
+    /// Buffer a state packet from this player. Returns false if it arrived
+    /// out of order and was dropped.
+    pub fn push_snapshot(
+        &mut self,
+        seq: u16,
+        sent_at: f64,
+        position: Vec3,
+        yaw: f32,
+        pitch: f32,
+    ) -> bool {
+        self.snapshots
+            .push(Instant::now(), seq, sent_at, position, yaw, pitch)
+    }
+
+    /// Check a state update against the movement rules. Once enough moves
+    /// in a round break them, flags the player and returns the reason to
+    /// report them for (only the first time).
+    pub fn check_movement(
+        &mut self,
+        physics: &PhysicsWorld,
+        position: Vec3,
+        sent_at: f64,
+        mask: u8,
+    ) -> Option<MovementViolation> {
+        let mut flagged = None;
+        for violation in self.movement.check(physics, position, sent_at, mask) {
+            log::debug!("Implausible move: {:?}", violation);
+            self.violations += 1;
+            if self.suspicion.is_none() && self.violations >= SUSPICION_THRESHOLD {
+                let reason = match violation {
+                    Violation::InvalidMask(_) => MovementViolation::InvalidMask,
+                    Violation::TooFast { .. } => MovementViolation::TooFast,
+                    Violation::Flying { .. } => MovementViolation::Flying,
+                    Violation::ThroughWall => MovementViolation::ThroughWall,
+                };
+                self.suspicion = Some(reason);
+                flagged = Some(reason);
+            }
+        }
+        flagged
+    }
+
+    /// Move to the interpolated pose for this frame. Players we've never had
+    /// a state packet from (e.g. debug mannequins) stay where they are.
+    pub fn interpolate(&mut self, now: Instant) {
+        if let Some(pose) = self.snapshots.sample(now) {
+            self.position = pose.position;
+            self.yaw = pose.yaw;
+            self.pitch = pose.pitch;
+            self.velocity = pose.velocity;
+        }
+    }
+
+    /// How far our view of this player trails their present, in seconds:
+    /// half the RTT plus the interpolation delay. By symmetry, also roughly
+    /// how stale their view of us is.
+    pub fn view_latency(&self) -> f32 {
+        if self.snapshots.is_empty() {
+            return 0.0;
         }
-        self.prev_position = self.position;
-        self.position = new_position;
+        let one_way = self.rtt_ms.unwrap_or(0.0) / 2000.0;
+        (one_way + self.snapshots.delay()) as f32
+    }
+
+    /// How far gaze checks rewind this player: what the shooter saw, up to
+    /// `MAX_LAG_COMPENSATION`.
+    pub fn lag_compensation(&self) -> f32 {
+        self.view_latency().min(MAX_LAG_COMPENSATION)
+    }
+
+    /// How far the pose gaze checks use runs ahead of the rendered pose, in
+    /// seconds. Zero unless `MAX_LAG_COMPENSATION` caps the rewind.
+    pub fn targeting_offset(&self) -> f32 {
+        self.view_latency() - self.lag_compensation()
+    }
+
+    /// Head position to test the local gaze against: as the player was
+    /// `lag_compensation` seconds before their present. That is the rendered
+    /// head unless the rewind is capped, see `targeting_offset`.
+    pub fn compensated_head_position(&self, now: Instant) -> Vec3 {
+        let one_way = self.rtt_ms.unwrap_or(0.0) / 2000.0;
+        self.snapshots
+            .sample_behind(now, one_way, self.lag_compensation() as f64)
+            .map(|pose| pose.position + Vec3::new(0.0, EYE_HEIGHT, 0.0))
+            .unwrap_or_else(|| self.head_position())
     }
 
     pub fn model_matrix(&self) -> Mat4 {
@@ -1,10 +1,11 @@
+use gameplay::mask::MaskType;
 use glam::{Mat4, Vec3};
 use std::sync::Arc;
 use wasm_bindgen::JsCast;
 use winit::window::Window;
 
 use crate::assets::{EMBEDDED_MASK, EMBEDDED_PLAYER, EMBEDDED_TOMBSTONE};
-use crate::config::PLAYER_HEIGHT;
+use crate::config::{LOST_CONNECTION_TINT, PLAYER_HEIGHT};
 use crate::game::GameState;
 use crate::glb::load_mesh_from_bytes;
 use crate::gpu::{camera_bind_group_layout, create_depth_texture};
@@ -64,8 +65,6 @@ use threat::ThreatIndicatorRenderer;
 use traits::Renderable;
 use view_mask::{MASK_ANIM_DURATION, ViewMaskRenderer};
 
-use crate::player::MaskType;
-
 pub struct RenderContext {
     pub window: Arc<Window>,
     pub surface: wgpu::Surface<'static>,
@@ -337,11 +336,14 @@ impl Renderer {
                 .values()
                 .filter(|remote| remote.is_alive)
                 .map(|remote| {
-                    (
-                        remote.model_matrix(),
-                        [1.0, 1.0, 1.0, 1.0_f32],
-                        remote.velocity,
-                    )
+                    // Players we can't reach are greyed out, not left to look
+                    // frozen in place
+                    let tint = if remote.connection_lost {
+                        LOST_CONNECTION_TINT
+                    } else {
+                        [1.0, 1.0, 1.0, 1.0]
+                    };
+                    (remote.model_matrix(), tint, remote.velocity)
                 })
                 .collect();
 
@@ -143,13 +143,37 @@ impl PhysicsWorld {
         (final_pos, on_ground)
     }
 
+    /// Distance from step height above `position` down to the ground, if
+    /// there's ground within a player height.
+    fn ground_distance(&self, position: Vec3) -> Option<f32> {
+        let ground_origin = position + Vec3::new(0.0, STEP_OVER_HEIGHT, 0.0);
+        self.cast_ray(ground_origin, Vec3::NEG_Y, PLAYER_HEIGHT)
+    }
+
+    /// Whether `position` is beyond the map's sides or below its lowest
+    /// point, where nobody gets without falling off.
+    pub fn is_off_map(&self, position: Vec3) -> bool {
+        let aabb = self.trimesh.local_aabb();
+        position.x < aabb.mins.x
+            || position.x > aabb.maxs.x
+            || position.z < aabb.mins.z
+            || position.z > aabb.maxs.z
+            || position.y < aabb.mins.y
+    }
+
+    /// Whether a player at `position` stands on the ground, by the same
+    /// test `move_player` uses.
+    pub fn is_on_ground(&self, position: Vec3) -> bool {
+        self.ground_distance(position)
+            .is_some_and(|d| d < STEP_OVER_HEIGHT + GROUND_SNAP_MARGIN)
+    }
+
     /// Get debug information about collision state at a position
     pub fn get_debug_info(&self, position: Vec3) -> CollisionDebug {
         let half_width = PLAYER_WIDTH / 2.0;
 
         // Ground check
-        let ground_origin = position + Vec3::new(0.0, STEP_OVER_HEIGHT, 0.0);
-        let ground_distance = self.cast_ray(ground_origin, Vec3::NEG_Y, PLAYER_HEIGHT);
+        let ground_distance = self.ground_distance(position);
         let on_ground = ground_distance
             .map(|d| d < STEP_OVER_HEIGHT + GROUND_SNAP_MARGIN)
             .unwrap_or(false);
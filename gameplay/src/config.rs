@@ -0,0 +1,49 @@
+// Player dimensions and physics
+pub const PLAYER_HEIGHT: f32 = 80.0;
+pub const PLAYER_WIDTH: f32 = 22.0;
+pub const EYE_HEIGHT: f32 = 70.0;
+pub const STEP_OVER_HEIGHT: f32 = 28.0;
+pub const GROUND_SNAP_MARGIN: f32 = 5.0;
+
+// Movement
+pub const MOVE_SPEED: f32 = 350.0;
+
+// Jump physics (derived: GRAVITY = 8*H/T², JUMP_VELOCITY = 4*H/T)
+pub const JUMP_HEIGHT: f32 = 70.0;
+pub const JUMP_DURATION: f32 = 0.4;
+pub const GRAVITY: f32 = 8.0 * JUMP_HEIGHT / (JUMP_DURATION * JUMP_DURATION);
+pub const JUMP_VELOCITY: f32 = 4.0 * JUMP_HEIGHT / JUMP_DURATION;
+
+// Mask system
+pub const COWARD_SPEED_MULTIPLIER: f32 = 1.5;
+
+// Coward dash (directional blink in look direction)
+pub const DASH_DISTANCE: f32 = 300.0; // max distance
+pub const DASH_SPEED: f32 = 1500.0; // units/second
+pub const DASH_COOLDOWN: f32 = 0.0; // seconds between dashes
+
+// Map
+pub const SPAWN_POINTS: &[[f32; 3]] = &[
+    [-408.5, -127.0, 2414.2],
+    [-196.2, -127.0, 2417.7],
+    [-277.4, -127.0, 2204.3],
+    [299.0, 0.0, 498.4],
+    [657.3, 0.0, 412.4],
+    [-58.9, 0.0, 347.5],
+    [-1322.2, 128.0, 2969.8],
+    [-1113.8, 5.0, 474.7],
+    [-251.3, 5.0, -373.5],
+    [1446.0, 128.0, -530.5],
+    [1536.0, 36.4, 1162.0],
+    [1583.0, 4.6, 2294.4],
+];
+
+// Collision: path check (anti-tunnelling)
+pub const PATH_HIT_MARGIN: f32 = 2.0;
+
+// Targeting system
+pub const TARGETING_ANGLE: f32 = 60.0;
+pub const TARGETING_DURATION: f32 = 1.0;
+pub const DEATH_GRACE_PERIOD: f32 = 0.05; // 50ms for continued targeting after death
+pub const HUNTER_KILL_DURATION: f32 = 0.7;
+pub const SUDDEN_DEATH_TARGETING_SPEED: f32 = 2.0; // gaze timers fill twice as fast in sudden death
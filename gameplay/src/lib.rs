@@ -0,0 +1,12 @@
+//! Gameplay rules shared by the client and the server.
+//!
+//! The client uses these to simulate movement and targeting locally; the
+//! server uses the exact same code to validate kill claims. Clients also
+//! check each other's reported movement against the same rules.
+
+pub mod collision;
+pub mod config;
+pub mod map;
+pub mod mask;
+pub mod plausibility;
+pub mod targeting;
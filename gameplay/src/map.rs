@@ -0,0 +1,73 @@
+use glam::Vec3;
+use gltf::buffer::Source;
+
+use crate::collision::PhysicsWorld;
+
+/// Collision geometry extracted from a map GLB.
+pub struct CollisionMesh {
+    pub vertices: Vec<Vec3>,
+    pub indices: Vec<[u32; 3]>,
+    pub bounds: (Vec3, Vec3),
+}
+
+impl CollisionMesh {
+    /// Load triangle geometry from GLB bytes, skipping textures.
+    ///
+    /// The map is rotated 180 degrees around Z (X and Y negated) to match the
+    /// game's coordinate system, the same transform the renderer applies.
+    pub fn from_glb(data: &[u8]) -> Result<Self, String> {
+        let gltf =
+            gltf::Gltf::from_slice(data).map_err(|e| format!("Failed to load GLB: {}", e))?;
+        let blob = gltf.blob.as_deref();
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        for mesh in gltf.document.meshes() {
+            for primitive in mesh.primitives() {
+                let reader = primitive.reader(|buffer| match buffer.source() {
+                    Source::Bin => blob,
+                    Source::Uri(_) => None,
+                });
+
+                let Some(positions) = reader.read_positions() else {
+                    continue;
+                };
+
+                let base_idx = vertices.len() as u32;
+                vertices.extend(positions.map(|[x, y, z]| Vec3::new(-x, -y, z)));
+                let vertex_count = vertices.len() as u32 - base_idx;
+
+                let primitive_indices: Vec<u32> = reader
+                    .read_indices()
+                    .map(|i| i.into_u32().collect())
+                    .unwrap_or_else(|| (0..vertex_count).collect());
+                for chunk in primitive_indices.chunks(3) {
+                    if chunk.len() == 3 {
+                        indices.push([
+                            base_idx + chunk[0],
+                            base_idx + chunk[1],
+                            base_idx + chunk[2],
+                        ]);
+                    }
+                }
+            }
+        }
+
+        let bounds = vertices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), v| (min.min(*v), max.max(*v)),
+        );
+
+        Ok(Self {
+            vertices,
+            indices,
+            bounds,
+        })
+    }
+
+    /// Build the physics world used for movement and line-of-sight checks.
+    pub fn physics_world(&self) -> Option<PhysicsWorld> {
+        PhysicsWorld::new(&self.vertices, &self.indices)
+    }
+}
@@ -0,0 +1,45 @@
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[repr(u8)]
+pub enum MaskType {
+    #[default]
+    Ghost = 1,
+    Coward = 2,
+    Hunter = 3,
+}
+
+impl MaskType {
+    /// Unknown values fall back to Ghost; see `try_from_u8` to catch them.
+    pub fn from_u8(v: u8) -> Self {
+        Self::try_from_u8(v).unwrap_or_default()
+    }
+
+    pub fn try_from_u8(v: u8) -> Option<Self> {
+        match v {
+            1 => Some(Self::Ghost),
+            2 => Some(Self::Coward),
+            3 => Some(Self::Hunter),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Ghost => Self::Coward,
+            Self::Coward => Self::Hunter,
+            Self::Hunter => Self::Ghost,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        match self {
+            Self::Ghost => Self::Hunter,
+            Self::Coward => Self::Ghost,
+            Self::Hunter => Self::Coward,
+        }
+    }
+
+    /// Cowards trade their gaze for speed and cannot kill.
+    pub fn can_kill(self) -> bool {
+        self != Self::Coward
+    }
+}
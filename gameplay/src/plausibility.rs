@@ -0,0 +1,311 @@
+//! Plausibility checks on movement reported by other players.
+//!
+//! Every player simulates their own movement and sends the result, so
+//! nothing stops a modified client from teleporting, flying or sending a
+//! mask that doesn't exist. A `MovementCheck` follows one player's state
+//! updates and flags the ones our movement rules couldn't have produced.
+//!
+//! The limits leave room for timing noise, but the odd glitch can still get
+//! flagged; it's repeated violations that point to cheating.
+
+use glam::Vec3;
+
+use crate::collision::PhysicsWorld;
+use crate::config::*;
+use crate::mask::MaskType;
+
+/// Timing error we allow for: positions are sampled once a frame but
+/// stamped when sent, and the sender's estimate of the server clock drifts.
+const TIMING_SLACK: f32 = 0.1; // seconds
+
+/// Updates this close to a spawn point may be a respawn rather than a move,
+/// if the player had fallen off the map.
+const SPAWN_RADIUS: f32 = 2.0 * PLAYER_WIDTH;
+
+/// Over longer gaps between updates, the straight line between them says
+/// little about the path taken, so we don't check it for walls.
+const MAX_PATH_CHECK_GAP: f32 = 0.1; // seconds
+
+/// Something the movement rules don't allow.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Violation {
+    /// A mask value that isn't one of ours.
+    InvalidMask(u8),
+    /// Covered more ground than running or dashing allows.
+    TooFast { distance: f32, allowed: f32 },
+    /// Further above the last ground than a jump or dash reaches.
+    Flying { height: f32, allowed: f32 },
+    /// Moved through map geometry.
+    ThroughWall,
+}
+
+#[derive(Clone, Copy)]
+struct Sample {
+    position: Vec3,
+    time: f64,
+    mask: MaskType,
+}
+
+/// Follows one player's state updates.
+#[derive(Default)]
+pub struct MovementCheck {
+    last: Option<Sample>,
+    /// Height of the ground the player last stood on.
+    ground_height: Option<f32>,
+    /// First update since then that had them in the air: the latest they
+    /// can have left the ground.
+    airborne_since: Option<f64>,
+    /// Whether they were a coward around leaving the ground, and so may
+    /// have dashed up rather than jumped.
+    may_have_dashed: bool,
+}
+
+impl MovementCheck {
+    /// Forget the movement so far, for when updates stop being checked for
+    /// a while (the player was dead or spectating).
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Check an update putting the player at `position` with `mask` at
+    /// `time` (seconds on the server clock). Updates no newer than the last
+    /// one are only checked for their mask.
+    pub fn check(
+        &mut self,
+        physics: &PhysicsWorld,
+        position: Vec3,
+        time: f64,
+        mask: u8,
+    ) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let mask = MaskType::try_from_u8(mask).unwrap_or_else(|| {
+            violations.push(Violation::InvalidMask(mask));
+            MaskType::default()
+        });
+        if self.last.is_some_and(|last| time <= last.time) {
+            return violations;
+        }
+        let sample = Sample {
+            position,
+            time,
+            mask,
+        };
+
+        // Falling off the map puts players back on a spawn point. Deaths
+        // reset the check, so those respawns never look like a move.
+        let respawned = self
+            .last
+            .is_some_and(|last| physics.is_off_map(last.position))
+            && is_near_spawn_point(position);
+        if respawned || physics.is_on_ground(position) {
+            self.ground_height = Some(position.y);
+            self.airborne_since = None;
+            self.may_have_dashed = false;
+        } else {
+            self.airborne_since.get_or_insert(time);
+            self.may_have_dashed |= mask == MaskType::Coward
+                || self.last.is_some_and(|last| last.mask == MaskType::Coward);
+        }
+
+        if let Some(last) = self.last
+            && !respawned
+        {
+            violations.extend(check_speed(last, sample));
+            if !physics_allows_path(physics, last, sample) {
+                violations.push(Violation::ThroughWall);
+            }
+        }
+        if let (Some(ground_height), Some(airborne_since)) =
+            (self.ground_height, self.airborne_since)
+        {
+            let height = position.y - ground_height;
+            let allowed = height_limit((time - airborne_since) as f32, self.may_have_dashed)
+                + STEP_OVER_HEIGHT;
+            if height > allowed {
+                violations.push(Violation::Flying { height, allowed });
+            }
+        }
+
+        self.last = Some(sample);
+        violations
+    }
+}
+
+/// Flag moves covering more horizontal ground than the masks on either end
+/// allow.
+fn check_speed(from: Sample, to: Sample) -> Option<Violation> {
+    let dt = (to.time - from.time) as f32;
+    let delta = to.position - from.position;
+    let distance = Vec3::new(delta.x, 0.0, delta.z).length();
+    let allowed = top_speed(from.mask).max(top_speed(to.mask)) * (dt + TIMING_SLACK);
+    (distance > allowed).then_some(Violation::TooFast { distance, allowed })
+}
+
+/// Fastest horizontal speed with a mask.
+fn top_speed(mask: MaskType) -> f32 {
+    match mask {
+        // With no DASH_COOLDOWN, dashes can follow each other back to back
+        MaskType::Coward => (MOVE_SPEED * COWARD_SPEED_MULTIPLIER).max(DASH_SPEED),
+        _ => MOVE_SPEED,
+    }
+}
+
+/// Whether the straight path between two close updates stays clear of the
+/// map, by the same test players' own movement is clamped with. Collision
+/// keeps players half a width from walls, so a legal path between two
+/// close updates doesn't clip them.
+fn physics_allows_path(physics: &PhysicsWorld, from: Sample, to: Sample) -> bool {
+    if (to.time - from.time) as f32 > MAX_PATH_CHECK_GAP {
+        return true;
+    }
+    // The destination comes back untouched unless something is in the way
+    physics.clamp_desired_to_path(from.position, to.position) == to.position
+}
+
+/// Highest a player can be above the ground they left, `t` seconds after
+/// they did: the top of a jump (or dash), then a fall from rest.
+fn height_limit(t: f32, may_have_dashed: bool) -> f32 {
+    let (rise, rise_time) = if may_have_dashed {
+        (DASH_DISTANCE, DASH_DISTANCE / DASH_SPEED)
+    } else {
+        (JUMP_HEIGHT, JUMP_VELOCITY / GRAVITY)
+    };
+    let falling = (t - rise_time - TIMING_SLACK).max(0.0);
+    rise - 0.5 * GRAVITY * falling * falling
+}
+
+fn is_near_spawn_point(position: Vec3) -> bool {
+    SPAWN_POINTS.iter().any(|&[x, y, z]| {
+        let offset = Vec3::new(position.x - x, 0.0, position.z - z);
+        offset.length() <= SPAWN_RADIUS && position.y <= y + STEP_OVER_HEIGHT
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GHOST: u8 = MaskType::Ghost as u8;
+    const COWARD: u8 = MaskType::Coward as u8;
+
+    /// A floor at y = 0 with a wall across it at x = 200, away from the
+    /// spawn points.
+    fn world() -> PhysicsWorld {
+        let vertices = [
+            Vec3::new(-5000.0, 0.0, -5000.0),
+            Vec3::new(5000.0, 0.0, -5000.0),
+            Vec3::new(5000.0, 0.0, 5000.0),
+            Vec3::new(-5000.0, 0.0, 5000.0),
+            Vec3::new(200.0, 0.0, -1000.0),
+            Vec3::new(200.0, 0.0, -500.0),
+            Vec3::new(200.0, 300.0, -500.0),
+            Vec3::new(200.0, 300.0, -1000.0),
+        ];
+        let indices = [[0, 2, 1], [0, 3, 2], [4, 5, 6], [4, 6, 7]];
+        PhysicsWorld::new(&vertices, &indices).unwrap()
+    }
+
+    /// Feed `(x, y, time)` updates along z = -750 and collect what's flagged.
+    fn run(mask: u8, updates: &[(f32, f32, f64)]) -> Vec<Violation> {
+        let physics = world();
+        let mut check = MovementCheck::default();
+        updates
+            .iter()
+            .flat_map(|&(x, y, time)| check.check(&physics, Vec3::new(x, y, -750.0), time, mask))
+            .collect()
+    }
+
+    #[test]
+    fn running_and_jumping_pass() {
+        let mut updates = Vec::new();
+        let mut y = 0.0;
+        let mut vy = 0.0;
+        for tick in 0..60 {
+            let t = tick as f32 / 30.0;
+            // Jump every half second
+            if y <= 0.0 && tick % 15 == 0 {
+                vy = JUMP_VELOCITY;
+            }
+            y = (y + vy / 30.0).max(0.0);
+            vy -= GRAVITY / 30.0;
+            updates.push((-1000.0 + MOVE_SPEED * t, y, t as f64));
+        }
+        assert_eq!(run(GHOST, &updates), vec![]);
+    }
+
+    #[test]
+    fn teleporting_is_too_fast() {
+        let violations = run(GHOST, &[(-1000.0, 0.0, 0.0), (-500.0, 0.0, 0.1)]);
+        assert!(matches!(violations[..], [Violation::TooFast { .. }]));
+    }
+
+    #[test]
+    fn cowards_may_dash() {
+        let dash = [(-1000.0, 0.0, 0.0), (-700.0, 0.0, 0.2)];
+        assert_eq!(run(COWARD, &dash), vec![]);
+        assert!(!run(GHOST, &dash).is_empty());
+    }
+
+    #[test]
+    fn hovering_is_flying() {
+        let violations = run(
+            GHOST,
+            &[
+                (-1000.0, 0.0, 0.0),
+                (-1000.0, 60.0, 0.2),
+                (-1000.0, 60.0, 1.0),
+            ],
+        );
+        assert!(matches!(violations[..], [Violation::Flying { .. }]));
+    }
+
+    #[test]
+    fn walking_through_walls_is_flagged() {
+        let violations = run(GHOST, &[(190.0, 0.0, 0.0), (210.0, 0.0, 0.05)]);
+        assert_eq!(violations, vec![Violation::ThroughWall]);
+    }
+
+    #[test]
+    fn unknown_masks_are_flagged() {
+        assert_eq!(
+            run(7, &[(-1000.0, 0.0, 0.0)]),
+            vec![Violation::InvalidMask(7)]
+        );
+    }
+
+    #[test]
+    fn respawning_is_not_a_teleport() {
+        let [x, y, z] = SPAWN_POINTS[0];
+        let physics = world();
+        let mut check = MovementCheck::default();
+        // Falling below the floor
+        assert!(
+            check
+                .check(&physics, Vec3::new(-1000.0, -300.0, -750.0), 0.0, GHOST)
+                .is_empty()
+        );
+        assert!(
+            check
+                .check(&physics, Vec3::new(x, y, z), 0.1, GHOST)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn teleporting_to_a_spawn_point_is_flagged() {
+        let [x, y, z] = SPAWN_POINTS[3];
+        let physics = world();
+        let mut check = MovementCheck::default();
+        assert!(
+            check
+                .check(&physics, Vec3::new(-1000.0, 0.0, -750.0), 0.0, GHOST)
+                .is_empty()
+        );
+        let violations = check.check(&physics, Vec3::new(x, y, z), 0.1, GHOST);
+        assert!(
+            violations
+                .iter()
+                .any(|v| matches!(v, Violation::TooFast { .. }))
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use glam::Vec3;
+
+use crate::collision::PhysicsWorld;
+use crate::config::*;
+use crate::mask::MaskType;
+
+/// Centralized direction calculations from yaw/pitch angles.
+/// Yaw: 0 = looking towards -Z, increases clockwise (looking down from above).
+/// Pitch: 0 = horizontal, positive = looking up, negative = looking down.
+pub fn look_direction_from_angles(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        yaw.sin() * pitch.cos(),
+        pitch.sin(),
+        -yaw.cos() * pitch.cos(),
+    )
+    .normalize()
+}
+
+/// How long a target must stay in the gaze cone before dying.
+pub fn kill_duration(mask: MaskType) -> f32 {
+    match mask {
+        MaskType::Hunter => HUNTER_KILL_DURATION,
+        _ => TARGETING_DURATION,
+    }
+}
+
+/// Check whether `target` is inside the gaze cone of someone at `eye`
+/// looking along `yaw`/`pitch`, with no map geometry in between.
+///
+/// This is the single rule behind kills, threat indicators and the
+/// server-side kill validation.
+pub fn is_in_gaze(physics: &PhysicsWorld, eye: Vec3, yaw: f32, pitch: f32, target: Vec3) -> bool {
+    let to_target = target - eye;
+    let distance = to_target.length();
+    if distance < 1.0 {
+        return false;
+    }
+
+    let look_dir = look_direction_from_angles(yaw, pitch);
+    let half_angle_rad = (TARGETING_ANGLE / 2.0).to_radians();
+    let dot = look_dir.dot(to_target / distance).clamp(-1.0, 1.0);
+
+    dot.acos() < half_angle_rad && physics.is_visible(eye, target)
+}
@@ -0,0 +1,142 @@
+//! ICE configuration the server publishes at `/ice-servers`.
+//!
+//! Clients build their peer connections from this list alone, so which
+//! STUN and TURN servers get used is up to whoever runs the server.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Which kinds of ICE server to use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IcePolicy {
+    /// STUN and TURN servers alike.
+    #[default]
+    All,
+    /// TURN servers only, and only relayed candidates: every peer
+    /// connection goes through a relay.
+    RelayOnly,
+    /// STUN servers only: peers connect directly or not at all.
+    StunOnly,
+}
+
+impl IcePolicy {
+    /// Whether a server URL is allowed under this policy.
+    pub fn allows(self, url: &str) -> bool {
+        let is_turn = url.starts_with("turn:") || url.starts_with("turns:");
+        match self {
+            Self::All => true,
+            Self::RelayOnly => is_turn,
+            Self::StunOnly => !is_turn,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::RelayOnly => "relay-only",
+            Self::StunOnly => "stun-only",
+        }
+    }
+}
+
+impl fmt::Display for IcePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for IcePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Self::All),
+            "relay-only" => Ok(Self::RelayOnly),
+            "stun-only" => Ok(Self::StunOnly),
+            _ => Err(format!(
+                "Unknown ICE policy '{}' (expected all, relay-only or stun-only)",
+                s
+            )),
+        }
+    }
+}
+
+/// One entry of `RTCConfiguration.iceServers`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IceServer {
+    pub urls: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+}
+
+/// Response of `/ice-servers`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct IceServerList {
+    /// Servers in the order they should be tried, already filtered by
+    /// `policy`.
+    #[serde(rename = "iceServers")]
+    pub ice_servers: Vec<IceServer>,
+    pub policy: IcePolicy,
+    /// Seconds the list, and the credentials in it, stay valid.
+    pub ttl: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_matches_rtc_configuration() {
+        let list = IceServerList {
+            ice_servers: vec![
+                IceServer {
+                    urls: vec!["stun:example.com:3478".to_string()],
+                    username: None,
+                    credential: None,
+                },
+                IceServer {
+                    urls: vec!["turn:example.com:3478".to_string()],
+                    username: Some("1700000000:ab".to_string()),
+                    credential: Some("c2VjcmV0".to_string()),
+                },
+            ],
+            policy: IcePolicy::RelayOnly,
+            ttl: 600,
+        };
+        let json = serde_json::to_value(&list).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "iceServers": [
+                    { "urls": ["stun:example.com:3478"] },
+                    {
+                        "urls": ["turn:example.com:3478"],
+                        "username": "1700000000:ab",
+                        "credential": "c2VjcmV0",
+                    },
+                ],
+                "policy": "relay-only",
+                "ttl": 600,
+            })
+        );
+        assert_eq!(serde_json::from_value::<IceServerList>(json).unwrap(), list);
+    }
+
+    #[test]
+    fn policies_parse_and_filter() {
+        for policy in [IcePolicy::All, IcePolicy::RelayOnly, IcePolicy::StunOnly] {
+            assert_eq!(policy.as_str().parse(), Ok(policy));
+        }
+        assert!("relay".parse::<IcePolicy>().is_err());
+
+        assert!(IcePolicy::RelayOnly.allows("turns:example.com:5349"));
+        assert!(!IcePolicy::RelayOnly.allows("stun:example.com:3478"));
+        assert!(IcePolicy::StunOnly.allows("stun:example.com:3478"));
+        assert!(!IcePolicy::StunOnly.allows("turn:example.com:3478"));
+    }
+}
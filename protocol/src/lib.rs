@@ -0,0 +1,633 @@
+//! Signaling protocol shared by the client and the server.
+//!
+//! Every message exchanged over the `/ws` WebSocket is defined here, so a
+//! protocol change has to compile on both sides. Messages are JSON objects
+//! tagged by a `type` field.
+//!
+//! Times on the wire (phase deadlines, kill claims) are milliseconds on the
+//! server's clock, counted from when it started. Clients estimate that clock
+//! with `ping`/`pong` exchanges.
+//!
+//! The binary player-state packets peers exchange directly live in [`state`],
+//! and the ICE server list served over HTTP in [`ice`].
+
+use serde::{Deserialize, Serialize};
+
+pub mod ice;
+pub mod state;
+
+/// Unique identifier the server assigns to each connected client.
+pub type ClientId = u64;
+
+/// Game phase, driven by the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamePhase {
+    WaitingForPlayers,
+    GracePeriod,
+    Playing,
+    /// The round ran out of time: kills are faster, and if nobody wins before
+    /// this ends too, the round is a draw.
+    SuddenDeath,
+    Victory,
+}
+
+impl GamePhase {
+    /// Whether a round is being played and kills count.
+    pub fn is_round_active(self) -> bool {
+        matches!(self, Self::Playing | Self::SuddenDeath)
+    }
+}
+
+/// Round rules the server is running with.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameRules {
+    /// Seconds of no-damage countdown before each round.
+    #[serde(rename = "gracePeriod")]
+    pub grace_period: f32,
+    /// Seconds the round-end screen stays up.
+    #[serde(rename = "victoryDuration")]
+    pub victory_duration: f32,
+    /// Seconds of regular play before sudden death; 0 means no limit.
+    #[serde(rename = "roundTimeLimit")]
+    pub round_time_limit: f32,
+    /// Seconds of sudden death before the round ends in a draw.
+    #[serde(rename = "suddenDeathDuration")]
+    pub sudden_death_duration: f32,
+    /// Players needed before a round starts.
+    #[serde(rename = "minPlayers")]
+    pub min_players: u32,
+}
+
+/// Basic peer info from the server.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub id: ClientId,
+}
+
+/// Which peer-to-peer data channel a payload belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelKind {
+    /// Unreliable, unordered channel for high-frequency position updates.
+    State,
+    /// Reliable, ordered channel for game events (introductions).
+    Events,
+}
+
+/// Why the server turned down a kill claim.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KillRejectReason {
+    /// The victim killed us first: of two players claiming each other, only
+    /// the earlier claim stands.
+    KilledFirst,
+    /// We were already dead when the claim arrived.
+    KillerDead,
+    /// The claim didn't check out (victim out of sight, wrong mask, ...).
+    Invalid,
+}
+
+/// Why a client reports another's movement as implausible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MovementViolation {
+    /// Sent a mask value that doesn't exist.
+    InvalidMask,
+    /// Moved faster than running or dashing allows.
+    TooFast,
+    /// Stayed higher off the ground than a jump or dash reaches.
+    Flying,
+    /// Moved through map geometry.
+    ThroughWall,
+}
+
+/// Default `Welcome::state_stride`, for servers that don't thin out state.
+fn every_packet() -> u16 {
+    1
+}
+
+/// How game traffic gets between the players in a room.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Topology {
+    /// Every pair of players connects directly over WebRTC.
+    #[default]
+    Mesh,
+    /// Every player sends to the server once, over the WebSocket, and the
+    /// server passes it on to the others.
+    Star,
+}
+
+impl std::str::FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mesh" => Ok(Self::Mesh),
+            "star" => Ok(Self::Star),
+            _ => Err(format!("Unknown topology '{}' (expected mesh or star)", s)),
+        }
+    }
+}
+
+/// Credentials for reclaiming our slot after the WebSocket dropped.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Resume {
+    #[serde(rename = "clientId")]
+    pub client_id: ClientId,
+    pub token: String,
+}
+
+// ============================================================================
+// Client -> server
+// ============================================================================
+
+/// Messages sent by a client to the signaling server.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    /// Join the game, optionally in a specific room.
+    ///
+    /// With `resume`, the server hands back our previous slot if it is still
+    /// being held for us; otherwise this is a regular join.
+    #[serde(rename = "join")]
+    Join {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        room: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        resume: Option<Resume>,
+    },
+
+    /// Leave the game.
+    #[serde(rename = "leave")]
+    Leave,
+
+    /// Notify the server that we died.
+    #[serde(rename = "player_died")]
+    PlayerDied,
+
+    /// Position and orientation sample the server uses to validate kills.
+    #[serde(rename = "pose")]
+    Pose {
+        position: [f32; 3],
+        yaw: f32,
+        pitch: f32,
+        mask: u8,
+    },
+
+    /// Claim that we killed another player (confirmed or rejected by the server).
+    #[serde(rename = "kill-claim")]
+    KillClaim {
+        #[serde(rename = "victimId")]
+        victim_id: ClientId,
+        /// When our gaze completed the kill, on the server clock. The server
+        /// orders conflicting claims by it.
+        #[serde(rename = "killedAt")]
+        killed_at: f64,
+    },
+
+    /// Report a peer whose state updates keep breaking the movement rules.
+    #[serde(rename = "report-peer")]
+    ReportPeer {
+        #[serde(rename = "peerId")]
+        peer_id: ClientId,
+        reason: MovementViolation,
+    },
+
+    /// Clock sync request; the server answers right away with `pong`.
+    #[serde(rename = "ping")]
+    Ping {
+        /// Our local clock when sending, echoed back.
+        #[serde(rename = "clientTime")]
+        client_time: f64,
+    },
+
+    /// Send a WebRTC offer to a peer.
+    #[serde(rename = "offer")]
+    Offer {
+        #[serde(rename = "targetId")]
+        target_id: ClientId,
+        sdp: String,
+        /// Renegotiates ICE on the existing connection instead of starting
+        /// a new one.
+        #[serde(
+            rename = "iceRestart",
+            default,
+            skip_serializing_if = "std::ops::Not::not"
+        )]
+        ice_restart: bool,
+    },
+
+    /// Send a WebRTC answer to a peer.
+    #[serde(rename = "answer")]
+    Answer {
+        #[serde(rename = "targetId")]
+        target_id: ClientId,
+        sdp: String,
+    },
+
+    /// Send an ICE candidate to a peer.
+    #[serde(rename = "ice-candidate")]
+    IceCandidate {
+        #[serde(rename = "targetId")]
+        target_id: ClientId,
+        candidate: String,
+        #[serde(rename = "sdpMid")]
+        sdp_mid: Option<String>,
+        #[serde(rename = "sdpMLineIndex")]
+        sdp_m_line_index: Option<u16>,
+    },
+
+    /// Forward a data channel payload to a peer we can't reach over WebRTC.
+    #[serde(rename = "relay")]
+    Relay {
+        #[serde(rename = "targetId")]
+        target_id: ClientId,
+        channel: ChannelKind,
+        /// Text payload, or base64 of a binary one.
+        data: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        binary: bool,
+    },
+
+    /// Send a data channel payload to everyone else in the room, in a
+    /// `Topology::Star` room. It arrives as a `relay`.
+    #[serde(rename = "broadcast")]
+    Broadcast {
+        channel: ChannelKind,
+        /// Text payload, or base64 of a binary one.
+        data: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        binary: bool,
+    },
+}
+
+// ============================================================================
+// Server -> client
+// ============================================================================
+
+/// Messages sent by the signaling server to a client.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ServerMessage {
+    /// Welcome message with our ID and list of existing peers.
+    #[serde(rename = "welcome")]
+    Welcome {
+        #[serde(rename = "clientId")]
+        client_id: ClientId,
+        /// Room code we were placed in.
+        room: String,
+        peers: Vec<PeerInfo>,
+        #[serde(rename = "gamePhase")]
+        game_phase: GamePhase,
+        /// When the current phase ends on the server clock; absent if it
+        /// has no time limit.
+        #[serde(
+            rename = "phaseEndsAt",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        phase_ends_at: Option<f64>,
+        /// The server clock when this was sent, a first estimate until a
+        /// `pong` arrives.
+        #[serde(rename = "serverTime")]
+        server_time: f64,
+        rules: GameRules,
+        /// Secret to present in `Join::resume` if the connection drops.
+        #[serde(rename = "resumeToken")]
+        resume_token: String,
+        /// Whether this reclaims a previous slot rather than starting fresh.
+        resumed: bool,
+        /// Whether our slot is alive this round.
+        alive: bool,
+        /// Kills credited to our slot.
+        kills: u32,
+        /// How to reach the other players.
+        #[serde(default)]
+        topology: Topology,
+        /// Between players out of each other's interest radius, the server
+        /// only passes on state packets whose sequence number is a multiple
+        /// of this; 1 when every packet gets through.
+        #[serde(rename = "stateStride", default = "every_packet")]
+        state_stride: u16,
+    },
+
+    /// A new peer joined the room.
+    #[serde(rename = "peer-joined")]
+    PeerJoined {
+        #[serde(rename = "peerId")]
+        peer_id: ClientId,
+    },
+
+    /// A peer came back after a dropped connection and will send a new offer.
+    #[serde(rename = "peer-reconnected")]
+    PeerReconnected {
+        #[serde(rename = "peerId")]
+        peer_id: ClientId,
+    },
+
+    /// A peer left the room.
+    #[serde(rename = "peer-left")]
+    PeerLeft {
+        #[serde(rename = "peerId")]
+        peer_id: ClientId,
+    },
+
+    /// Game phase changed.
+    #[serde(rename = "game-phase")]
+    GamePhase {
+        phase: GamePhase,
+        /// When the phase ends on the server clock; absent if it has no time
+        /// limit.
+        #[serde(rename = "endsAt", default, skip_serializing_if = "Option::is_none")]
+        ends_at: Option<f64>,
+        /// Set when the round ended because sudden death ran out, with
+        /// several players still alive.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        draw: bool,
+    },
+
+    /// The server confirmed a kill.
+    #[serde(rename = "player-killed")]
+    PlayerKilled {
+        #[serde(rename = "killerId")]
+        killer_id: ClientId,
+        #[serde(rename = "victimId")]
+        victim_id: ClientId,
+    },
+
+    /// The server rejected one of our kill claims.
+    #[serde(rename = "kill-rejected")]
+    KillRejected {
+        #[serde(rename = "victimId")]
+        victim_id: ClientId,
+        reason: KillRejectReason,
+    },
+
+    /// Answer to a `ping`.
+    #[serde(rename = "pong")]
+    Pong {
+        /// The `clientTime` of the ping.
+        #[serde(rename = "clientTime")]
+        client_time: f64,
+        /// The server clock when the ping was answered.
+        #[serde(rename = "serverTime")]
+        server_time: f64,
+    },
+
+    /// WebRTC offer from another peer.
+    #[serde(rename = "offer")]
+    Offer {
+        #[serde(rename = "fromId")]
+        from_id: ClientId,
+        sdp: String,
+        /// An ICE restart for the existing connection.
+        #[serde(
+            rename = "iceRestart",
+            default,
+            skip_serializing_if = "std::ops::Not::not"
+        )]
+        ice_restart: bool,
+    },
+
+    /// WebRTC answer from another peer.
+    #[serde(rename = "answer")]
+    Answer {
+        #[serde(rename = "fromId")]
+        from_id: ClientId,
+        sdp: String,
+    },
+
+    /// ICE candidate from another peer.
+    #[serde(rename = "ice-candidate")]
+    IceCandidate {
+        #[serde(rename = "fromId")]
+        from_id: ClientId,
+        candidate: String,
+        #[serde(rename = "sdpMid")]
+        sdp_mid: Option<String>,
+        #[serde(rename = "sdpMLineIndex")]
+        sdp_m_line_index: Option<u16>,
+    },
+
+    /// Message from the server operators, shown to every player.
+    #[serde(rename = "notice")]
+    Notice { message: String },
+
+    /// We were removed from the game; the server closes the socket next.
+    #[serde(rename = "kicked")]
+    Kicked { reason: String },
+
+    /// Data channel payload relayed from a peer we can't reach over WebRTC,
+    /// or from any peer in a `Topology::Star` room.
+    #[serde(rename = "relay")]
+    Relay {
+        #[serde(rename = "fromId")]
+        from_id: ClientId,
+        channel: ChannelKind,
+        /// Text payload, or base64 of a binary one.
+        data: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        binary: bool,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(msg: T) {
+        let json = serde_json::to_string(&msg).unwrap();
+        let parsed: T = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, msg, "round trip through {}", json);
+    }
+
+    #[test]
+    fn client_messages_round_trip() {
+        let messages = vec![
+            ClientMessage::Join {
+                room: None,
+                name: None,
+                resume: None,
+            },
+            ClientMessage::Join {
+                room: Some("playtest".to_string()),
+                name: Some("Zoé Martin".to_string()),
+                resume: Some(Resume {
+                    client_id: 3,
+                    token: "abc123".to_string(),
+                }),
+            },
+            ClientMessage::Leave,
+            ClientMessage::PlayerDied,
+            ClientMessage::Pose {
+                position: [1.0, -2.5, 300.0],
+                yaw: 0.5,
+                pitch: -0.25,
+                mask: 3,
+            },
+            ClientMessage::KillClaim {
+                victim_id: 7,
+                killed_at: 1250.0,
+            },
+            ClientMessage::ReportPeer {
+                peer_id: 4,
+                reason: MovementViolation::TooFast,
+            },
+            ClientMessage::Ping {
+                client_time: 812.25,
+            },
+            ClientMessage::Offer {
+                target_id: 1,
+                sdp: "v=0".to_string(),
+                ice_restart: true,
+            },
+            ClientMessage::Answer {
+                target_id: 2,
+                sdp: "v=0".to_string(),
+            },
+            ClientMessage::IceCandidate {
+                target_id: 3,
+                candidate: "candidate:1 1 udp 1 10.0.0.1 5000 typ host".to_string(),
+                sdp_mid: Some("0".to_string()),
+                sdp_m_line_index: Some(0),
+            },
+            ClientMessage::Relay {
+                target_id: 4,
+                channel: ChannelKind::State,
+                data: "AQMAAA==".to_string(),
+                binary: true,
+            },
+            ClientMessage::Broadcast {
+                channel: ChannelKind::Events,
+                data: "{\"type\":\"introduction\",\"name\":\"Zoé\"}".to_string(),
+                binary: false,
+            },
+        ];
+        for msg in messages {
+            round_trip(msg);
+        }
+    }
+
+    #[test]
+    fn server_messages_round_trip() {
+        let messages = vec![
+            ServerMessage::Welcome {
+                client_id: 4,
+                room: "public".to_string(),
+                peers: vec![PeerInfo { id: 1 }, PeerInfo { id: 2 }],
+                game_phase: GamePhase::GracePeriod,
+                phase_ends_at: Some(17_500.0),
+                server_time: 10_000.0,
+                rules: GameRules {
+                    grace_period: 10.0,
+                    victory_duration: 10.0,
+                    round_time_limit: 180.0,
+                    sudden_death_duration: 30.0,
+                    min_players: 2,
+                },
+                resume_token: "abc123".to_string(),
+                resumed: true,
+                alive: false,
+                kills: 2,
+                topology: Topology::Star,
+                state_stride: 3,
+            },
+            ServerMessage::PeerJoined { peer_id: 5 },
+            ServerMessage::PeerReconnected { peer_id: 5 },
+            ServerMessage::PeerLeft { peer_id: 5 },
+            ServerMessage::GamePhase {
+                phase: GamePhase::Victory,
+                ends_at: Some(40_000.0),
+                draw: true,
+            },
+            ServerMessage::GamePhase {
+                phase: GamePhase::SuddenDeath,
+                ends_at: Some(30_000.0),
+                draw: false,
+            },
+            ServerMessage::Pong {
+                client_time: 812.25,
+                server_time: 10_004.5,
+            },
+            ServerMessage::PlayerKilled {
+                killer_id: 1,
+                victim_id: 2,
+            },
+            ServerMessage::KillRejected {
+                victim_id: 2,
+                reason: KillRejectReason::KilledFirst,
+            },
+            ServerMessage::Offer {
+                from_id: 1,
+                sdp: "v=0".to_string(),
+                ice_restart: false,
+            },
+            ServerMessage::Answer {
+                from_id: 2,
+                sdp: "v=0".to_string(),
+            },
+            ServerMessage::IceCandidate {
+                from_id: 3,
+                candidate: "candidate:1 1 udp 1 10.0.0.1 5000 typ host".to_string(),
+                sdp_mid: None,
+                sdp_m_line_index: None,
+            },
+            ServerMessage::Notice {
+                message: "Restarting in 5 minutes".to_string(),
+            },
+            ServerMessage::Kicked {
+                reason: "AFK".to_string(),
+            },
+            ServerMessage::Relay {
+                from_id: 4,
+                channel: ChannelKind::Events,
+                data: "{}".to_string(),
+                binary: false,
+            },
+        ];
+        for msg in messages {
+            round_trip(msg);
+        }
+    }
+
+    #[test]
+    fn wire_format_is_stable() {
+        let join = serde_json::to_value(ClientMessage::Join {
+            room: None,
+            name: None,
+            resume: None,
+        })
+        .unwrap();
+        assert_eq!(join, serde_json::json!({ "type": "join" }));
+
+        let offer = serde_json::to_value(ClientMessage::Offer {
+            target_id: 9,
+            sdp: "x".to_string(),
+            ice_restart: false,
+        })
+        .unwrap();
+        assert_eq!(
+            offer,
+            serde_json::json!({ "type": "offer", "targetId": 9, "sdp": "x" })
+        );
+
+        let phase = serde_json::to_value(ServerMessage::GamePhase {
+            phase: GamePhase::WaitingForPlayers,
+            ends_at: None,
+            draw: false,
+        })
+        .unwrap();
+        assert_eq!(
+            phase,
+            serde_json::json!({
+                "type": "game-phase",
+                "phase": "waiting_for_players",
+            })
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Binary encoding of player state for the unreliable `state` data channel.
+//!
+//! Every client sends its pose to every peer each frame, so the packet is
+//! kept small and fixed-size instead of JSON:
+//!
+//! ```text
+//! offset  size  field
+//! 0       1     version (STATE_PACKET_VERSION)
+//! 1       1     flags: bits 0-1 mask, bits 2-7 reserved (zero)
+//! 2       2     sequence number, wrapping
+//! 4       4     sender timestamp, milliseconds on the server clock
+//! 8       6     position x, y, z as i16 in 1/POSITION_SCALE units
+//! 14      2     yaw as a 16-bit fraction of a full turn
+//! 16      2     pitch as a 16-bit fraction of a full turn
+//! ```
+//!
+//! All integers are little-endian. A receiver drops packets whose version it
+//! doesn't know, so the layout can change by bumping the version.
+
+use std::f32::consts::TAU;
+
+/// Current packet layout version.
+pub const STATE_PACKET_VERSION: u8 = 2;
+
+/// Encoded size of a state packet.
+pub const STATE_PACKET_LEN: usize = 18;
+
+/// Quantization steps per world unit. A quarter unit is far below what a
+/// player can see, and keeps the whole map (±8192 units) in an `i16`.
+const POSITION_SCALE: f32 = 4.0;
+
+const MASK_BITS: u8 = 0b11;
+
+/// One player's pose, as sent to peers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StatePacket {
+    /// Increments with every packet the sender sends.
+    pub seq: u16,
+    /// When the sender sampled this state, on the server clock as the sender
+    /// estimates it (see the crate docs).
+    pub sent_at_ms: u32,
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub mask: u8,
+}
+
+impl StatePacket {
+    pub fn encode(&self) -> [u8; STATE_PACKET_LEN] {
+        let mut buf = [0u8; STATE_PACKET_LEN];
+        buf[0] = STATE_PACKET_VERSION;
+        buf[1] = self.mask & MASK_BITS;
+        buf[2..4].copy_from_slice(&self.seq.to_le_bytes());
+        buf[4..8].copy_from_slice(&self.sent_at_ms.to_le_bytes());
+        for (i, coord) in self.position.iter().enumerate() {
+            let offset = 8 + i * 2;
+            buf[offset..offset + 2].copy_from_slice(&quantize_position(*coord).to_le_bytes());
+        }
+        buf[14..16].copy_from_slice(&quantize_angle(self.yaw).to_le_bytes());
+        buf[16..18].copy_from_slice(&quantize_angle(self.pitch).to_le_bytes());
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self, String> {
+        let Some(&version) = buf.first() else {
+            return Err("empty state packet".to_string());
+        };
+        if version != STATE_PACKET_VERSION {
+            return Err(format!("unsupported state packet version {}", version));
+        }
+        if buf.len() != STATE_PACKET_LEN {
+            return Err(format!(
+                "state packet is {} bytes, expected {}",
+                buf.len(),
+                STATE_PACKET_LEN
+            ));
+        }
+
+        let u16_at = |offset: usize| u16::from_le_bytes([buf[offset], buf[offset + 1]]);
+        let position_at = |offset: usize| u16_at(offset) as i16 as f32 / POSITION_SCALE;
+
+        Ok(Self {
+            seq: u16_at(2),
+            sent_at_ms: u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            position: [position_at(8), position_at(10), position_at(12)],
+            yaw: dequantize_angle(u16_at(14)),
+            pitch: dequantize_angle(u16_at(16)),
+            mask: buf[1] & MASK_BITS,
+        })
+    }
+}
+
+fn quantize_position(coord: f32) -> i16 {
+    // `as` saturates, so positions off the map clamp to its edge
+    (coord * POSITION_SCALE).round() as i16
+}
+
+/// Map an angle in radians to a fraction of a full turn.
+fn quantize_angle(radians: f32) -> u16 {
+    let turns = (radians / TAU).rem_euclid(1.0);
+    // rem_euclid can round up to exactly 1.0, which wraps to 0 like it should
+    (turns * 65536.0).round() as u32 as u16
+}
+
+/// Inverse of `quantize_angle`, in `[-PI, PI)`.
+fn dequantize_angle(value: u16) -> f32 {
+    value as i16 as f32 / 65536.0 * TAU
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn angle_diff(a: f32, b: f32) -> f32 {
+        let d = (a - b).rem_euclid(TAU);
+        d.min(TAU - d)
+    }
+
+    #[test]
+    fn round_trip_within_quantization_error() {
+        let packet = StatePacket {
+            seq: 65535,
+            sent_at_ms: 4_000_000_000,
+            position: [-1322.2, 128.0, 2969.8],
+            yaw: 7.5,
+            pitch: -1.2,
+            mask: 3,
+        };
+        let decoded = StatePacket::decode(&packet.encode()).unwrap();
+
+        assert_eq!(decoded.seq, packet.seq);
+        assert_eq!(decoded.sent_at_ms, packet.sent_at_ms);
+        assert_eq!(decoded.mask, packet.mask);
+        for (a, b) in decoded.position.iter().zip(packet.position) {
+            assert!((a - b).abs() <= 0.5 / POSITION_SCALE, "{} vs {}", a, b);
+        }
+        let step = TAU / 65536.0;
+        assert!(angle_diff(decoded.yaw, packet.yaw) <= step);
+        assert!(angle_diff(decoded.pitch, packet.pitch) <= step);
+        assert!((-PI..PI).contains(&decoded.yaw));
+    }
+
+    #[test]
+    fn positions_off_the_map_saturate() {
+        let packet = StatePacket {
+            seq: 0,
+            sent_at_ms: 0,
+            position: [1e6, -1e6, 0.0],
+            yaw: 0.0,
+            pitch: 0.0,
+            mask: 1,
+        };
+        let decoded = StatePacket::decode(&packet.encode()).unwrap();
+        assert_eq!(decoded.position[0], i16::MAX as f32 / POSITION_SCALE);
+        assert_eq!(decoded.position[1], i16::MIN as f32 / POSITION_SCALE);
+    }
+
+    #[test]
+    fn malformed_packets_are_rejected() {
+        let mut buf = StatePacket {
+            seq: 1,
+            sent_at_ms: 20,
+            position: [0.0; 3],
+            yaw: 0.0,
+            pitch: 0.0,
+            mask: 2,
+        }
+        .encode();
+        assert!(StatePacket::decode(&buf[..STATE_PACKET_LEN - 1]).is_err());
+        assert!(StatePacket::decode(&[]).is_err());
+
+        buf[0] = STATE_PACKET_VERSION + 1;
+        assert!(StatePacket::decode(&buf).is_err());
+    }
+}
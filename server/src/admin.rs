@@ -0,0 +1,124 @@
+//! Token-protected admin API for inspecting and steering matches.
+//!
+//! Mounted under `/admin` when an admin token is configured. Every request
+//! needs an `Authorization: Bearer <token>` header.
+//!
+//! - `GET  /admin/clients`                 list clients with room, phase and alive state
+//! - `POST /admin/clients/{id}/kick`       remove a client (optional `?reason=`)
+//! - `POST /admin/rooms/{code}/phase`      force a phase: `{"phase": "victory", "duration": 5}`
+//! - `POST /admin/notice`                  show a notice: `{"message": "...", "room": "public"}`
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, Request, State},
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use protocol::{ClientId, GamePhase};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::config::MAX_PHASE_DURATION;
+use crate::signaling::{self, ClientSummary};
+
+pub fn router(token: String) -> Router {
+    Router::new()
+        .route("/clients", get(list_clients))
+        .route("/clients/{id}/kick", post(kick_client))
+        .route("/rooms/{code}/phase", post(force_phase))
+        .route("/notice", post(broadcast_notice))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(token),
+            require_token,
+        ))
+}
+
+async fn require_token(State(token): State<Arc<String>>, request: Request, next: Next) -> Response {
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided {
+        Some(provided) if tokens_match(provided, &token) => next.run(request).await,
+        _ => {
+            log::warn!("Rejected admin request to {}", request.uri().path());
+            StatusCode::UNAUTHORIZED.into_response()
+        }
+    }
+}
+
+/// Compare tokens without bailing out at the first differing byte.
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn list_clients() -> Json<Vec<ClientSummary>> {
+    Json(signaling::list_clients().await)
+}
+
+#[derive(Deserialize)]
+struct KickParams {
+    reason: Option<String>,
+}
+
+async fn kick_client(Path(id): Path<ClientId>, Query(params): Query<KickParams>) -> StatusCode {
+    let reason = params
+        .reason
+        .unwrap_or_else(|| "Removed by an admin".to_string());
+    if signaling::kick_client(id, &reason).await {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[derive(Deserialize)]
+struct PhaseRequest {
+    phase: GamePhase,
+    /// Seconds; defaults to the room's rules for the phase.
+    duration: Option<f32>,
+}
+
+async fn force_phase(Path(code): Path<String>, Json(request): Json<PhaseRequest>) -> StatusCode {
+    if request
+        .duration
+        .is_some_and(|d| !(d.is_finite() && (0.0..=MAX_PHASE_DURATION).contains(&d)))
+    {
+        return StatusCode::BAD_REQUEST;
+    }
+    if signaling::force_phase(&code, request.phase, request.duration).await {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[derive(Deserialize)]
+struct NoticeRequest {
+    message: String,
+    /// Limit the notice to one room; all rooms if omitted.
+    room: Option<String>,
+}
+
+#[derive(Serialize)]
+struct NoticeResponse {
+    delivered: usize,
+}
+
+async fn broadcast_notice(Json(request): Json<NoticeRequest>) -> Response {
+    let message = request.message.trim();
+    if message.is_empty() {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    let delivered = signaling::broadcast_notice(request.room.as_deref(), message).await;
+    Json(NoticeResponse { delivered }).into_response()
+}
@@ -0,0 +1,48 @@
+//! The server clock that protocol timestamps are measured on.
+//!
+//! Timestamps are milliseconds since the clock was first read, which happens
+//! at startup. Clients estimate the offset to it with `ping`/`pong`.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+fn epoch() -> Instant {
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// Start the clock, so that timestamps count from startup.
+pub fn start() {
+    epoch();
+}
+
+/// `at` as a protocol timestamp.
+pub fn to_millis(at: Instant) -> f64 {
+    at.saturating_duration_since(epoch()).as_secs_f64() * 1000.0
+}
+
+/// The current protocol timestamp.
+pub fn now_millis() -> f64 {
+    to_millis(Instant::now())
+}
+
+/// The instant a protocol timestamp refers to; nonsense values map to the
+/// start of the clock.
+pub fn from_millis(millis: f64) -> Instant {
+    let offset = Duration::try_from_secs_f64(millis / 1000.0).unwrap_or_default();
+    epoch().checked_add(offset).unwrap_or_else(epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_round_trip() {
+        let at = Instant::now() + Duration::from_millis(1500);
+        let back = from_millis(to_millis(at));
+        assert!(back.max(at) - back.min(at) < Duration::from_micros(1));
+        assert_eq!(from_millis(-5.0), from_millis(f64::NAN));
+    }
+}
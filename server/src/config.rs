@@ -0,0 +1,507 @@
+//! Server configuration.
+//!
+//! Values start from built-in defaults, are overridden by an optional TOML
+//! file, then by environment variables, then by command-line flags. The
+//! result is validated once at startup and read through `get_config()`.
+
+use clap::Parser;
+use protocol::ice::{IcePolicy, IceServer};
+use protocol::{GameRules, Topology};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Config file picked up from the working directory when `--config` is not given.
+const DEFAULT_CONFIG_FILE: &str = "server.toml";
+
+/// Longest phase, in seconds, that the rules or an admin may ask for.
+pub const MAX_PHASE_DURATION: f32 = 24.0 * 60.0 * 60.0;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// HTTP + WebSocket port.
+    pub port: u16,
+    /// TURN/STUN UDP port.
+    pub turn_port: u16,
+    /// Bearer token for the `/admin` API; the API is off when unset.
+    pub admin_token: Option<String>,
+    pub rules: RulesConfig,
+    pub turn: TurnConfig,
+    pub ice: IceConfig,
+    pub network: NetworkConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RulesConfig {
+    /// Seconds of no-damage countdown before each round.
+    pub grace_period: f32,
+    /// Seconds the round-end screen stays up.
+    pub victory_duration: f32,
+    /// Seconds of regular play before sudden death; 0 disables the limit.
+    pub round_time_limit: f32,
+    /// Seconds of sudden death before the round ends in a draw.
+    pub sudden_death_duration: f32,
+    /// Players needed before a round starts.
+    pub min_players: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TurnConfig {
+    pub realm: String,
+    /// Shared secret for signing TURN credentials; random per run if unset.
+    pub secret: String,
+    /// Seconds a minted TURN credential stays valid.
+    pub credential_ttl: u64,
+}
+
+/// The ICE server list served at `/ice-servers`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IceConfig {
+    /// Which servers clients may use; a client can ask for another one with
+    /// `?policy=`.
+    pub policy: IcePolicy,
+    /// List our own STUN and TURN server first.
+    pub self_hosted: bool,
+    /// Further servers, tried in this order after ours. `{host}` in a URL
+    /// stands for the host name the client reached us at.
+    pub servers: Vec<IceServer>,
+}
+
+/// How game traffic gets between players.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// `mesh` for direct connections between players, `star` to pass all
+    /// game traffic through the server.
+    pub topology: Topology,
+    /// In a star, players further apart than this only get every
+    /// `distant_update_interval`-th state update of each other; 0 sends
+    /// them all.
+    pub interest_radius: f32,
+    pub distant_update_interval: u16,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 9000,
+            turn_port: 3478,
+            admin_token: None,
+            rules: RulesConfig::default(),
+            turn: TurnConfig::default(),
+            ice: IceConfig::default(),
+            network: NetworkConfig::default(),
+        }
+    }
+}
+
+impl Default for RulesConfig {
+    fn default() -> Self {
+        Self {
+            grace_period: 10.0,
+            victory_duration: 10.0,
+            round_time_limit: 180.0,
+            sudden_death_duration: 30.0,
+            min_players: 2,
+        }
+    }
+}
+
+impl Default for TurnConfig {
+    fn default() -> Self {
+        Self {
+            realm: "globalgamejam".to_string(),
+            secret: String::new(),
+            credential_ttl: 12 * 60 * 60,
+        }
+    }
+}
+
+impl Default for IceConfig {
+    fn default() -> Self {
+        Self {
+            policy: IcePolicy::All,
+            self_hosted: true,
+            servers: Vec::new(),
+        }
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            topology: Topology::Mesh,
+            interest_radius: 3000.0,
+            distant_update_interval: 3,
+        }
+    }
+}
+
+impl RulesConfig {
+    /// The rules as sent to clients.
+    pub fn to_wire(&self) -> GameRules {
+        GameRules {
+            grace_period: self.grace_period,
+            victory_duration: self.victory_duration,
+            round_time_limit: self.round_time_limit,
+            sudden_death_duration: self.sudden_death_duration,
+            min_players: self.min_players as u32,
+        }
+    }
+}
+
+/// Command-line flags; each one can also be set through its environment variable.
+#[derive(Debug, Parser)]
+#[command(version = env!("GIT_HASH"), about = "Game server: signaling, TURN and static files")]
+struct Cli {
+    /// TOML config file (defaults to ./server.toml if present)
+    #[arg(long, env = "SERVER_CONFIG")]
+    config: Option<PathBuf>,
+    /// HTTP + WebSocket port
+    #[arg(long, env = "PORT")]
+    port: Option<u16>,
+    /// TURN/STUN UDP port
+    #[arg(long, env = "TURN_PORT")]
+    turn_port: Option<u16>,
+    /// Bearer token that enables the /admin API
+    #[arg(long, env = "ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+    /// Grace period before each round, in seconds
+    #[arg(long, env = "GRACE_PERIOD")]
+    grace_period: Option<f32>,
+    /// Round-end screen duration, in seconds
+    #[arg(long, env = "VICTORY_DURATION")]
+    victory_duration: Option<f32>,
+    /// Seconds of play before sudden death (0 for no limit)
+    #[arg(long, env = "ROUND_TIME_LIMIT")]
+    round_time_limit: Option<f32>,
+    /// Sudden-death duration before a draw, in seconds
+    #[arg(long, env = "SUDDEN_DEATH_DURATION")]
+    sudden_death_duration: Option<f32>,
+    /// Players needed before a round starts
+    #[arg(long, env = "MIN_PLAYERS")]
+    min_players: Option<usize>,
+    /// TURN realm
+    #[arg(long, env = "TURN_REALM")]
+    turn_realm: Option<String>,
+    /// Secret for signing TURN credentials
+    #[arg(long, env = "TURN_SECRET", hide_env_values = true)]
+    turn_secret: Option<String>,
+    /// How long minted TURN credentials stay valid, in seconds
+    #[arg(long, env = "TURN_CREDENTIAL_TTL")]
+    turn_credential_ttl: Option<u64>,
+    /// ICE servers clients may use: all, relay-only or stun-only
+    #[arg(long, env = "ICE_POLICY")]
+    ice_policy: Option<IcePolicy>,
+    /// How game traffic gets between players: mesh or star
+    #[arg(long, env = "TOPOLOGY")]
+    topology: Option<Topology>,
+}
+
+impl Config {
+    /// Build the configuration from the config file, environment and flags.
+    fn load(cli: Cli) -> Result<Self, String> {
+        let path = cli.config.clone().or_else(|| {
+            Path::new(DEFAULT_CONFIG_FILE)
+                .exists()
+                .then(|| PathBuf::from(DEFAULT_CONFIG_FILE))
+        });
+
+        let mut config = match &path {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+                toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            None => Config::default(),
+        };
+
+        if let Some(path) = &path {
+            log::info!("Loaded config from {}", path.display());
+        }
+
+        config.apply(cli);
+        config.validate()?;
+
+        if config.turn.secret.is_empty() {
+            log::warn!("No TURN secret configured, using a random one for this run");
+            config.turn.secret = format!("{:032x}", rand::random::<u128>());
+        }
+
+        Ok(config)
+    }
+
+    /// Override file values with flags and environment variables.
+    fn apply(&mut self, cli: Cli) {
+        if let Some(port) = cli.port {
+            self.port = port;
+        }
+        if let Some(port) = cli.turn_port {
+            self.turn_port = port;
+        }
+        if let Some(token) = cli.admin_token {
+            self.admin_token = Some(token);
+        }
+        if let Some(secs) = cli.grace_period {
+            self.rules.grace_period = secs;
+        }
+        if let Some(secs) = cli.victory_duration {
+            self.rules.victory_duration = secs;
+        }
+        if let Some(secs) = cli.round_time_limit {
+            self.rules.round_time_limit = secs;
+        }
+        if let Some(secs) = cli.sudden_death_duration {
+            self.rules.sudden_death_duration = secs;
+        }
+        if let Some(count) = cli.min_players {
+            self.rules.min_players = count;
+        }
+        if let Some(realm) = cli.turn_realm {
+            self.turn.realm = realm;
+        }
+        if let Some(secret) = cli.turn_secret {
+            self.turn.secret = secret;
+        }
+        if let Some(ttl) = cli.turn_credential_ttl {
+            self.turn.credential_ttl = ttl;
+        }
+        if let Some(policy) = cli.ice_policy {
+            self.ice.policy = policy;
+        }
+        if let Some(topology) = cli.topology {
+            self.network.topology = topology;
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.port == 0 || self.turn_port == 0 {
+            return Err("port and turn_port must be non-zero".to_string());
+        }
+        if self.port == self.turn_port {
+            return Err(format!(
+                "port and turn_port must differ (both are {})",
+                self.port
+            ));
+        }
+
+        if self.admin_token.as_ref().is_some_and(|t| t.len() < 16) {
+            return Err("admin_token must be at least 16 characters".to_string());
+        }
+
+        let rules = &self.rules;
+        if !(rules.grace_period.is_finite()
+            && rules.grace_period > 0.0
+            && rules.grace_period <= MAX_PHASE_DURATION)
+        {
+            return Err(format!(
+                "rules.grace_period must be a positive number of seconds up to {MAX_PHASE_DURATION}, got {}",
+                rules.grace_period
+            ));
+        }
+        if !(rules.victory_duration.is_finite()
+            && rules.victory_duration > 0.0
+            && rules.victory_duration <= MAX_PHASE_DURATION)
+        {
+            return Err(format!(
+                "rules.victory_duration must be a positive number of seconds up to {MAX_PHASE_DURATION}, got {}",
+                rules.victory_duration
+            ));
+        }
+        if !(rules.round_time_limit.is_finite()
+            && rules.round_time_limit >= 0.0
+            && rules.round_time_limit <= MAX_PHASE_DURATION)
+        {
+            return Err(format!(
+                "rules.round_time_limit must be zero or a positive number of seconds up to {MAX_PHASE_DURATION}, got {}",
+                rules.round_time_limit
+            ));
+        }
+        if !(rules.sudden_death_duration.is_finite()
+            && rules.sudden_death_duration > 0.0
+            && rules.sudden_death_duration <= MAX_PHASE_DURATION)
+        {
+            return Err(format!(
+                "rules.sudden_death_duration must be a positive number of seconds up to {MAX_PHASE_DURATION}, got {}",
+                rules.sudden_death_duration
+            ));
+        }
+        // With one player the last survivor is decided the moment a round starts
+        if rules.min_players < 2 {
+            return Err(format!(
+                "rules.min_players must be at least 2, got {}",
+                rules.min_players
+            ));
+        }
+
+        if self.turn.realm.is_empty() {
+            return Err("turn.realm must not be empty".to_string());
+        }
+        if self.turn.credential_ttl == 0 {
+            return Err("turn.credential_ttl must be at least 1 second".to_string());
+        }
+
+        for server in &self.ice.servers {
+            if server.urls.is_empty() {
+                return Err("ice.servers entries need at least one URL".to_string());
+            }
+            for url in &server.urls {
+                if !["stun:", "stuns:", "turn:", "turns:"]
+                    .iter()
+                    .any(|scheme| url.starts_with(scheme))
+                {
+                    return Err(format!("ice.servers: '{}' is not a STUN or TURN URL", url));
+                }
+                if IcePolicy::RelayOnly.allows(url)
+                    && (server.username.is_none() || server.credential.is_none())
+                {
+                    return Err(format!(
+                        "ice.servers: TURN server '{}' needs a username and credential",
+                        url
+                    ));
+                }
+            }
+        }
+        let has_turn = self.ice.self_hosted
+            || self
+                .ice
+                .servers
+                .iter()
+                .flat_map(|server| &server.urls)
+                .any(|url| IcePolicy::RelayOnly.allows(url));
+        if self.ice.policy == IcePolicy::RelayOnly && !has_turn {
+            return Err("ice.policy relay-only needs a TURN server".to_string());
+        }
+
+        let network = &self.network;
+        if !(network.interest_radius.is_finite() && network.interest_radius >= 0.0) {
+            return Err(format!(
+                "network.interest_radius must be zero or a positive distance, got {}",
+                network.interest_radius
+            ));
+        }
+        if network.distant_update_interval == 0 {
+            return Err("network.distant_update_interval must be at least 1".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Parse flags, load and validate the configuration. Call once at startup.
+pub fn init_config() -> Result<&'static Config, String> {
+    let config = Config::load(Cli::parse())?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// Use `config` as is, without flags or a config file (for embedding the
+/// server, e.g. in tests). Returns the configuration in effect, which is the
+/// first one set.
+pub fn init_with(config: Config) -> &'static Config {
+    CONFIG.get_or_init(|| config)
+}
+
+/// Get the configuration loaded by `init_config`.
+pub fn get_config() -> &'static Config {
+    CONFIG
+        .get()
+        .expect("init_config() must run before the config is used")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli(args: &[&str]) -> Cli {
+        Cli::parse_from(std::iter::once("server").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn file_values_are_overridden_by_flags() {
+        let mut config: Config = toml::from_str(
+            r#"
+            port = 8000
+
+            [rules]
+            grace_period = 5.0
+            min_players = 3
+            "#,
+        )
+        .unwrap();
+        config.apply(cli(&["--min-players", "4"]));
+
+        assert_eq!(config.port, 8000);
+        assert_eq!(config.turn_port, 3478);
+        assert_eq!(config.rules.grace_period, 5.0);
+        assert_eq!(config.rules.victory_duration, 10.0);
+        assert_eq!(config.rules.min_players, 4);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("[rules]\ngrace = 5.0").is_err());
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        let mut config = Config::default();
+        config.rules.min_players = 1;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.rules.grace_period = -1.0;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.rules.sudden_death_duration = 0.0;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.rules.round_time_limit = 1e20;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.turn_port = config.port;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn ice_servers_are_checked() {
+        let config: Config = toml::from_str(
+            r#"
+            [ice]
+            self_hosted = false
+
+            [[ice.servers]]
+            urls = ["stun:stun.example.com:3478"]
+
+            [[ice.servers]]
+            urls = ["turns:turn.example.com:5349"]
+            username = "game"
+            credential = "hunter2"
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let mut config = Config::default();
+        config.ice.servers.push(IceServer {
+            urls: vec!["turn:turn.example.com".to_string()],
+            username: None,
+            credential: None,
+        });
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.ice.self_hosted = false;
+        config.ice.policy = IcePolicy::RelayOnly;
+        assert!(config.validate().is_err());
+    }
+}
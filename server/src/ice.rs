@@ -0,0 +1,170 @@
+//! The ICE server list clients build their peer connections from.
+//!
+//! Our own STUN/TURN server comes first (unless `ice.self_hosted` is off),
+//! then the servers from `ice.servers` in order, all filtered by the ICE
+//! policy. Our TURN entry carries a freshly minted credential, so the list
+//! is only good for `turn.credential_ttl` seconds.
+
+use axum::Json;
+use axum::extract::Query;
+use axum::http::{HeaderMap, header};
+use protocol::ice::{IcePolicy, IceServer, IceServerList};
+use serde::Deserialize;
+
+use crate::config::{self, Config};
+use crate::turn;
+
+#[derive(Deserialize)]
+pub(crate) struct IceQuery {
+    /// Overrides `ice.policy`, for debugging connectivity.
+    policy: Option<IcePolicy>,
+}
+
+pub(crate) async fn ice_servers(
+    headers: HeaderMap,
+    Query(query): Query<IceQuery>,
+) -> Json<IceServerList> {
+    let config = config::get_config();
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(host_name);
+    let policy = query.policy.unwrap_or(config.ice.policy);
+    Json(server_list(config, host, policy))
+}
+
+/// The list for a client that reached us at `host`.
+fn server_list(config: &Config, host: Option<&str>, policy: IcePolicy) -> IceServerList {
+    let mut servers = Vec::new();
+    if config.ice.self_hosted {
+        servers.push(IceServer {
+            urls: vec![format!("stun:{{host}}:{}", config.turn_port)],
+            username: None,
+            credential: None,
+        });
+        // Every request gets its own pair, so leaked credentials expire on their own
+        let user_id = format!("{:016x}", rand::random::<u64>());
+        let (username, credential) = turn::mint_credentials(&config.turn, &user_id);
+        servers.push(IceServer {
+            urls: vec![format!("turn:{{host}}:{}", config.turn_port)],
+            username: Some(username),
+            credential: Some(credential),
+        });
+    }
+    servers.extend(config.ice.servers.iter().cloned());
+
+    let ice_servers = servers
+        .into_iter()
+        .filter_map(|mut server| {
+            server.urls = server
+                .urls
+                .into_iter()
+                .filter(|url| policy.allows(url))
+                // Without a Host header there's no telling what `{host}` is
+                .filter_map(|url| match host {
+                    Some(host) => Some(url.replace("{host}", host)),
+                    None => (!url.contains("{host}")).then_some(url),
+                })
+                .collect();
+            (!server.urls.is_empty()).then_some(server)
+        })
+        .collect();
+
+    IceServerList {
+        ice_servers,
+        policy,
+        ttl: config.turn.credential_ttl,
+    }
+}
+
+/// The host name in a `Host` header, without the port.
+fn host_name(host: &str) -> &str {
+    match host.find(']') {
+        // IPv6 literal: [::1]:9000
+        Some(end) => &host[..=end],
+        None => host.split(':').next().unwrap_or(host),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.ice.servers = vec![
+            IceServer {
+                urls: vec!["stun:stun.example.com:3478".to_string()],
+                username: None,
+                credential: None,
+            },
+            IceServer {
+                urls: vec![
+                    "turns:{host}:5349".to_string(),
+                    "stun:{host}:5349".to_string(),
+                ],
+                username: Some("game".to_string()),
+                credential: Some("hunter2".to_string()),
+            },
+        ];
+        config
+    }
+
+    fn urls(list: &IceServerList) -> Vec<&str> {
+        list.ice_servers
+            .iter()
+            .flat_map(|server| &server.urls)
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn our_servers_come_first() {
+        let list = server_list(&config(), Some("game.example.com"), IcePolicy::All);
+        assert_eq!(
+            urls(&list),
+            [
+                "stun:game.example.com:3478",
+                "turn:game.example.com:3478",
+                "stun:stun.example.com:3478",
+                "turns:game.example.com:5349",
+                "stun:game.example.com:5349",
+            ]
+        );
+        assert!(list.ice_servers[1].credential.is_some());
+        assert_eq!(list.ttl, Config::default().turn.credential_ttl);
+    }
+
+    #[test]
+    fn policy_filters_urls() {
+        let host = Some("game.example.com");
+        let relay = server_list(&config(), host, IcePolicy::RelayOnly);
+        assert_eq!(
+            urls(&relay),
+            ["turn:game.example.com:3478", "turns:game.example.com:5349"]
+        );
+
+        let stun = server_list(&config(), host, IcePolicy::StunOnly);
+        assert_eq!(
+            urls(&stun),
+            [
+                "stun:game.example.com:3478",
+                "stun:stun.example.com:3478",
+                "stun:game.example.com:5349",
+            ]
+        );
+    }
+
+    #[test]
+    fn host_placeholders_need_a_host() {
+        let list = server_list(&config(), None, IcePolicy::All);
+        assert_eq!(urls(&list), ["stun:stun.example.com:3478"]);
+    }
+
+    #[test]
+    fn host_names_lose_their_port() {
+        assert_eq!(host_name("game.example.com:9000"), "game.example.com");
+        assert_eq!(host_name("game.example.com"), "game.example.com");
+        assert_eq!(host_name("[::1]:9000"), "[::1]");
+    }
+}
@@ -0,0 +1,41 @@
+//! Game server: WebSocket signaling and rounds, TURN for peers that can't
+//! connect directly, and the admin and metrics endpoints.
+//!
+//! `main.rs` runs it from the command line. Tests embed it: set the
+//! configuration with `config::init_with`, call `start_game`, and serve
+//! `router`.
+
+mod admin;
+pub mod clock;
+pub mod config;
+mod ice;
+mod metrics;
+mod referee;
+mod signaling;
+pub mod turn;
+
+use axum::{Router, routing::get};
+
+use crate::config::Config;
+
+/// Load the map and start the game loop. Needs a Tokio runtime.
+pub fn start_game() {
+    // Load the map before the first kill claim needs it
+    referee::get_referee();
+
+    signaling::start_game_loop();
+}
+
+/// Routes for the signaling WebSocket, the ICE server list, metrics and (with
+/// an admin token configured) the admin API.
+pub fn router(config: &Config) -> Router {
+    let app = Router::new()
+        .route("/ws", get(signaling::ws_handler))
+        .route("/ice-servers", get(ice::ice_servers))
+        .route("/metrics", get(metrics::metrics_handler));
+
+    match &config.admin_token {
+        Some(token) => app.nest("/admin", admin::router(token.clone())),
+        None => app,
+    }
+}
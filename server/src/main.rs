@@ -1,13 +1,7 @@
-mod signaling;
-mod turn;
-
-use axum::{Json, Router, routing::get};
-use serde::Serialize;
+use server::{clock, config, turn};
 use std::net::SocketAddr;
 use tower_http::services::ServeDir;
 
-const PORT: u16 = 9000;
-const TURN_PORT: u16 = 3478;
 const GIT_HASH: &str = env!("GIT_HASH");
 
 #[tokio::main]
@@ -17,48 +11,67 @@ async fn main() {
         env_logger::Env::default().default_filter_or("info,turn::server=warn"),
     )
     .init();
+    clock::start();
+
+    let config = match config::init_config() {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     let public_ip = turn::get_public_ip();
 
     log::info!("=== Server version: {} ===", GIT_HASH);
     log::info!("Starting Rust game server...");
-    log::info!("  HTTP+WS:   http://localhost:{}", PORT);
-    log::info!("  TURN/STUN: turn:{}:{}", public_ip, TURN_PORT);
+    log::info!("  HTTP+WS:   http://localhost:{}", config.port);
+    log::info!("  TURN/STUN: turn:{}:{}", public_ip, config.turn_port);
+    log::info!("  Topology:  {:?}", config.network.topology);
+    log::info!(
+        "  ICE:       {} policy, {}{} more servers",
+        config.ice.policy,
+        if config.ice.self_hosted {
+            "ours + "
+        } else {
+            ""
+        },
+        config.ice.servers.len()
+    );
+    log::info!(
+        "  Rules:     {}s grace period, {}s round end, {} players to start",
+        config.rules.grace_period,
+        config.rules.victory_duration,
+        config.rules.min_players
+    );
+    log::info!(
+        "  Rounds:    {}s time limit, then {}s sudden death",
+        config.rules.round_time_limit,
+        config.rules.sudden_death_duration
+    );
 
     // Start TURN/STUN server (UDP)
     tokio::spawn(async move {
-        if let Err(e) = turn::run_turn_server(TURN_PORT, public_ip).await {
+        if let Err(e) = turn::run_turn_server(config.turn_port, public_ip, &config.turn).await {
             log::error!("TURN server error: {}", e);
         }
     });
 
-    // Start game loop
-    signaling::start_game_loop();
+    // Load the map and start the game loop
+    server::start_game();
+
+    if config.admin_token.is_some() {
+        log::info!("  Admin API: /admin (bearer token required)");
+    } else {
+        log::info!("  Admin API: disabled (no admin token configured)");
+    }
 
     // Build router with WebSocket and static file serving
-    let app = Router::new()
-        .route("/ws", get(signaling::ws_handler))
-        .route("/turn-credentials", get(turn_credentials))
-        .fallback_service(ServeDir::new("client/dist"));
+    let app = server::router(config).fallback_service(ServeDir::new("client/dist"));
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], PORT));
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     log::info!("Server listening on {}", addr);
 
     axum::serve(listener, app).await.unwrap();
 }
-
-#[derive(Serialize)]
-struct TurnCredential {
-    urls: String,
-    username: String,
-    credential: String,
-}
-
-async fn turn_credentials() -> Json<Vec<TurnCredential>> {
-    Json(vec![TurnCredential {
-        urls: format!("turn:{{host}}:{}", TURN_PORT),
-        username: turn::TURN_USERNAME.to_string(),
-        credential: turn::TURN_PASSWORD.to_string(),
-    }])
-}
@@ -0,0 +1,236 @@
+//! Prometheus metrics, exported on `/metrics`.
+//!
+//! Counters are bumped where things happen (signaling, round transitions,
+//! the TURN server); gauges are refreshed by the game loop.
+
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use protocol::{GamePhase, MovementViolation};
+use std::sync::OnceLock;
+
+pub struct Metrics {
+    registry: Registry,
+    /// Clients with an open socket (held slots are not counted).
+    pub connected_clients: IntGauge,
+    /// Clients in rooms, by the room's game phase.
+    pub clients_by_phase: IntGaugeVec,
+    pub rounds_started: IntCounter,
+    pub rounds_finished: IntCounter,
+    /// Finished rounds that ended in a draw after sudden death.
+    pub rounds_drawn: IntCounter,
+    /// Seconds from the end of the grace period to the round-end screen.
+    pub round_duration: Histogram,
+    /// Messages forwarded between clients, by message type.
+    pub signaling_relayed: IntCounterVec,
+    /// Closed WebSocket connections, by reason.
+    pub ws_disconnects: IntCounterVec,
+    /// Clients reported by a peer for implausible movement, by reason.
+    pub peer_reports: IntCounterVec,
+    /// Copies of star broadcasts, by whether they were sent or held back
+    /// by interest management.
+    pub star_deliveries: IntCounterVec,
+    pub turn_allocations_active: IntGauge,
+    pub turn_allocations_closed: IntCounter,
+    pub turn_relayed_bytes: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("game".to_string()), None).expect("metric prefix is valid");
+
+        let metrics = Self {
+            connected_clients: IntGauge::new(
+                "connected_clients",
+                "Clients with an open signaling connection",
+            )
+            .unwrap(),
+            clients_by_phase: IntGaugeVec::new(
+                Opts::new("clients", "Clients in rooms, by game phase"),
+                &["phase"],
+            )
+            .unwrap(),
+            rounds_started: IntCounter::new("rounds_started_total", "Rounds that began playing")
+                .unwrap(),
+            rounds_finished: IntCounter::new(
+                "rounds_finished_total",
+                "Rounds that reached the round-end screen",
+            )
+            .unwrap(),
+            rounds_drawn: IntCounter::new(
+                "rounds_drawn_total",
+                "Rounds that ended in a draw after sudden death",
+            )
+            .unwrap(),
+            round_duration: Histogram::with_opts(
+                HistogramOpts::new(
+                    "round_duration_seconds",
+                    "Time from round start to round end",
+                )
+                .buckets(vec![15.0, 30.0, 60.0, 90.0, 120.0, 180.0, 300.0, 600.0]),
+            )
+            .unwrap(),
+            signaling_relayed: IntCounterVec::new(
+                Opts::new(
+                    "signaling_relayed_total",
+                    "Messages forwarded between clients, by type",
+                ),
+                &["type"],
+            )
+            .unwrap(),
+            ws_disconnects: IntCounterVec::new(
+                Opts::new(
+                    "ws_disconnects_total",
+                    "Closed WebSocket connections, by reason",
+                ),
+                &["reason"],
+            )
+            .unwrap(),
+            peer_reports: IntCounterVec::new(
+                Opts::new(
+                    "peer_reports_total",
+                    "Clients reported by a peer for implausible movement, by reason",
+                ),
+                &["reason"],
+            )
+            .unwrap(),
+            star_deliveries: IntCounterVec::new(
+                Opts::new(
+                    "star_deliveries_total",
+                    "Copies of star broadcasts, sent or held back by interest management",
+                ),
+                &["outcome"],
+            )
+            .unwrap(),
+            turn_allocations_active: IntGauge::new(
+                "turn_allocations_active",
+                "Open TURN allocations",
+            )
+            .unwrap(),
+            turn_allocations_closed: IntCounter::new(
+                "turn_allocations_closed_total",
+                "TURN allocations that were closed or expired",
+            )
+            .unwrap(),
+            turn_relayed_bytes: IntCounter::new(
+                "turn_relayed_bytes_total",
+                "Bytes relayed by closed TURN allocations",
+            )
+            .unwrap(),
+            registry,
+        };
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 13] = [
+            Box::new(metrics.connected_clients.clone()),
+            Box::new(metrics.clients_by_phase.clone()),
+            Box::new(metrics.rounds_started.clone()),
+            Box::new(metrics.rounds_finished.clone()),
+            Box::new(metrics.rounds_drawn.clone()),
+            Box::new(metrics.round_duration.clone()),
+            Box::new(metrics.signaling_relayed.clone()),
+            Box::new(metrics.ws_disconnects.clone()),
+            Box::new(metrics.peer_reports.clone()),
+            Box::new(metrics.star_deliveries.clone()),
+            Box::new(metrics.turn_allocations_active.clone()),
+            Box::new(metrics.turn_allocations_closed.clone()),
+            Box::new(metrics.turn_relayed_bytes.clone()),
+        ];
+        for collector in collectors {
+            metrics
+                .registry
+                .register(collector)
+                .expect("metric names are unique");
+        }
+
+        metrics
+    }
+
+    /// Render every metric in the Prometheus text format.
+    fn render(&self) -> Result<String, String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| e.to_string())?;
+        String::from_utf8(buffer).map_err(|e| e.to_string())
+    }
+}
+
+/// Label value for a game phase, matching its wire name.
+pub fn phase_label(phase: GamePhase) -> &'static str {
+    match phase {
+        GamePhase::WaitingForPlayers => "waiting_for_players",
+        GamePhase::GracePeriod => "grace_period",
+        GamePhase::Playing => "playing",
+        GamePhase::SuddenDeath => "sudden_death",
+        GamePhase::Victory => "victory",
+    }
+}
+
+/// Label value for a movement report, matching its wire name.
+pub fn violation_label(reason: MovementViolation) -> &'static str {
+    match reason {
+        MovementViolation::InvalidMask => "invalid-mask",
+        MovementViolation::TooFast => "too-fast",
+        MovementViolation::Flying => "flying",
+        MovementViolation::ThroughWall => "through-wall",
+    }
+}
+
+pub const ALL_PHASES: [GamePhase; 5] = [
+    GamePhase::WaitingForPlayers,
+    GamePhase::GracePeriod,
+    GamePhase::Playing,
+    GamePhase::SuddenDeath,
+    GamePhase::Victory,
+];
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+pub fn get_metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+pub async fn metrics_handler() -> Response {
+    match get_metrics().render() {
+        Ok(body) => (
+            [(
+                header::CONTENT_TYPE,
+                TextEncoder::new().format_type().to_string(),
+            )],
+            body,
+        )
+            .into_response(),
+        Err(e) => {
+            log::error!("Failed to encode metrics: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_render_with_prefix_and_labels() {
+        let metrics = Metrics::new();
+        metrics
+            .signaling_relayed
+            .with_label_values(&["offer"])
+            .inc();
+        metrics
+            .clients_by_phase
+            .with_label_values(&[phase_label(GamePhase::Playing)])
+            .set(3);
+        metrics.round_duration.observe(42.0);
+
+        let text = metrics.render().unwrap();
+        assert!(text.contains("game_signaling_relayed_total{type=\"offer\"} 1"));
+        assert!(text.contains("game_clients{phase=\"playing\"} 3"));
+        assert!(text.contains("game_round_duration_seconds_count 1"));
+    }
+}
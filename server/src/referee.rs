@@ -0,0 +1,374 @@
+//! Server-side kill validation.
+//!
+//! Clients run the kill rule locally and claim the kills it gives them, but
+//! the server has the final word: it keeps a short history of every client's
+//! pose and re-checks each claim against the map with the same rule, gaze
+//! cone, line of sight and hold time included.
+
+use gameplay::collision::PhysicsWorld;
+use gameplay::config::{EYE_HEIGHT, SUDDEN_DEATH_TARGETING_SPEED};
+use gameplay::map::CollisionMesh;
+use gameplay::mask::MaskType;
+use gameplay::targeting::{is_in_gaze, kill_duration};
+use glam::Vec3;
+use protocol::KillRejectReason;
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// The map the client embeds; the server checks line of sight against it.
+const MAP_GLB: &[u8] = include_bytes!("../../client/assets/dust2.glb");
+
+/// How long pose samples are kept per client.
+const POSE_HISTORY: Duration = Duration::from_millis(2500);
+
+/// Kill claims are checked against poses from this recent window: the
+/// longest hold plus room for network latency between the shooter, the
+/// victim and the server, and for arbitration.
+const VALIDATION_WINDOW: Duration = Duration::from_secs(2);
+
+/// Victim poses this far from a killer pose, either way, may be what the
+/// killer saw: each side's poses arrive with their own latency.
+const MAX_POSE_SKEW: Duration = Duration::from_millis(300);
+
+/// Killer poses further apart than this break a hold; clients send one
+/// every 100ms.
+const MAX_POSE_GAP: Duration = Duration::from_millis(250);
+
+/// Poses are sampled, and arrive with jitter, so a hold shows up to this
+/// much shorter than the client timed it.
+const HOLD_SLACK: Duration = Duration::from_millis(250);
+
+/// A position/orientation sample reported by a client.
+#[derive(Clone, Copy, Debug)]
+pub struct Pose {
+    pub received: Instant,
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub mask: MaskType,
+}
+
+impl Pose {
+    fn eye_position(&self) -> Vec3 {
+        self.position + Vec3::new(0.0, EYE_HEIGHT, 0.0)
+    }
+}
+
+/// Recent poses of one client, oldest first.
+#[derive(Default)]
+pub struct PoseHistory {
+    samples: VecDeque<Pose>,
+}
+
+impl PoseHistory {
+    pub fn push(&mut self, pose: Pose) {
+        self.samples.push_back(pose);
+        while self
+            .samples
+            .front()
+            .is_some_and(|p| p.received.elapsed() > POSE_HISTORY)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    fn recent(&self) -> impl Iterator<Item = &Pose> {
+        self.samples
+            .iter()
+            .filter(|p| p.received.elapsed() <= VALIDATION_WINDOW)
+    }
+}
+
+/// Why a kill claim was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// Kills only count while the round is being played.
+    NotPlaying,
+    /// The killer or victim is not in the room.
+    UnknownPlayer,
+    /// The victim is already dead (or is the killer).
+    VictimDead,
+    /// The killer died before making the claim.
+    KillerDead,
+    /// The victim had already killed the killer with an earlier claim.
+    KilledFirst,
+    /// The killer has sent no recent pose.
+    NoKillerPose,
+    /// The victim has sent no recent pose.
+    NoVictimPose,
+    /// The killer was wearing a mask that cannot kill.
+    MaskCannotKill,
+    /// The victim was never inside the killer's gaze cone with line of sight.
+    NotInGaze,
+    /// The victim was in the killer's gaze, but not for long enough to die.
+    HeldTooBriefly,
+}
+
+impl Rejection {
+    /// What the claimant is told.
+    pub fn reason(self) -> KillRejectReason {
+        match self {
+            Rejection::KilledFirst => KillRejectReason::KilledFirst,
+            Rejection::KillerDead => KillRejectReason::KillerDead,
+            _ => KillRejectReason::Invalid,
+        }
+    }
+}
+
+pub struct Referee {
+    physics: PhysicsWorld,
+}
+
+impl Referee {
+    fn load() -> Self {
+        let collision = CollisionMesh::from_glb(MAP_GLB).expect("Failed to load map collision");
+        let physics = collision
+            .physics_world()
+            .expect("Failed to create physics world");
+        log::info!(
+            "Loaded map collision for kill validation: {} triangles",
+            collision.indices.len()
+        );
+        Self { physics }
+    }
+
+    /// Check a kill claim against the recent poses of the killer and victim.
+    ///
+    /// The claim is accepted if a run of killer poses, each with a nearby
+    /// victim pose in its gaze, lasted the killer's kill duration; targeting
+    /// runs `SUDDEN_DEATH_TARGETING_SPEED` times faster in sudden death.
+    pub fn validate_kill(
+        &self,
+        killer: &PoseHistory,
+        victim: &PoseHistory,
+        sudden_death: bool,
+    ) -> Result<(), Rejection> {
+        if killer.recent().next().is_none() {
+            return Err(Rejection::NoKillerPose);
+        }
+        if victim.recent().next().is_none() {
+            return Err(Rejection::NoVictimPose);
+        }
+        let speed = if sudden_death {
+            SUDDEN_DEATH_TARGETING_SPEED
+        } else {
+            1.0
+        };
+
+        let mut any_can_kill = false;
+        let mut any_in_gaze = false;
+        // First and latest pose of the current in-gaze run
+        let mut hold: Option<(Instant, Instant)> = None;
+        for shooter in killer.recent() {
+            let in_gaze = shooter.mask.can_kill() && self.sees(shooter, victim);
+            any_can_kill |= shooter.mask.can_kill();
+            any_in_gaze |= in_gaze;
+            hold = match hold {
+                _ if !in_gaze => None,
+                Some((start, last)) if shooter.received - last <= MAX_POSE_GAP => {
+                    Some((start, shooter.received))
+                }
+                _ => Some((shooter.received, shooter.received)),
+            };
+
+            if let Some((start, last)) = hold {
+                let needed = Duration::from_secs_f32(kill_duration(shooter.mask) / speed);
+                if last - start + HOLD_SLACK >= needed {
+                    return Ok(());
+                }
+            }
+        }
+
+        if any_in_gaze {
+            Err(Rejection::HeldTooBriefly)
+        } else if any_can_kill {
+            Err(Rejection::NotInGaze)
+        } else {
+            Err(Rejection::MaskCannotKill)
+        }
+    }
+
+    /// Whether a victim pose from around the time of `shooter` was in its
+    /// gaze.
+    fn sees(&self, shooter: &Pose, victim: &PoseHistory) -> bool {
+        let eye = shooter.eye_position();
+        victim
+            .recent()
+            .filter(|target| {
+                let (earlier, later) = if target.received < shooter.received {
+                    (target.received, shooter.received)
+                } else {
+                    (shooter.received, target.received)
+                };
+                later - earlier <= MAX_POSE_SKEW
+            })
+            .any(|target| {
+                is_in_gaze(
+                    &self.physics,
+                    eye,
+                    shooter.yaw,
+                    shooter.pitch,
+                    target.eye_position(),
+                )
+            })
+    }
+}
+
+static REFEREE: OnceLock<Referee> = OnceLock::new();
+
+/// Get the shared referee, loading the map on first use.
+pub fn get_referee() -> &'static Referee {
+    REFEREE.get_or_init(Referee::load)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    /// Open ground on the map, with a clear view a few hundred units east
+    /// and a wall before 1000.
+    const OPEN: Vec3 = Vec3::new(1446.0, 128.0, -530.5);
+
+    /// Looking east, along +X.
+    const EAST: f32 = FRAC_PI_2;
+
+    /// Longer than any mask takes to kill.
+    const HELD: Duration = Duration::from_millis(1200);
+
+    /// Poses every 100ms, as clients send them, over the `held` leading up
+    /// to now.
+    fn history(position: Vec3, yaw: f32, mask: MaskType, held: Duration) -> PoseHistory {
+        let now = Instant::now();
+        let mut history = PoseHistory::default();
+        for age in (0..=held.as_millis() as u64 / 100).rev() {
+            history.push(Pose {
+                received: now - Duration::from_millis(100 * age),
+                position,
+                yaw,
+                pitch: 0.0,
+                mask,
+            });
+        }
+        history
+    }
+
+    /// Check a claim by someone at `OPEN` looking `yaw` with `mask`, against
+    /// a victim at `victim`, both standing still for `held`.
+    fn stare(yaw: f32, mask: MaskType, victim: Vec3, held: Duration) -> Result<(), Rejection> {
+        let killer = history(OPEN, yaw, mask, held);
+        let victim = history(victim, 0.0, MaskType::Ghost, held);
+        get_referee().validate_kill(&killer, &victim, false)
+    }
+
+    #[test]
+    fn held_stare_in_the_open_is_accepted() {
+        let victim = OPEN + Vec3::X * 300.0;
+        assert_eq!(stare(EAST, MaskType::Ghost, victim, HELD), Ok(()));
+        assert_eq!(stare(EAST, MaskType::Hunter, victim, HELD), Ok(()));
+    }
+
+    #[test]
+    fn victim_outside_the_cone_is_not_in_gaze() {
+        let victim = OPEN + Vec3::X * 300.0;
+        assert_eq!(
+            stare(-EAST, MaskType::Ghost, victim, HELD),
+            Err(Rejection::NotInGaze)
+        );
+    }
+
+    #[test]
+    fn victim_behind_a_wall_is_not_in_gaze() {
+        let victim = OPEN + Vec3::X * 1000.0;
+        assert_eq!(
+            stare(EAST, MaskType::Ghost, victim, HELD),
+            Err(Rejection::NotInGaze)
+        );
+    }
+
+    #[test]
+    fn cowards_cannot_kill() {
+        let victim = OPEN + Vec3::X * 300.0;
+        assert_eq!(
+            stare(EAST, MaskType::Coward, victim, HELD),
+            Err(Rejection::MaskCannotKill)
+        );
+    }
+
+    #[test]
+    fn claims_need_recent_poses() {
+        let recent = history(OPEN, EAST, MaskType::Ghost, HELD);
+        let referee = get_referee();
+        assert_eq!(
+            referee.validate_kill(&PoseHistory::default(), &recent, false),
+            Err(Rejection::NoKillerPose)
+        );
+        assert_eq!(
+            referee.validate_kill(&recent, &PoseHistory::default(), false),
+            Err(Rejection::NoVictimPose)
+        );
+
+        // Poses from before the window don't count
+        let mut stale = PoseHistory::default();
+        stale.push(Pose {
+            received: Instant::now() - VALIDATION_WINDOW - Duration::from_millis(100),
+            position: OPEN,
+            yaw: EAST,
+            pitch: 0.0,
+            mask: MaskType::Ghost,
+        });
+        assert_eq!(
+            referee.validate_kill(&stale, &recent, false),
+            Err(Rejection::NoKillerPose)
+        );
+    }
+
+    #[test]
+    fn stares_must_last_the_kill_duration() {
+        let victim = OPEN + Vec3::X * 300.0;
+        let glance = Duration::from_millis(400);
+        assert_eq!(
+            stare(EAST, MaskType::Ghost, victim, glance),
+            Err(Rejection::HeldTooBriefly)
+        );
+
+        // Hunters kill faster than Ghosts
+        let hunter_hold = Duration::from_millis(600);
+        assert_eq!(stare(EAST, MaskType::Hunter, victim, hunter_hold), Ok(()));
+        assert_eq!(
+            stare(EAST, MaskType::Ghost, victim, hunter_hold),
+            Err(Rejection::HeldTooBriefly)
+        );
+    }
+
+    #[test]
+    fn looking_away_restarts_the_hold() {
+        let victim = history(OPEN + Vec3::X * 300.0, 0.0, MaskType::Ghost, HELD);
+        let mut killer = history(OPEN, EAST, MaskType::Ghost, HELD);
+        // A single glance away halfway through
+        let samples = &mut killer.samples;
+        let middle = samples.len() / 2;
+        samples[middle].yaw = -EAST;
+        assert_eq!(
+            get_referee().validate_kill(&killer, &victim, false),
+            Err(Rejection::HeldTooBriefly)
+        );
+    }
+
+    #[test]
+    fn sudden_death_shortens_the_hold() {
+        let killer = history(OPEN, EAST, MaskType::Ghost, Duration::from_millis(500));
+        let victim = history(OPEN + Vec3::X * 300.0, 0.0, MaskType::Ghost, HELD);
+        let referee = get_referee();
+        assert_eq!(
+            referee.validate_kill(&killer, &victim, false),
+            Err(Rejection::HeldTooBriefly)
+        );
+        assert_eq!(referee.validate_kill(&killer, &victim, true), Ok(()));
+    }
+}
@@ -1,141 +1,160 @@
 use axum::{
-    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    extract::ws::{CloseFrame, Message, Utf8Bytes, WebSocket, WebSocketUpgrade, close_code},
     response::IntoResponse,
 };
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use gameplay::mask::MaskType;
+use glam::Vec3;
+use protocol::state::StatePacket;
+use protocol::{
+    ChannelKind, ClientId, ClientMessage, GamePhase, MovementViolation, PeerInfo, Resume,
+    ServerMessage, Topology,
+};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, mpsc};
 
-const GRACE_PERIOD_DURATION: f32 = 10.0;
-const VICTORY_DURATION: f32 = 10.0;
-const MIN_PLAYERS_TO_START: usize = 2;
-
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
-pub enum GamePhase {
-    WaitingForPlayers,
-    GracePeriod,
-    Playing,
-    Victory,
-}
+use crate::clock;
+use crate::config::{NetworkConfig, RulesConfig, get_config};
+use crate::metrics::{ALL_PHASES, get_metrics, phase_label, violation_label};
+use crate::referee::{Pose, PoseHistory, Rejection, get_referee};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(tag = "type")]
-enum ClientMessage {
-    #[serde(rename = "join")]
-    Join,
-    #[serde(rename = "offer")]
-    Offer {
-        #[serde(rename = "targetId")]
-        target_id: ClientId,
-        sdp: String,
-    },
-    #[serde(rename = "answer")]
-    Answer {
-        #[serde(rename = "targetId")]
-        target_id: ClientId,
-        sdp: String,
-    },
-    #[serde(rename = "ice-candidate")]
-    IceCandidate {
-        #[serde(rename = "targetId")]
-        target_id: ClientId,
-        candidate: String,
-        #[serde(rename = "sdpMid")]
-        sdp_mid: Option<String>,
-        #[serde(rename = "sdpMLineIndex")]
-        sdp_m_line_index: Option<u16>,
-    },
-    #[serde(rename = "player_died")]
-    PlayerDied,
-    #[serde(rename = "leave")]
-    Leave,
-}
+/// Room that clients land in when they join without a code.
+const DEFAULT_ROOM: &str = "public";
+const MAX_ROOM_CODE_LEN: usize = 32;
 
-#[derive(Serialize, Debug, Clone)]
-#[serde(tag = "type")]
-enum ServerMessage {
-    #[serde(rename = "welcome")]
-    Welcome {
-        #[serde(rename = "clientId")]
-        client_id: ClientId,
-        peers: Vec<PeerInfo>,
-        #[serde(rename = "gamePhase")]
-        game_phase: GamePhase,
-        #[serde(rename = "phaseTimeRemaining")]
-        phase_time_remaining: f32,
-    },
-    #[serde(rename = "peer-joined")]
-    PeerJoined {
-        #[serde(rename = "peerId")]
-        peer_id: ClientId,
-    },
-    #[serde(rename = "peer-left")]
-    PeerLeft {
-        #[serde(rename = "peerId")]
-        peer_id: ClientId,
-    },
-    #[serde(rename = "game-phase")]
-    GamePhase {
-        phase: GamePhase,
-        #[serde(rename = "timeRemaining")]
-        time_remaining: f32,
-    },
-    #[serde(rename = "offer")]
-    Offer {
-        #[serde(rename = "fromId")]
-        from_id: ClientId,
-        sdp: String,
-    },
-    #[serde(rename = "answer")]
-    Answer {
-        #[serde(rename = "fromId")]
-        from_id: ClientId,
-        sdp: String,
-    },
-    #[serde(rename = "ice-candidate")]
-    IceCandidate {
-        #[serde(rename = "fromId")]
-        from_id: ClientId,
-        candidate: String,
-        #[serde(rename = "sdpMid")]
-        sdp_mid: Option<String>,
-        #[serde(rename = "sdpMLineIndex")]
-        sdp_m_line_index: Option<u16>,
-    },
-}
+/// Kills a killer completed this soon after their own death still count:
+/// the client keeps targeting for `DEATH_GRACE_PERIOD` after death, and
+/// its clock estimate is only so accurate.
+const DEAD_KILLER_GRACE: Duration = Duration::from_millis(250);
 
-#[derive(Serialize, Debug, Clone)]
-struct PeerInfo {
-    id: ClientId,
-}
+/// Kill claims wait this long before being applied, so that a conflicting
+/// claim sent about the same time over a slower link can still arrive and
+/// be ordered before it.
+const ARBITRATION_WINDOW: Duration = Duration::from_millis(150);
 
-type ClientId = u64;
-type ClientSender = mpsc::UnboundedSender<String>;
+/// How far before its arrival a claim may say its kill happened.
+const MAX_CLAIM_AGE: Duration = Duration::from_millis(500);
+
+/// How long a dropped client's slot is held for it to resume.
+const RESUME_GRACE: Duration = Duration::from_secs(20);
+
+type ClientSender = mpsc::UnboundedSender<Message>;
+
+fn encode(msg: &ServerMessage) -> Option<Message> {
+    serde_json::to_string(msg)
+        .ok()
+        .map(|json| Message::Text(Utf8Bytes::from(json)))
+}
 
 struct ClientInfo {
     sender: ClientSender,
+    /// Socket currently attached to this slot; changes when the client resumes.
+    connection_id: ClientId,
+    resume_token: String,
+    name: Option<String>,
     is_alive: bool,
+    /// When this client died, as best we know (kills are backdated to when
+    /// the killer's gaze completed).
+    died_at: Option<Instant>,
+    killed_by: Option<ClientId>,
+    kills: u32,
+    poses: PoseHistory,
+    /// Peers who reported this client's movement as implausible.
+    reported_by: HashSet<ClientId>,
+    /// Where the client last said it was, in a star room.
+    broadcast_position: Option<Vec3>,
+    /// Set while the slot is held for a dropped client.
+    disconnected_at: Option<Instant>,
 }
 
-struct SignalingState {
-    next_id: ClientId,
+impl ClientInfo {
+    fn new(
+        sender: ClientSender,
+        connection_id: ClientId,
+        name: Option<String>,
+        is_alive: bool,
+    ) -> Self {
+        Self {
+            sender,
+            connection_id,
+            resume_token: format!("{:032x}", rand::random::<u128>()),
+            name,
+            is_alive,
+            died_at: None,
+            killed_by: None,
+            kills: 0,
+            poses: PoseHistory::default(),
+            reported_by: HashSet::new(),
+            broadcast_position: None,
+            disconnected_at: None,
+        }
+    }
+
+    fn mark_dead(&mut self) {
+        if self.is_alive {
+            self.is_alive = false;
+            self.died_at = Some(Instant::now());
+        }
+    }
+
+    fn mark_killed(&mut self, killer_id: ClientId, at: Instant) {
+        if self.is_alive {
+            self.is_alive = false;
+            self.died_at = Some(at);
+            self.killed_by = Some(killer_id);
+        }
+    }
+
+    /// Whether a kill this client completed at `killed_at` still counts.
+    fn can_claim_kill_at(&self, killed_at: Instant) -> bool {
+        self.is_alive
+            || self
+                .died_at
+                .is_some_and(|died_at| killed_at <= died_at + DEAD_KILLER_GRACE)
+    }
+}
+
+/// A kill claim waiting out the arbitration window.
+#[derive(Clone, Copy, Debug)]
+struct KillClaim {
+    killer_id: ClientId,
+    victim_id: ClientId,
+    /// When the killer's gaze completed the kill, on our clock.
+    killed_at: Instant,
+    received: Instant,
+}
+
+/// A single match: its own set of clients and game phase.
+struct Room {
+    code: String,
+    rules: RulesConfig,
+    network: NetworkConfig,
     clients: HashMap<ClientId, ClientInfo>,
     game_phase: GamePhase,
     phase_start: Instant,
     phase_duration: f32,
+    /// When the current round left the grace period, for the duration metric.
+    round_started_at: Option<Instant>,
+    /// Kill claims not yet applied, in arrival order.
+    pending_claims: Vec<KillClaim>,
 }
 
-impl SignalingState {
-    fn new() -> Self {
+impl Room {
+    fn new(code: String, rules: RulesConfig, network: NetworkConfig) -> Self {
         Self {
-            next_id: 0,
+            code,
+            rules,
+            network,
             clients: HashMap::new(),
             game_phase: GamePhase::WaitingForPlayers,
             phase_start: Instant::now(),
             phase_duration: 0.0,
+            round_started_at: None,
+            pending_claims: Vec::new(),
         }
     }
 
@@ -144,37 +163,526 @@ impl SignalingState {
         (self.phase_duration - elapsed).max(0.0)
     }
 
+    /// When the current phase ends, as a protocol timestamp; `None` if it
+    /// has no time limit.
+    fn phase_ends_at(&self) -> Option<f64> {
+        if self.phase_duration <= 0.0 {
+            return None;
+        }
+        let duration = Duration::try_from_secs_f32(self.phase_duration).ok()?;
+        self.phase_start.checked_add(duration).map(clock::to_millis)
+    }
+
     fn broadcast(&self, msg: &ServerMessage) {
-        if let Ok(json) = serde_json::to_string(msg) {
+        if let Some(frame) = encode(msg) {
             for info in self.clients.values() {
-                let _ = info.sender.send(json.clone());
+                let _ = info.sender.send(frame.clone());
+            }
+        }
+    }
+
+    fn broadcast_except(&self, except: ClientId, msg: &ServerMessage) {
+        if let Some(frame) = encode(msg) {
+            for (&id, info) in &self.clients {
+                if id != except {
+                    let _ = info.sender.send(frame.clone());
+                }
             }
         }
     }
 
+    fn send_to(&self, client_id: ClientId, msg: &ServerMessage) {
+        if let Some(target) = self.clients.get(&client_id)
+            && let Some(frame) = encode(msg)
+        {
+            let _ = target.sender.send(frame);
+        }
+    }
+
+    /// Every how many state packets `fan_out` passes on between distant
+    /// players.
+    fn state_stride(&self) -> u16 {
+        let network = &self.network;
+        if network.topology == Topology::Star && network.interest_radius > 0.0 {
+            network.distant_update_interval
+        } else {
+            1
+        }
+    }
+
+    /// Pass a star client's broadcast on to everyone else in the room.
+    ///
+    /// State packets carry the sender's position. Between players further
+    /// apart than the interest radius, only every few of them get through;
+    /// the far player moves less smoothly, but is hard to see anyway.
+    fn fan_out(&mut self, from_id: ClientId, channel: ChannelKind, data: String, binary: bool) {
+        let packet = (channel == ChannelKind::State && binary)
+            .then(|| BASE64_STANDARD.decode(&data).ok())
+            .flatten()
+            .and_then(|bytes| StatePacket::decode(&bytes).ok());
+        let Some(sender) = self.clients.get_mut(&from_id) else {
+            return;
+        };
+        if let Some(packet) = &packet {
+            sender.broadcast_position = Some(Vec3::from_array(packet.position));
+        }
+        let from_position = sender.broadcast_position;
+
+        let Some(frame) = encode(&ServerMessage::Relay {
+            from_id,
+            channel,
+            data,
+            binary,
+        }) else {
+            return;
+        };
+        let deliveries = &get_metrics().star_deliveries;
+        let radius = self.network.interest_radius;
+        for (&id, client) in &self.clients {
+            if id == from_id {
+                continue;
+            }
+            let distant = radius > 0.0
+                && from_position
+                    .zip(client.broadcast_position)
+                    .is_some_and(|(from, to)| from.distance(to) > radius);
+            // Going by the sequence number thins out every sender evenly
+            if distant
+                && let Some(packet) = &packet
+                && packet.seq % self.network.distant_update_interval != 0
+            {
+                deliveries.with_label_values(&["held-back"]).inc();
+                continue;
+            }
+            let _ = client.sender.send(frame.clone());
+            deliveries.with_label_values(&["sent"]).inc();
+        }
+    }
+
     fn set_phase(&mut self, phase: GamePhase, duration: f32) {
+        self.enter_phase(phase, duration, false);
+    }
+
+    /// End the round with no winner after sudden death ran out.
+    fn end_in_draw(&mut self) {
+        log::info!(
+            "Room '{}': sudden death ran out with {} alive, round is a draw",
+            self.code,
+            self.alive_count()
+        );
+        self.enter_phase(GamePhase::Victory, self.rules.victory_duration, true);
+    }
+
+    fn enter_phase(&mut self, phase: GamePhase, duration: f32, draw: bool) {
         self.game_phase = phase;
         self.phase_start = Instant::now();
         self.phase_duration = duration;
 
+        let metrics = get_metrics();
+        match phase {
+            GamePhase::Playing => {
+                metrics.rounds_started.inc();
+                self.round_started_at = Some(Instant::now());
+            }
+            GamePhase::Victory => {
+                if let Some(started) = self.round_started_at.take() {
+                    metrics.rounds_finished.inc();
+                    if draw {
+                        metrics.rounds_drawn.inc();
+                    }
+                    metrics
+                        .round_duration
+                        .observe(started.elapsed().as_secs_f64());
+                }
+            }
+            GamePhase::SuddenDeath => {}
+            // A round that falls back to waiting was abandoned, not finished
+            GamePhase::WaitingForPlayers | GamePhase::GracePeriod => self.round_started_at = None,
+        }
+
         // Reset all players to alive when starting a new round
         if phase == GamePhase::GracePeriod {
             for client in self.clients.values_mut() {
                 client.is_alive = true;
+                client.died_at = None;
+                client.killed_by = None;
+                // Everyone respawns, so old poses no longer apply
+                client.poses.clear();
             }
         }
 
-        log::info!("Game phase changed to {:?}, duration: {}s", phase, duration);
+        log::info!(
+            "Room '{}': game phase changed to {:?}, duration: {}s",
+            self.code,
+            phase,
+            duration
+        );
 
         self.broadcast(&ServerMessage::GamePhase {
             phase,
-            time_remaining: duration,
+            ends_at: self.phase_ends_at(),
+            draw,
         });
     }
 
     fn alive_count(&self) -> usize {
         self.clients.values().filter(|c| c.is_alive).count()
     }
+
+    /// Check a kill claim against the round state and the players' poses.
+    fn check_kill_claim(&self, claim: &KillClaim) -> Result<(), Rejection> {
+        if !self.game_phase.is_round_active() {
+            return Err(Rejection::NotPlaying);
+        }
+        let (Some(killer), Some(victim)) = (
+            self.clients.get(&claim.killer_id),
+            self.clients.get(&claim.victim_id),
+        ) else {
+            return Err(Rejection::UnknownPlayer);
+        };
+        if claim.killer_id == claim.victim_id || !victim.is_alive {
+            return Err(Rejection::VictimDead);
+        }
+        // Two players who stared each other down can't both win
+        if killer.killed_by == Some(claim.victim_id) {
+            return Err(Rejection::KilledFirst);
+        }
+        if !killer.can_claim_kill_at(claim.killed_at) {
+            return Err(Rejection::KillerDead);
+        }
+        get_referee().validate_kill(
+            &killer.poses,
+            &victim.poses,
+            self.game_phase == GamePhase::SuddenDeath,
+        )
+    }
+
+    /// Queue a kill claim for arbitration. `killed_at_ms` is the killer's
+    /// estimate of the server clock, so it's kept within reason.
+    fn claim_kill(&mut self, killer_id: ClientId, victim_id: ClientId, killed_at_ms: f64) {
+        let received = Instant::now();
+        let earliest = received.checked_sub(MAX_CLAIM_AGE).unwrap_or(received);
+        let killed_at = clock::from_millis(killed_at_ms).clamp(earliest, received);
+        self.pending_claims.push(KillClaim {
+            killer_id,
+            victim_id,
+            killed_at,
+            received,
+        });
+    }
+
+    /// Apply the claims whose arbitration window has passed, earliest kill
+    /// first, along with any pending claim whose kill came before theirs.
+    fn resolve_claims(&mut self, now: Instant) {
+        let Some(horizon) = self
+            .pending_claims
+            .iter()
+            .filter(|claim| now.duration_since(claim.received) >= ARBITRATION_WINDOW)
+            .map(|claim| claim.killed_at)
+            .max()
+        else {
+            return;
+        };
+        let (mut due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending_claims)
+            .into_iter()
+            .partition(|claim| claim.killed_at <= horizon);
+        self.pending_claims = pending;
+
+        // Ties go to the lower ID so every run resolves the same way
+        due.sort_by_key(|claim| (claim.killed_at, claim.killer_id));
+        for claim in due {
+            self.apply_claim(&claim);
+        }
+    }
+
+    /// Validate a kill claim and broadcast the death if it holds up.
+    fn apply_claim(&mut self, claim: &KillClaim) {
+        let KillClaim {
+            killer_id,
+            victim_id,
+            ..
+        } = *claim;
+        match self.check_kill_claim(claim) {
+            Ok(()) => {
+                if let Some(victim) = self.clients.get_mut(&victim_id) {
+                    victim.mark_killed(killer_id, claim.killed_at);
+                }
+                if let Some(killer) = self.clients.get_mut(&killer_id) {
+                    killer.kills += 1;
+                }
+                log::info!(
+                    "Room '{}': client {} killed {}, {} alive",
+                    self.code,
+                    killer_id,
+                    victim_id,
+                    self.alive_count()
+                );
+                self.broadcast(&ServerMessage::PlayerKilled {
+                    killer_id,
+                    victim_id,
+                });
+            }
+            Err(reason) => {
+                log::warn!(
+                    "Room '{}': rejected kill of {} by {}: {:?}",
+                    self.code,
+                    victim_id,
+                    killer_id,
+                    reason
+                );
+                self.send_to(
+                    killer_id,
+                    &ServerMessage::KillRejected {
+                        victim_id,
+                        reason: reason.reason(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Record a report that `reported_id` moves in ways the rules don't
+    /// allow. Each peer's reports count once; they only inform operators.
+    fn report_peer(
+        &mut self,
+        reporter_id: ClientId,
+        reported_id: ClientId,
+        reason: MovementViolation,
+    ) {
+        let peers = self.clients.len().saturating_sub(1);
+        let Some(reported) = self.clients.get_mut(&reported_id) else {
+            return;
+        };
+        if reporter_id == reported_id || !reported.reported_by.insert(reporter_id) {
+            return;
+        }
+        get_metrics()
+            .peer_reports
+            .with_label_values(&[violation_label(reason)])
+            .inc();
+        log::warn!(
+            "Room '{}': client {} reported {} for {:?} ({} of {} peers)",
+            self.code,
+            reporter_id,
+            reported_id,
+            reason,
+            reported.reported_by.len(),
+            peers
+        );
+    }
+
+    /// Send the welcome message describing the room and `client_id`'s slot.
+    fn welcome(&self, client_id: ClientId, resumed: bool) {
+        let Some(client) = self.clients.get(&client_id) else {
+            return;
+        };
+        let peers: Vec<PeerInfo> = self
+            .clients
+            .keys()
+            .filter(|&&id| id != client_id)
+            .map(|&id| PeerInfo { id })
+            .collect();
+
+        self.send_to(
+            client_id,
+            &ServerMessage::Welcome {
+                client_id,
+                room: self.code.clone(),
+                peers,
+                game_phase: self.game_phase,
+                phase_ends_at: self.phase_ends_at(),
+                server_time: clock::now_millis(),
+                rules: self.rules.to_wire(),
+                resume_token: client.resume_token.clone(),
+                resumed,
+                alive: client.is_alive,
+                kills: client.kills,
+                topology: self.network.topology,
+                state_stride: self.state_stride(),
+            },
+        );
+    }
+
+    /// Drop slots whose clients did not come back in time, returning their IDs.
+    fn expire_disconnected(&mut self) -> Vec<ClientId> {
+        let expired: Vec<ClientId> = self
+            .clients
+            .iter()
+            .filter(|(_, c)| {
+                c.disconnected_at
+                    .is_some_and(|t| t.elapsed() > RESUME_GRACE)
+            })
+            .map(|(&id, _)| id)
+            .collect();
+
+        for &client_id in &expired {
+            self.clients.remove(&client_id);
+            self.broadcast(&ServerMessage::PeerLeft { peer_id: client_id });
+            log::info!(
+                "Client {} did not resume, removed from room '{}', {} clients remaining",
+                client_id,
+                self.code,
+                self.clients.len()
+            );
+        }
+
+        expired
+    }
+
+    /// Advance this room's phase timers and check round transitions.
+    fn update(&mut self) {
+        self.resolve_claims(Instant::now());
+
+        let time_remaining = self.phase_time_remaining();
+        let player_count = self.clients.len();
+        let alive_count = self.alive_count();
+        let min_players = self.rules.min_players;
+        let grace_period = self.rules.grace_period;
+
+        match self.game_phase {
+            GamePhase::WaitingForPlayers => {
+                if player_count >= min_players {
+                    self.set_phase(GamePhase::GracePeriod, grace_period);
+                }
+            }
+            GamePhase::GracePeriod => {
+                if time_remaining <= 0.0 {
+                    // A zero duration means no time limit
+                    self.set_phase(GamePhase::Playing, self.rules.round_time_limit);
+                }
+            }
+            GamePhase::Playing | GamePhase::SuddenDeath => {
+                // Check for victory condition
+                if player_count >= min_players && alive_count <= 1 {
+                    self.set_phase(GamePhase::Victory, self.rules.victory_duration);
+                } else if player_count < min_players {
+                    // Not enough players, go back to waiting
+                    self.set_phase(GamePhase::WaitingForPlayers, 0.0);
+                } else if self.phase_duration > 0.0 && time_remaining <= 0.0 {
+                    if self.game_phase == GamePhase::Playing {
+                        self.set_phase(GamePhase::SuddenDeath, self.rules.sudden_death_duration);
+                    } else {
+                        self.end_in_draw();
+                    }
+                }
+            }
+            GamePhase::Victory => {
+                if time_remaining <= 0.0 {
+                    if player_count >= min_players {
+                        self.set_phase(GamePhase::GracePeriod, grace_period);
+                    } else {
+                        self.set_phase(GamePhase::WaitingForPlayers, 0.0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct SignalingState {
+    next_id: ClientId,
+    rooms: HashMap<String, Room>,
+    /// Room code of every client that has joined.
+    client_rooms: HashMap<ClientId, String>,
+}
+
+impl SignalingState {
+    fn new() -> Self {
+        Self {
+            next_id: 0,
+            rooms: HashMap::new(),
+            client_rooms: HashMap::new(),
+        }
+    }
+
+    /// Get the room a client has joined, if any.
+    fn room_of(&self, client_id: ClientId) -> Option<&Room> {
+        self.client_rooms
+            .get(&client_id)
+            .and_then(|code| self.rooms.get(code))
+    }
+
+    fn room_of_mut(&mut self, client_id: ClientId) -> Option<&mut Room> {
+        self.client_rooms
+            .get(&client_id)
+            .and_then(|code| self.rooms.get_mut(code))
+    }
+
+    /// Reattach a returning client to its held slot, if the token matches.
+    fn resume(&mut self, resume: &Resume, connection_id: ClientId, sender: ClientSender) -> bool {
+        let client_id = resume.client_id;
+        let Some(room) = self.room_of_mut(client_id) else {
+            return false;
+        };
+        let Some(client) = room.clients.get_mut(&client_id) else {
+            return false;
+        };
+        if client.resume_token != resume.token {
+            return false;
+        }
+
+        // A half-open old socket may still be attached; the new one takes over
+        client.sender = sender;
+        client.connection_id = connection_id;
+        client.disconnected_at = None;
+
+        log::info!(
+            "Client {} ({}) resumed in room '{}' on connection {}",
+            client_id,
+            client.name.as_deref().unwrap_or("unnamed"),
+            room.code,
+            connection_id
+        );
+
+        // Peers tear down their old connection and wait for a fresh offer
+        room.broadcast_except(
+            client_id,
+            &ServerMessage::PeerReconnected { peer_id: client_id },
+        );
+        room.welcome(client_id, true);
+        true
+    }
+
+    /// Remove a client and its slot right away.
+    fn remove_client(&mut self, client_id: ClientId) {
+        let Some(code) = self.client_rooms.remove(&client_id) else {
+            // Never joined a room
+            return;
+        };
+        let Some(room) = self.rooms.get_mut(&code) else {
+            return;
+        };
+
+        room.clients.remove(&client_id);
+
+        // Notify all remaining clients that this peer left
+        room.broadcast(&ServerMessage::PeerLeft { peer_id: client_id });
+
+        log::info!(
+            "Client {} removed from room '{}', {} clients remaining",
+            client_id,
+            code,
+            room.clients.len()
+        );
+    }
+}
+
+/// Normalize a requested room code; missing or empty codes map to the public room.
+fn normalize_room_code(room: Option<&str>) -> String {
+    let code: String = room
+        .unwrap_or_default()
+        .trim()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .take(MAX_ROOM_CODE_LEN)
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    if code.is_empty() {
+        DEFAULT_ROOM.to_string()
+    } else {
+        code
+    }
 }
 
 type SharedState = Arc<Mutex<SignalingState>>;
@@ -198,40 +706,152 @@ pub fn start_game_loop() {
 async fn update_game_state(state: &SharedState) {
     let mut s = state.lock().await;
 
-    let time_remaining = s.phase_time_remaining();
-    let player_count = s.clients.len();
-    let alive_count = s.alive_count();
+    let mut expired = Vec::new();
+    for room in s.rooms.values_mut() {
+        expired.extend(room.expire_disconnected());
+        room.update();
+    }
+    for client_id in expired {
+        s.client_rooms.remove(&client_id);
+    }
 
-    match s.game_phase {
-        GamePhase::WaitingForPlayers => {
-            if player_count >= MIN_PLAYERS_TO_START {
-                s.set_phase(GamePhase::GracePeriod, GRACE_PERIOD_DURATION);
-            }
-        }
-        GamePhase::GracePeriod => {
-            if time_remaining <= 0.0 {
-                s.set_phase(GamePhase::Playing, 0.0); // No time limit for playing
-            }
-        }
-        GamePhase::Playing => {
-            // Check for victory condition
-            if player_count >= MIN_PLAYERS_TO_START && alive_count <= 1 {
-                s.set_phase(GamePhase::Victory, VICTORY_DURATION);
-            } else if player_count < MIN_PLAYERS_TO_START {
-                // Not enough players, go back to waiting
-                s.set_phase(GamePhase::WaitingForPlayers, 0.0);
-            }
+    let metrics = get_metrics();
+    let clients = s.rooms.values().flat_map(|room| room.clients.values());
+    metrics
+        .connected_clients
+        .set(clients.filter(|c| c.disconnected_at.is_none()).count() as i64);
+    for phase in ALL_PHASES {
+        let count: usize = s
+            .rooms
+            .values()
+            .filter(|room| room.game_phase == phase)
+            .map(|room| room.clients.len())
+            .sum();
+        metrics
+            .clients_by_phase
+            .with_label_values(&[phase_label(phase)])
+            .set(count as i64);
+    }
+
+    // Rooms are created on demand, so drop them once everyone has left
+    s.rooms.retain(|code, room| {
+        if room.clients.is_empty() {
+            log::info!("Room '{}' is empty, closing it", code);
+            false
+        } else {
+            true
         }
-        GamePhase::Victory => {
-            if time_remaining <= 0.0 {
-                if player_count >= MIN_PLAYERS_TO_START {
-                    s.set_phase(GamePhase::GracePeriod, GRACE_PERIOD_DURATION);
-                } else {
-                    s.set_phase(GamePhase::WaitingForPlayers, 0.0);
-                }
-            }
+    });
+}
+
+// ============================================================================
+// Admin operations
+// ============================================================================
+
+/// A client as listed by the admin API.
+#[derive(Debug, Serialize)]
+pub struct ClientSummary {
+    pub id: ClientId,
+    pub name: Option<String>,
+    pub room: String,
+    pub phase: GamePhase,
+    pub alive: bool,
+    /// False while the slot is held for a dropped client.
+    pub connected: bool,
+    pub kills: u32,
+    /// Peers who reported this client's movement as implausible.
+    pub reports: usize,
+}
+
+/// List every client in every room.
+pub async fn list_clients() -> Vec<ClientSummary> {
+    let s = get_state().lock().await;
+    let mut clients: Vec<ClientSummary> = s
+        .rooms
+        .values()
+        .flat_map(|room| {
+            room.clients.iter().map(|(&id, client)| ClientSummary {
+                id,
+                name: client.name.clone(),
+                room: room.code.clone(),
+                phase: room.game_phase,
+                alive: client.is_alive,
+                connected: client.disconnected_at.is_none(),
+                kills: client.kills,
+                reports: client.reported_by.len(),
+            })
+        })
+        .collect();
+    clients.sort_by_key(|c| c.id);
+    clients
+}
+
+/// Remove a client and close its socket. Returns false if it isn't in a room.
+pub async fn kick_client(client_id: ClientId, reason: &str) -> bool {
+    let mut s = get_state().lock().await;
+    let Some(room) = s.room_of(client_id) else {
+        return false;
+    };
+
+    // Tell the client first so it doesn't try to resume
+    room.send_to(
+        client_id,
+        &ServerMessage::Kicked {
+            reason: reason.to_string(),
+        },
+    );
+    if let Some(client) = room.clients.get(&client_id) {
+        let _ = client.sender.send(Message::Close(Some(CloseFrame {
+            code: close_code::POLICY,
+            reason: Utf8Bytes::from_static("kicked"),
+        })));
+    }
+
+    log::info!("Client {} kicked by admin: {}", client_id, reason);
+    s.remove_client(client_id);
+    true
+}
+
+/// Force a room into `phase`. Without a `duration` the room's rules apply.
+/// Returns false if the room doesn't exist.
+pub async fn force_phase(room: &str, phase: GamePhase, duration: Option<f32>) -> bool {
+    let mut s = get_state().lock().await;
+    let code = normalize_room_code(Some(room));
+    let Some(room) = s.rooms.get_mut(&code) else {
+        return false;
+    };
+
+    let duration = duration.unwrap_or(match phase {
+        GamePhase::GracePeriod => room.rules.grace_period,
+        GamePhase::Playing => room.rules.round_time_limit,
+        GamePhase::SuddenDeath => room.rules.sudden_death_duration,
+        GamePhase::Victory => room.rules.victory_duration,
+        GamePhase::WaitingForPlayers => 0.0,
+    });
+    log::info!("Room '{}': admin forced phase {:?}", code, phase);
+    room.set_phase(phase, duration);
+    true
+}
+
+/// Show a notice to everyone in `room`, or in every room. Returns the number
+/// of clients it was sent to.
+pub async fn broadcast_notice(room: Option<&str>, message: &str) -> usize {
+    let s = get_state().lock().await;
+    let code = room.map(|room| normalize_room_code(Some(room)));
+    let notice = ServerMessage::Notice {
+        message: message.to_string(),
+    };
+
+    let mut delivered = 0;
+    for room in s.rooms.values() {
+        if code.as_ref().is_none_or(|code| *code == room.code) {
+            room.broadcast(&notice);
+            delivered += room.clients.len();
         }
     }
+
+    log::info!("Admin notice to {} clients: {}", delivered, message);
+    delivered
 }
 
 pub async fn ws_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
@@ -241,131 +861,259 @@ pub async fn ws_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
 async fn handle_socket(socket: WebSocket) {
     let state = get_state();
 
-    let client_id = {
+    let connection_id = {
         let mut s = state.lock().await;
         let id = s.next_id;
         s.next_id += 1;
         id
     };
 
+    // A fresh join keeps the connection ID; a resume adopts the old client ID
+    let mut client_id = connection_id;
+
     log::info!("Client {} connected", client_id);
 
-    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
 
     let (mut ws_tx, mut ws_rx) = socket.split();
 
     use futures_util::{SinkExt, StreamExt};
 
-    // Task to send messages to client
-    let send_task = tokio::spawn(async move {
+    // Task to send messages to client; returns true if we closed the socket
+    let mut send_task = tokio::spawn(async move {
         while let Some(msg) = rx.recv().await {
-            if ws_tx.send(Message::Text(msg.into())).await.is_err() {
+            let closing = matches!(msg, Message::Close(_));
+            if ws_tx.send(msg).await.is_err() {
                 break;
             }
+            if closing {
+                return true;
+            }
         }
+        false
     });
 
     // Store sender temporarily (will be moved to clients map on join)
     let sender = tx;
 
+    let mut left = false;
+
     // Receive messages from client
-    while let Some(Ok(msg)) = ws_rx.next().await {
+    let transport_reason = loop {
+        let msg = match ws_rx.next().await {
+            Some(Ok(msg)) => msg,
+            Some(Err(_)) => break "error",
+            None => break "dropped",
+        };
+        if let Message::Close(_) = msg {
+            break "closed";
+        }
         if let Message::Text(text) = msg
             && let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text)
         {
-            handle_message(client_id, client_msg, state, sender.clone()).await;
+            left |= matches!(client_msg, ClientMessage::Leave);
+            if let ClientMessage::Join { room, name, resume } = client_msg {
+                client_id = handle_join(
+                    connection_id,
+                    client_id,
+                    room,
+                    name,
+                    resume,
+                    state,
+                    sender.clone(),
+                )
+                .await;
+            } else {
+                handle_message(client_id, client_msg, state).await;
+            }
         }
-    }
+    };
+
+    // The send task only finishes on its own after we closed the socket (kick)
+    let server_closed = send_task.is_finished() && (&mut send_task).await.unwrap_or(false);
+    let reason = if left {
+        "leave"
+    } else if server_closed {
+        "kicked"
+    } else {
+        transport_reason
+    };
+    get_metrics()
+        .ws_disconnects
+        .with_label_values(&[reason])
+        .inc();
 
     // Cleanup
-    log::info!("Client {} disconnected", client_id);
-    cleanup_client(client_id, state).await;
+    log::info!("Client {} disconnected ({})", client_id, reason);
+    cleanup_client(connection_id, client_id, state).await;
     send_task.abort();
 }
 
-async fn handle_message(
+/// Place a connection in a room, returning the client ID it now speaks for.
+async fn handle_join(
+    connection_id: ClientId,
     client_id: ClientId,
-    msg: ClientMessage,
+    room: Option<String>,
+    name: Option<String>,
+    resume: Option<Resume>,
     state: &SharedState,
     sender: ClientSender,
-) {
-    match msg {
-        ClientMessage::Join => {
-            let mut s = state.lock().await;
+) -> ClientId {
+    let mut s = state.lock().await;
 
-            // Get list of existing peers
-            let peers: Vec<PeerInfo> = s.clients.iter().map(|(&id, _)| PeerInfo { id }).collect();
+    if let Some(code) = s.client_rooms.get(&client_id) {
+        log::warn!("Client {} already joined room '{}'", client_id, code);
+        return client_id;
+    }
 
-            log::info!(
-                "Client {} joined, {} existing peers, phase: {:?}",
-                client_id,
-                peers.len(),
-                s.game_phase
-            );
+    if let Some(resume) = resume {
+        if s.resume(&resume, connection_id, sender.clone()) {
+            return resume.client_id;
+        }
+        log::info!(
+            "Client {} could not resume as {}, joining fresh",
+            client_id,
+            resume.client_id
+        );
+    }
 
-            // Broadcast peer-joined to all existing clients
-            let peer_joined = ServerMessage::PeerJoined { peer_id: client_id };
-            if let Ok(json) = serde_json::to_string(&peer_joined) {
-                for info in s.clients.values() {
-                    let _ = info.sender.send(json.clone());
-                }
-            }
+    let code = normalize_room_code(room.as_deref());
+    s.client_rooms.insert(client_id, code.clone());
+    let room = s.rooms.entry(code.clone()).or_insert_with(|| {
+        Room::new(
+            code.clone(),
+            get_config().rules.clone(),
+            get_config().network.clone(),
+        )
+    });
 
-            // Add this client to the room
-            // Late joiners start as dead if game is in progress
-            let is_alive = matches!(
-                s.game_phase,
-                GamePhase::WaitingForPlayers | GamePhase::GracePeriod
-            );
+    log::info!(
+        "Client {} ({}) joined room '{}', {} existing peers, phase: {:?}",
+        client_id,
+        name.as_deref().unwrap_or("unnamed"),
+        code,
+        room.clients.len(),
+        room.game_phase
+    );
 
-            s.clients.insert(
-                client_id,
-                ClientInfo {
-                    sender: sender.clone(),
-                    is_alive,
-                },
-            );
+    // Broadcast peer-joined to all existing clients
+    room.broadcast(&ServerMessage::PeerJoined { peer_id: client_id });
 
-            // Send welcome message to the new client with game state
-            let welcome = ServerMessage::Welcome {
-                client_id,
-                peers,
-                game_phase: s.game_phase,
-                phase_time_remaining: s.phase_time_remaining(),
-            };
-            if let Ok(json) = serde_json::to_string(&welcome) {
-                let _ = sender.send(json);
+    // Add this client to the room
+    // Late joiners start as dead if game is in progress
+    let is_alive = matches!(
+        room.game_phase,
+        GamePhase::WaitingForPlayers | GamePhase::GracePeriod
+    );
+
+    room.clients.insert(
+        client_id,
+        ClientInfo::new(sender.clone(), connection_id, name, is_alive),
+    );
+
+    // Send welcome message to the new client with game state
+    room.welcome(client_id, false);
+    client_id
+}
+
+async fn handle_message(client_id: ClientId, msg: ClientMessage, state: &SharedState) {
+    match msg {
+        ClientMessage::Join { .. } => unreachable!("joins are handled by handle_join"),
+        ClientMessage::PlayerDied => {
+            let mut s = state.lock().await;
+            if let Some(room) = s.room_of_mut(client_id)
+                && let Some(client) = room.clients.get_mut(&client_id)
+            {
+                client.mark_dead();
+                log::info!(
+                    "Client {} died, {} alive in room '{}'",
+                    client_id,
+                    room.alive_count(),
+                    room.code
+                );
             }
         }
-        ClientMessage::PlayerDied => {
+        ClientMessage::Pose {
+            position,
+            yaw,
+            pitch,
+            mask,
+        } => {
             let mut s = state.lock().await;
-            if let Some(client) = s.clients.get_mut(&client_id) {
-                client.is_alive = false;
-                log::info!("Client {} died, {} alive", client_id, s.alive_count());
+            if let Some(room) = s.room_of_mut(client_id)
+                && let Some(client) = room.clients.get_mut(&client_id)
+            {
+                client.poses.push(Pose {
+                    received: Instant::now(),
+                    position: position.into(),
+                    yaw,
+                    pitch,
+                    mask: MaskType::from_u8(mask),
+                });
             }
         }
-        ClientMessage::Offer { target_id, sdp } => {
+        ClientMessage::KillClaim {
+            victim_id,
+            killed_at,
+        } => {
+            let mut s = state.lock().await;
+            if let Some(room) = s.room_of_mut(client_id) {
+                room.claim_kill(client_id, victim_id, killed_at);
+            }
+        }
+        ClientMessage::ReportPeer { peer_id, reason } => {
+            let mut s = state.lock().await;
+            if let Some(room) = s.room_of_mut(client_id) {
+                room.report_peer(client_id, peer_id, reason);
+            }
+        }
+        ClientMessage::Ping { client_time } => {
             let s = state.lock().await;
-            if let Some(target) = s.clients.get(&target_id) {
-                let msg = ServerMessage::Offer {
-                    from_id: client_id,
-                    sdp,
-                };
-                if let Ok(json) = serde_json::to_string(&msg) {
-                    let _ = target.sender.send(json);
-                }
+            if let Some(room) = s.room_of(client_id) {
+                room.send_to(
+                    client_id,
+                    &ServerMessage::Pong {
+                        client_time,
+                        server_time: clock::now_millis(),
+                    },
+                );
+            }
+        }
+        ClientMessage::Offer {
+            target_id,
+            sdp,
+            ice_restart,
+        } => {
+            get_metrics()
+                .signaling_relayed
+                .with_label_values(&["offer"])
+                .inc();
+            let s = state.lock().await;
+            if let Some(room) = s.room_of(client_id) {
+                room.send_to(
+                    target_id,
+                    &ServerMessage::Offer {
+                        from_id: client_id,
+                        sdp,
+                        ice_restart,
+                    },
+                );
             }
         }
         ClientMessage::Answer { target_id, sdp } => {
+            get_metrics()
+                .signaling_relayed
+                .with_label_values(&["answer"])
+                .inc();
             let s = state.lock().await;
-            if let Some(target) = s.clients.get(&target_id) {
-                let msg = ServerMessage::Answer {
-                    from_id: client_id,
-                    sdp,
-                };
-                if let Ok(json) = serde_json::to_string(&msg) {
-                    let _ = target.sender.send(json);
-                }
+            if let Some(room) = s.room_of(client_id) {
+                room.send_to(
+                    target_id,
+                    &ServerMessage::Answer {
+                        from_id: client_id,
+                        sdp,
+                    },
+                );
             }
         }
         ClientMessage::IceCandidate {
@@ -374,41 +1122,290 @@ async fn handle_message(
             sdp_mid,
             sdp_m_line_index,
         } => {
+            get_metrics()
+                .signaling_relayed
+                .with_label_values(&["ice-candidate"])
+                .inc();
             let s = state.lock().await;
-            if let Some(target) = s.clients.get(&target_id) {
-                let msg = ServerMessage::IceCandidate {
-                    from_id: client_id,
-                    candidate,
-                    sdp_mid,
-                    sdp_m_line_index,
-                };
-                if let Ok(json) = serde_json::to_string(&msg) {
-                    let _ = target.sender.send(json);
-                }
+            if let Some(room) = s.room_of(client_id) {
+                room.send_to(
+                    target_id,
+                    &ServerMessage::IceCandidate {
+                        from_id: client_id,
+                        candidate,
+                        sdp_mid,
+                        sdp_m_line_index,
+                    },
+                );
+            }
+        }
+        ClientMessage::Relay {
+            target_id,
+            channel,
+            data,
+            binary,
+        } => {
+            get_metrics()
+                .signaling_relayed
+                .with_label_values(&["relay"])
+                .inc();
+            let s = state.lock().await;
+            if let Some(room) = s.room_of(client_id) {
+                room.send_to(
+                    target_id,
+                    &ServerMessage::Relay {
+                        from_id: client_id,
+                        channel,
+                        data,
+                        binary,
+                    },
+                );
+            }
+        }
+        ClientMessage::Broadcast {
+            channel,
+            data,
+            binary,
+        } => {
+            get_metrics()
+                .signaling_relayed
+                .with_label_values(&["broadcast"])
+                .inc();
+            let mut s = state.lock().await;
+            if let Some(room) = s.room_of_mut(client_id) {
+                room.fan_out(client_id, channel, data, binary);
             }
         }
         ClientMessage::Leave => {
             log::info!("Client {} requested disconnect (AFK)", client_id);
+            // Leaving on purpose gives up the slot, so don't hold it
+            state.lock().await.remove_client(client_id);
         }
     }
 }
 
-async fn cleanup_client(client_id: ClientId, state: &SharedState) {
+async fn cleanup_client(connection_id: ClientId, client_id: ClientId, state: &SharedState) {
     let mut s = state.lock().await;
 
-    s.clients.remove(&client_id);
-
-    // Notify all remaining clients that this peer left
-    let peer_left = ServerMessage::PeerLeft { peer_id: client_id };
-    if let Ok(json) = serde_json::to_string(&peer_left) {
-        for info in s.clients.values() {
-            let _ = info.sender.send(json.clone());
-        }
+    let Some(room) = s.room_of_mut(client_id) else {
+        // Never joined a room, or already left
+        return;
+    };
+    let Some(client) = room.clients.get_mut(&client_id) else {
+        return;
+    };
+    if client.connection_id != connection_id {
+        // The client already resumed on a newer connection
+        return;
     }
 
+    // Hold the slot so the client can resume after a network blip
+    client.disconnected_at = Some(Instant::now());
     log::info!(
-        "Client {} removed, {} clients remaining",
+        "Client {} ({}) dropped from room '{}', holding slot for {}s",
         client_id,
-        s.clients.len()
+        client.name.as_deref().unwrap_or("unnamed"),
+        room.code,
+        RESUME_GRACE.as_secs()
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+    use protocol::KillRejectReason;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn room_with_players(count: usize) -> (Room, mpsc::UnboundedReceiver<Message>) {
+        let mut room = Room::new(
+            "test".to_string(),
+            RulesConfig::default(),
+            NetworkConfig::default(),
+        );
+        let (tx, rx) = mpsc::unbounded_channel();
+        for id in 0..count as ClientId {
+            room.clients
+                .insert(id, ClientInfo::new(tx.clone(), id, None, true));
+        }
+        (room, rx)
+    }
+
+    /// Pretend the current phase started `secs` ago.
+    fn age_phase(room: &mut Room, secs: f32) {
+        room.phase_start = Instant::now() - Duration::from_secs_f32(secs);
+    }
+
+    fn last_phase_message(rx: &mut mpsc::UnboundedReceiver<Message>) -> Option<ServerMessage> {
+        let mut last = None;
+        while let Ok(Message::Text(text)) = rx.try_recv() {
+            if let Ok(msg @ ServerMessage::GamePhase { .. }) = serde_json::from_str(&text) {
+                last = Some(msg);
+            }
+        }
+        last
+    }
+
+    #[test]
+    fn out_of_range_phase_duration_has_no_deadline() {
+        let (mut room, _rx) = room_with_players(2);
+        room.set_phase(GamePhase::Victory, 1e20);
+        assert_eq!(room.phase_ends_at(), None);
+    }
+
+    #[test]
+    fn timed_out_round_goes_to_sudden_death_then_draw() {
+        let (mut room, mut rx) = room_with_players(3);
+        let rules = room.rules.clone();
+        room.set_phase(GamePhase::Playing, rules.round_time_limit);
+
+        age_phase(&mut room, rules.round_time_limit + 1.0);
+        room.update();
+        assert_eq!(room.game_phase, GamePhase::SuddenDeath);
+
+        age_phase(&mut room, rules.sudden_death_duration + 1.0);
+        room.update();
+        assert_eq!(room.game_phase, GamePhase::Victory);
+        let Some(ServerMessage::GamePhase {
+            phase,
+            ends_at: Some(ends_at),
+            draw,
+        }) = last_phase_message(&mut rx)
+        else {
+            panic!("no phase message with a deadline");
+        };
+        assert_eq!((phase, draw), (GamePhase::Victory, true));
+        // JSON may round the last bit of the timestamp
+        assert!((ends_at - room.phase_ends_at().unwrap()).abs() < 1e-6);
+        let ends_in = clock::from_millis(room.phase_ends_at().unwrap()) - Instant::now();
+        assert!(ends_in <= Duration::from_secs_f32(rules.victory_duration));
+    }
+
+    #[test]
+    fn last_survivor_in_sudden_death_wins() {
+        let (mut room, mut rx) = room_with_players(2);
+        room.set_phase(GamePhase::SuddenDeath, room.rules.sudden_death_duration);
+        room.clients.get_mut(&0).unwrap().mark_dead();
+
+        room.update();
+        assert_eq!(room.game_phase, GamePhase::Victory);
+        assert!(matches!(
+            last_phase_message(&mut rx),
+            Some(ServerMessage::GamePhase { draw: false, .. })
+        ));
+    }
+
+    fn kill_messages(rx: &mut mpsc::UnboundedReceiver<Message>) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        while let Ok(Message::Text(text)) = rx.try_recv() {
+            if let Ok(
+                msg @ (ServerMessage::PlayerKilled { .. } | ServerMessage::KillRejected { .. }),
+            ) = serde_json::from_str(&text)
+            {
+                messages.push(msg);
+            }
+        }
+        // Every client shares one channel here, so broadcasts show up once each
+        messages.dedup();
+        messages
+    }
+
+    #[test]
+    fn earlier_claim_wins_a_mutual_kill() {
+        let (mut room, mut rx) = room_with_players(2);
+        room.set_phase(GamePhase::Playing, 0.0);
+
+        // Face to face in the open, for longer than it takes to kill
+        let spawn = Vec3::new(1446.0, 128.0, -530.5);
+        let now = Instant::now();
+        for (id, position, yaw) in [
+            (0, spawn, FRAC_PI_2),
+            (1, spawn + Vec3::X * 100.0, -FRAC_PI_2),
+        ] {
+            for age in (0..=10).rev() {
+                room.clients.get_mut(&id).unwrap().poses.push(Pose {
+                    received: now - Duration::from_millis(100 * age),
+                    position,
+                    yaw,
+                    pitch: 0.0,
+                    mask: MaskType::Hunter,
+                });
+            }
+        }
+
+        // 0's claim reaches us first, but 1's stare finished 20ms earlier
+        // The clock starts with the first test to read it; backdating needs
+        // some history
+        while clock::now_millis() < 50.0 {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let now = clock::now_millis();
+        room.claim_kill(0, 1, now);
+        room.claim_kill(1, 0, now - 20.0);
+        room.resolve_claims(Instant::now() + ARBITRATION_WINDOW);
+
+        assert!(room.pending_claims.is_empty());
+        assert!(room.clients[&1].is_alive);
+        assert!(!room.clients[&0].is_alive);
+        assert_eq!(
+            kill_messages(&mut rx),
+            vec![
+                ServerMessage::PlayerKilled {
+                    killer_id: 1,
+                    victim_id: 0,
+                },
+                ServerMessage::KillRejected {
+                    victim_id: 1,
+                    reason: KillRejectReason::KilledFirst,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn claims_from_long_dead_killers_are_rejected() {
+        let (mut room, mut rx) = room_with_players(3);
+        room.set_phase(GamePhase::Playing, 0.0);
+        let now = Instant::now();
+        room.clients
+            .get_mut(&0)
+            .unwrap()
+            .mark_killed(2, now - DEAD_KILLER_GRACE * 2);
+
+        room.claim_kill(0, 1, 0.0);
+        room.resolve_claims(Instant::now() + ARBITRATION_WINDOW);
+
+        assert!(room.clients[&1].is_alive);
+        assert_eq!(
+            kill_messages(&mut rx),
+            vec![ServerMessage::KillRejected {
+                victim_id: 1,
+                reason: KillRejectReason::KillerDead,
+            }]
+        );
+    }
+
+    #[test]
+    fn each_peer_reports_a_client_once() {
+        let (mut room, _rx) = room_with_players(3);
+        room.report_peer(0, 2, MovementViolation::TooFast);
+        room.report_peer(0, 2, MovementViolation::Flying);
+        room.report_peer(1, 2, MovementViolation::ThroughWall);
+        room.report_peer(2, 2, MovementViolation::TooFast);
+        room.report_peer(0, 9, MovementViolation::TooFast);
+
+        let reported_by = &room.clients[&2].reported_by;
+        assert_eq!(reported_by, &HashSet::from([0, 1]));
+        assert!(room.clients[&0].reported_by.is_empty());
+    }
+
+    #[test]
+    fn zero_time_limit_never_times_out() {
+        let (mut room, _rx) = room_with_players(2);
+        room.set_phase(GamePhase::Playing, 0.0);
+
+        age_phase(&mut room, 3600.0);
+        room.update();
+        assert_eq!(room.game_phase, GamePhase::Playing);
+    }
+}
@@ -1,7 +1,11 @@
-use std::collections::HashMap;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use ring::hmac;
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
 use tokio::time::Duration;
 use turn::Error;
 use turn::auth::{AuthHandler, generate_auth_key};
@@ -10,40 +14,70 @@ use turn::server::Server;
 use turn::server::config::{ConnConfig, ServerConfig};
 use webrtc_util::vnet::net::Net;
 
-/// TURN server credentials - static for simplicity
-/// In production, use time-limited credentials
-pub const TURN_USERNAME: &str = "ggj26";
-pub const TURN_PASSWORD: &str = "globalgamejam2026";
-const TURN_REALM: &str = "globalgamejam";
+use crate::config::TurnConfig;
+use crate::metrics::get_metrics;
 
-struct StaticAuthHandler {
-    cred_map: HashMap<String, Vec<u8>>,
+/// How often the open-allocation gauge is refreshed.
+const ALLOCATION_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Mint a time-limited credential pair (TURN REST API style).
+///
+/// The username is `expiry:user_id` with the expiry in Unix seconds, and the
+/// password is the base64 HMAC-SHA1 of the username under the server secret,
+/// so the TURN server can check it without remembering what it handed out.
+pub fn mint_credentials(turn_config: &TurnConfig, user_id: &str) -> (String, String) {
+    let expiry = unix_now() + turn_config.credential_ttl;
+    let username = format!("{}:{}", expiry, user_id);
+    let password = credential_password(&turn_config.secret, &username);
+    (username, password)
 }
 
-impl StaticAuthHandler {
-    fn new(username: &str, password: &str, realm: &str) -> Self {
-        let mut cred_map = HashMap::new();
-        let key = generate_auth_key(username, realm, password);
-        cred_map.insert(username.to_owned(), key);
-        Self { cred_map }
-    }
+fn credential_password(secret: &str, username: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret.as_bytes());
+    BASE64_STANDARD.encode(hmac::sign(&key, username.as_bytes()))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Accepts credentials minted by `mint_credentials` until they expire.
+struct RestAuthHandler {
+    secret: String,
 }
 
-impl AuthHandler for StaticAuthHandler {
+impl AuthHandler for RestAuthHandler {
     fn auth_handle(
         &self,
         username: &str,
-        _realm: &str,
-        _src_addr: SocketAddr,
+        realm: &str,
+        src_addr: SocketAddr,
     ) -> Result<Vec<u8>, Error> {
-        self.cred_map
-            .get(username)
-            .cloned()
-            .ok_or(Error::ErrFakeErr)
+        let expiry = username
+            .split_once(':')
+            .and_then(|(expiry, _)| expiry.parse::<u64>().ok())
+            .ok_or_else(|| Error::Other(format!("Malformed TURN username {}", username)))?;
+
+        if expiry < unix_now() {
+            log::debug!("Expired TURN credential {} from {}", username, src_addr);
+            return Err(Error::Other(format!("Expired TURN username {}", username)));
+        }
+
+        // The key is derived from the expected password, so a request signed
+        // with anything but our HMAC fails the message integrity check
+        let password = credential_password(&self.secret, username);
+        Ok(generate_auth_key(username, realm, &password))
     }
 }
 
-pub async fn run_turn_server(port: u16, public_ip: IpAddr) -> Result<(), Error> {
+pub async fn run_turn_server(
+    port: u16,
+    public_ip: IpAddr,
+    turn_config: &TurnConfig,
+) -> Result<(), Error> {
     let conn = Arc::new(UdpSocket::bind(format!("0.0.0.0:{}", port)).await?);
     log::info!(
         "TURN/STUN server running on UDP port {}, public IP: {}",
@@ -51,6 +85,8 @@ pub async fn run_turn_server(port: u16, public_ip: IpAddr) -> Result<(), Error>
         public_ip
     );
 
+    let (alloc_close_tx, mut alloc_close_rx) = mpsc::channel(64);
+
     let server = Server::new(ServerConfig {
         conn_configs: vec![ConnConfig {
             conn,
@@ -60,31 +96,39 @@ pub async fn run_turn_server(port: u16, public_ip: IpAddr) -> Result<(), Error>
                 net: Arc::new(Net::new(None)),
             }),
         }],
-        realm: TURN_REALM.to_owned(),
-        auth_handler: Arc::new(StaticAuthHandler::new(
-            TURN_USERNAME,
-            TURN_PASSWORD,
-            TURN_REALM,
-        )),
+        realm: turn_config.realm.clone(),
+        auth_handler: Arc::new(RestAuthHandler {
+            secret: turn_config.secret.clone(),
+        }),
         // Channel bindings expire after 10 minutes (RFC 5766 recommends 10 min)
         channel_bind_timeout: Duration::from_secs(600),
-        alloc_close_notify: None,
+        alloc_close_notify: Some(alloc_close_tx),
     })
     .await?;
 
     log::info!(
-        "TURN server ready (credentials: {}:{})",
-        TURN_USERNAME,
-        TURN_PASSWORD
+        "TURN server ready (realm: {}, credentials valid for {}s)",
+        turn_config.realm,
+        turn_config.credential_ttl
     );
 
-    // Keep the server running indefinitely
-    // The Server handles all STUN/TURN requests internally
-    std::future::pending::<()>().await;
-
-    // This code is unreachable but keeps the compiler happy
-    server.close().await?;
-    Ok(())
+    // The Server handles all STUN/TURN requests internally; we only watch
+    // its allocations for the metrics
+    let metrics = get_metrics();
+    let mut poll = tokio::time::interval(ALLOCATION_POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            Some(info) = alloc_close_rx.recv() => {
+                log::debug!("TURN allocation for {} closed", info.username);
+                metrics.turn_allocations_closed.inc();
+                metrics.turn_relayed_bytes.inc_by(info.relayed_bytes as u64);
+            }
+            _ = poll.tick() => match server.get_allocations_info(None).await {
+                Ok(allocations) => metrics.turn_allocations_active.set(allocations.len() as i64),
+                Err(e) => log::warn!("Failed to read TURN allocations: {}", e),
+            },
+        }
+    }
 }
 
 /// Get public IP from environment variable or use fallback
@@ -97,3 +141,51 @@ pub fn get_public_ip() -> IpAddr {
             "0.0.0.0".parse().unwrap()
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn_config() -> TurnConfig {
+        TurnConfig {
+            realm: "test".to_string(),
+            secret: "secret".to_string(),
+            credential_ttl: 60,
+        }
+    }
+
+    fn auth(username: &str) -> Result<Vec<u8>, Error> {
+        let handler = RestAuthHandler {
+            secret: "secret".to_string(),
+        };
+        handler.auth_handle(username, "test", "127.0.0.1:5000".parse().unwrap())
+    }
+
+    #[test]
+    fn minted_credentials_are_accepted() {
+        let (username, password) = mint_credentials(&turn_config(), "abc");
+        assert!(username.ends_with(":abc"));
+        assert_eq!(
+            auth(&username).unwrap(),
+            generate_auth_key(&username, "test", &password)
+        );
+    }
+
+    #[test]
+    fn other_users_cannot_reuse_a_password() {
+        let (username, password) = mint_credentials(&turn_config(), "abc");
+        let forged = username.replace(":abc", ":xyz");
+        assert_ne!(
+            auth(&forged).unwrap(),
+            generate_auth_key(&forged, "test", &password)
+        );
+    }
+
+    #[test]
+    fn expired_and_malformed_usernames_are_rejected() {
+        let expired = format!("{}:abc", unix_now() - 1);
+        assert!(auth(&expired).is_err());
+        assert!(auth("ggj26").is_err());
+        assert!(auth("soon:abc").is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Estimate of the server clock.
+//!
+//! Protocol timestamps are milliseconds on the server's clock. We keep our
+//! own monotonic clock and an offset to the server's, measured NTP-style:
+//! a ping carries our send time, the pong adds the server's time, and
+//! assuming the two legs took equally long,
+//! `offset = server_time - (sent + received) / 2`. Exchanges that took
+//! longest are the least trustworthy, so the estimate follows the fastest
+//! recent one.
+
+use std::collections::VecDeque;
+use web_time::Instant;
+
+/// Ping/pong exchanges the estimate is chosen from.
+const MAX_SAMPLES: usize = 8;
+
+#[derive(Clone, Copy)]
+struct Sample {
+    rtt: f64,
+    offset: f64,
+}
+
+pub struct ClockSync {
+    epoch: Instant,
+    samples: VecDeque<Sample>,
+    /// Server clock minus local clock, in milliseconds.
+    offset: f64,
+}
+
+impl Default for ClockSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockSync {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            samples: VecDeque::with_capacity(MAX_SAMPLES),
+            offset: 0.0,
+        }
+    }
+
+    /// Our local clock, in milliseconds. Pings are stamped with it.
+    pub fn local_millis(&self) -> f64 {
+        self.epoch.elapsed().as_secs_f64() * 1000.0
+    }
+
+    /// Our best estimate of the server clock right now.
+    pub fn server_millis(&self) -> f64 {
+        self.local_millis() + self.offset
+    }
+
+    /// Forget all exchanges, e.g. after the server restarted with a new
+    /// clock origin. The next `observe` sets the offset again.
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Take a server timestamp of unknown latency as a first estimate. It is
+    /// behind by the one-way latency, so pongs replace it as soon as any
+    /// arrive.
+    pub fn observe(&mut self, server_millis: f64) {
+        if self.samples.is_empty() {
+            self.offset = server_millis - self.local_millis();
+        }
+    }
+
+    /// Fold in a pong for a ping we sent at `client_millis`.
+    pub fn handle_pong(&mut self, client_millis: f64, server_millis: f64) {
+        self.add_sample(client_millis, server_millis, self.local_millis());
+    }
+
+    /// Fold in an exchange that came back at `received` on our clock.
+    fn add_sample(&mut self, client_millis: f64, server_millis: f64, received: f64) {
+        let rtt = received - client_millis;
+        if rtt < 0.0 {
+            // Not one of ours (e.g. from before a page reload)
+            return;
+        }
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample {
+            rtt,
+            offset: server_millis - (client_millis + received) / 2.0,
+        });
+        if let Some(best) = self.samples.iter().min_by(|a, b| a.rtt.total_cmp(&b.rtt)) {
+            self.offset = best.offset;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fastest_exchange_sets_the_offset() {
+        let mut clock = ClockSync::new();
+        // Server ahead by 1000 ms; the slow exchange's return leg took longer
+        clock.add_sample(0.0, 1080.0, 200.0);
+        assert_eq!(clock.offset, 980.0);
+        clock.add_sample(300.0, 1310.0, 320.0);
+        assert_eq!(clock.offset, 1000.0);
+        clock.add_sample(400.0, 1500.0, 600.0);
+        assert_eq!(clock.offset, 1000.0);
+    }
+
+    #[test]
+    fn old_exchanges_age_out() {
+        let mut clock = ClockSync::new();
+        clock.add_sample(0.0, 1010.0, 20.0);
+        for sent in 1..=MAX_SAMPLES {
+            let sent = sent as f64 * 1000.0;
+            clock.add_sample(sent, sent + 2050.0, sent + 100.0);
+        }
+        assert_eq!(clock.samples.len(), MAX_SAMPLES);
+        assert_eq!(clock.offset, 2000.0);
+    }
+
+    #[test]
+    fn pongs_from_the_future_are_ignored() {
+        let mut clock = ClockSync::new();
+        clock.add_sample(500.0, 9000.0, 100.0);
+        assert!(clock.samples.is_empty());
+        assert_eq!(clock.offset, 0.0);
+    }
+
+    #[test]
+    fn observations_only_count_until_the_first_pong() {
+        let mut clock = ClockSync::new();
+        clock.observe(clock.local_millis() + 5000.0);
+        assert!((clock.offset - 5000.0).abs() < 50.0);
+
+        clock.add_sample(0.0, 1010.0, 20.0);
+        clock.observe(clock.local_millis() + 5000.0);
+        assert_eq!(clock.offset, 1000.0);
+
+        // A new server clock takes over again
+        clock.reset();
+        clock.observe(clock.local_millis() - 3000.0);
+        assert!((clock.offset + 3000.0).abs() < 50.0);
+    }
+}
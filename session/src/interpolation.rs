@@ -0,0 +1,425 @@
+//! Snapshot interpolation for remote players.
+//!
+//! State packets arrive at the sender's tick rate with network jitter, and on
+//! an unordered channel. Rather than snapping to the newest one, each remote
+//! player keeps a short buffer of timestamped snapshots and is rendered a
+//! little in the past, between two snapshots that have both arrived. The
+//! delay adapts to how late packets actually arrive; when the buffer runs dry
+//! (lost packets) the last motion is extrapolated for a short while.
+//!
+//! The buffer doubles as the pose history lag compensation rewinds into, and
+//! `HeadHistory` keeps the same for the local player.
+
+use glam::Vec3;
+use std::collections::VecDeque;
+use std::f32::consts::{PI, TAU};
+use web_time::{Duration, Instant};
+
+/// Snapshots kept per player; at 30 Hz about a second of history.
+const MAX_SNAPSHOTS: usize = 32;
+
+/// Bounds for the interpolation delay, in seconds.
+const MIN_DELAY: f64 = 0.03;
+const MAX_DELAY: f64 = 0.3;
+
+/// How quickly the delay follows its target, per packet.
+const DELAY_SMOOTHING: f64 = 0.1;
+
+/// How far past the newest snapshot motion is extrapolated, in seconds.
+const MAX_EXTRAPOLATION: f64 = 0.2;
+
+/// A jump between consecutive snapshots longer than this is a respawn, not
+/// movement, and isn't interpolated across.
+const TELEPORT_DISTANCE: f32 = 1000.0;
+
+/// Packets this far behind the newest sequence number are not reordered
+/// stragglers but a sender that restarted its counter.
+const REORDER_WINDOW: i16 = 64;
+
+/// A remote player's pose at a point in time.
+#[derive(Clone, Copy)]
+pub struct Pose {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Units per second, for motion blur.
+    pub velocity: Vec3,
+}
+
+#[derive(Clone, Copy)]
+struct Snapshot {
+    /// Sender clock, seconds.
+    sent_at: f64,
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+}
+
+pub struct SnapshotBuffer {
+    /// Ordered by `sent_at`.
+    snapshots: VecDeque<Snapshot>,
+    /// Local arrival time minus sender time, for each buffered snapshot.
+    /// The smallest is the best estimate of the clock offset plus the
+    /// one-way latency; anything above it is jitter.
+    arrival_offsets: VecDeque<f64>,
+    last_seq: Option<u16>,
+    /// Current interpolation delay, seconds.
+    delay: f64,
+    epoch: Instant,
+}
+
+impl Default for SnapshotBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SnapshotBuffer {
+    pub fn new() -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(MAX_SNAPSHOTS),
+            arrival_offsets: VecDeque::with_capacity(MAX_SNAPSHOTS),
+            last_seq: None,
+            delay: MAX_DELAY / 2.0,
+            epoch: Instant::now(),
+        }
+    }
+
+    fn local_time(&self, at: Instant) -> f64 {
+        at.duration_since(self.epoch).as_secs_f64()
+    }
+
+    /// Add a snapshot that arrived at `now`. Returns false if it was
+    /// discarded as out of order.
+    pub fn push(
+        &mut self,
+        now: Instant,
+        seq: u16,
+        sent_at: f64,
+        position: Vec3,
+        yaw: f32,
+        pitch: f32,
+    ) -> bool {
+        if let Some(last) = self.last_seq {
+            let ahead = seq.wrapping_sub(last) as i16;
+            if ahead <= 0 && ahead > -REORDER_WINDOW {
+                return false;
+            }
+            if ahead <= -REORDER_WINDOW {
+                self.clear();
+            }
+        }
+        self.last_seq = Some(seq);
+
+        if let Some(newest) = self.snapshots.back()
+            && newest.position.distance(position) > TELEPORT_DISTANCE
+        {
+            self.clear();
+            self.last_seq = Some(seq);
+        }
+
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+            self.arrival_offsets.pop_front();
+        }
+        let arrival = self.local_time(now);
+        self.snapshots.push_back(Snapshot {
+            sent_at,
+            position,
+            yaw,
+            pitch,
+        });
+        self.arrival_offsets.push_back(arrival - sent_at);
+        self.adapt_delay();
+        true
+    }
+
+    fn clear(&mut self) {
+        self.snapshots.clear();
+        self.arrival_offsets.clear();
+        self.last_seq = None;
+    }
+
+    /// Lowest observed arrival offset, i.e. the offset of the least delayed
+    /// packet. Tracks clock drift as old snapshots leave the buffer.
+    fn base_offset(&self) -> Option<f64> {
+        self.arrival_offsets.iter().copied().reduce(f64::min)
+    }
+
+    /// Aim the delay at one packet interval plus the worst recent lateness,
+    /// so the snapshot after the render time has usually arrived.
+    fn adapt_delay(&mut self) {
+        let (Some(base), Some(first), Some(last)) = (
+            self.base_offset(),
+            self.snapshots.front(),
+            self.snapshots.back(),
+        ) else {
+            return;
+        };
+        if self.snapshots.len() < 2 {
+            return;
+        }
+        let interval = (last.sent_at - first.sent_at) / (self.snapshots.len() - 1) as f64;
+        let jitter = self
+            .arrival_offsets
+            .iter()
+            .map(|offset| offset - base)
+            .fold(0.0, f64::max);
+        let target = (interval + jitter).clamp(MIN_DELAY, MAX_DELAY);
+        self.delay += (target - self.delay) * DELAY_SMOOTHING;
+    }
+
+    /// Whether no snapshot has arrived yet.
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Current interpolation delay, in seconds.
+    pub fn delay(&self) -> f64 {
+        self.delay
+    }
+
+    /// The pose to render now, or `None` before the first snapshot.
+    pub fn sample(&self, now: Instant) -> Option<Pose> {
+        let base = self.base_offset()?;
+        self.sample_at(self.local_time(now) - base - self.delay)
+    }
+
+    /// The pose the sender had `behind` seconds before its present, given
+    /// the one-way latency to it. With `behind` equal to the latency plus
+    /// the interpolation delay, this is the pose `sample` renders.
+    pub fn sample_behind(&self, now: Instant, one_way: f64, behind: f64) -> Option<Pose> {
+        let base = self.base_offset()?;
+        self.sample_at(self.local_time(now) - base + one_way - behind)
+    }
+
+    /// The pose at `render_at` on the sender's clock.
+    fn sample_at(&self, render_at: f64) -> Option<Pose> {
+        let newest = *self.snapshots.back()?;
+        let oldest = *self.snapshots.front()?;
+        if render_at <= oldest.sent_at {
+            return Some(Pose::at_rest(oldest));
+        }
+
+        if render_at >= newest.sent_at {
+            let Some(&previous) = self.snapshots.iter().rev().nth(1) else {
+                return Some(Pose::at_rest(newest));
+            };
+            let ahead = render_at - newest.sent_at;
+            if ahead > MAX_EXTRAPOLATION {
+                // Out of data for too long: hold where extrapolation stopped
+                let mut pose = Pose::between(previous, newest, newest.sent_at + MAX_EXTRAPOLATION);
+                pose.velocity = Vec3::ZERO;
+                return Some(pose);
+            }
+            return Some(Pose::between(previous, newest, render_at));
+        }
+
+        let next_index = self
+            .snapshots
+            .iter()
+            .position(|snapshot| snapshot.sent_at > render_at)?;
+        Some(Pose::between(
+            self.snapshots[next_index - 1],
+            self.snapshots[next_index],
+            render_at,
+        ))
+    }
+}
+
+impl Pose {
+    fn at_rest(snapshot: Snapshot) -> Self {
+        Self {
+            position: snapshot.position,
+            yaw: snapshot.yaw,
+            pitch: snapshot.pitch,
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    /// Interpolate (or, past `to`, extrapolate) between two snapshots.
+    fn between(from: Snapshot, to: Snapshot, at: f64) -> Self {
+        let span = to.sent_at - from.sent_at;
+        if span <= 0.0 {
+            return Self::at_rest(to);
+        }
+        let t = ((at - from.sent_at) / span) as f32;
+        Self {
+            position: from.position.lerp(to.position, t),
+            yaw: from.yaw + shortest_arc(from.yaw, to.yaw) * t,
+            pitch: from.pitch + (to.pitch - from.pitch) * t,
+            velocity: (to.position - from.position) / span as f32,
+        }
+    }
+}
+
+/// Head positions of the local player over the last second, so threat checks
+/// can look at where we were when a remote player saw us.
+pub struct HeadHistory {
+    samples: VecDeque<(Instant, Vec3)>,
+}
+
+/// How much local history `HeadHistory` keeps.
+const HEAD_HISTORY: Duration = Duration::from_secs(1);
+
+impl Default for HeadHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeadHistory {
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, now: Instant, head: Vec3) {
+        while let Some(&(at, _)) = self.samples.front() {
+            if now.duration_since(at) <= HEAD_HISTORY {
+                break;
+            }
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, head));
+    }
+
+    /// Where our head was at `at`, clamped to the recorded range.
+    pub fn at(&self, at: Instant) -> Option<Vec3> {
+        let next_index = self.samples.iter().position(|&(time, _)| time >= at);
+        match next_index {
+            None => self.samples.back().map(|&(_, head)| head),
+            Some(0) => self.samples.front().map(|&(_, head)| head),
+            Some(index) => {
+                let (from_time, from) = self.samples[index - 1];
+                let (to_time, to) = self.samples[index];
+                let span = to_time.duration_since(from_time).as_secs_f32();
+                if span <= 0.0 {
+                    return Some(to);
+                }
+                let t = at.duration_since(from_time).as_secs_f32() / span;
+                Some(from.lerp(to, t))
+            }
+        }
+    }
+}
+
+/// Signed smallest rotation from `from` to `to`, in `[-PI, PI)`.
+fn shortest_arc(from: f32, to: f32) -> f32 {
+    (to - from + PI).rem_euclid(TAU) - PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seconds between state packets.
+    const TICK: f64 = 1.0 / 30.0;
+
+    fn at(start: Instant, seconds: f64) -> Instant {
+        start + Duration::from_secs_f64(seconds)
+    }
+
+    /// Push snapshot `seq` of a player walking along X at 30 units per
+    /// tick, arriving with no delay.
+    fn walk(buffer: &mut SnapshotBuffer, start: Instant, seq: u16, tick: u32) -> bool {
+        let sent_at = tick as f64 * TICK;
+        let position = Vec3::new(tick as f32 * 30.0, 0.0, 0.0);
+        buffer.push(at(start, sent_at), seq, sent_at, position, 0.0, 0.0)
+    }
+
+    #[test]
+    fn late_packets_are_dropped() {
+        let mut buffer = SnapshotBuffer::new();
+        let start = buffer.epoch;
+        assert!(walk(&mut buffer, start, 10, 0));
+        assert!(walk(&mut buffer, start, 12, 2));
+        assert!(!walk(&mut buffer, start, 11, 1));
+        assert!(!walk(&mut buffer, start, 12, 2));
+        assert_eq!(buffer.snapshots.len(), 2);
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        let mut buffer = SnapshotBuffer::new();
+        let start = buffer.epoch;
+        assert!(walk(&mut buffer, start, u16::MAX - 1, 0));
+        assert!(walk(&mut buffer, start, u16::MAX, 1));
+        assert!(walk(&mut buffer, start, 0, 2));
+        assert!(walk(&mut buffer, start, 1, 3));
+        assert!(!walk(&mut buffer, start, u16::MAX, 1));
+        assert_eq!(buffer.snapshots.len(), 4);
+    }
+
+    #[test]
+    fn restarted_counters_start_over() {
+        let mut buffer = SnapshotBuffer::new();
+        let start = buffer.epoch;
+        for tick in 0..3 {
+            assert!(walk(&mut buffer, start, 1000 + tick as u16, tick));
+        }
+        assert!(walk(&mut buffer, start, 0, 3));
+        assert_eq!(buffer.snapshots.len(), 1);
+        assert!(walk(&mut buffer, start, 1, 4));
+    }
+
+    #[test]
+    fn teleports_are_not_interpolated_across() {
+        let mut buffer = SnapshotBuffer::new();
+        let start = buffer.epoch;
+        walk(&mut buffer, start, 0, 0);
+        walk(&mut buffer, start, 1, 1);
+        let far = Vec3::new(0.0, 0.0, TELEPORT_DISTANCE * 2.0);
+        assert!(buffer.push(at(start, 2.0 * TICK), 2, 2.0 * TICK, far, 0.0, 0.0));
+
+        assert_eq!(buffer.snapshots.len(), 1);
+        let pose = buffer.sample(at(start, 1.0)).unwrap();
+        assert_eq!(pose.position, far);
+        assert_eq!(pose.velocity, Vec3::ZERO);
+    }
+
+    #[test]
+    fn renders_between_snapshots() {
+        let mut buffer = SnapshotBuffer::new();
+        let start = buffer.epoch;
+        for tick in 0..10 {
+            walk(&mut buffer, start, tick as u16, tick);
+        }
+        let render_at = 5.5 * TICK;
+        let pose = buffer
+            .sample(at(start, render_at + buffer.delay()))
+            .unwrap();
+        assert!((pose.position.x - 165.0).abs() < 0.01);
+        assert!((pose.velocity.x - 900.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn extrapolation_holds_after_a_while() {
+        let mut buffer = SnapshotBuffer::new();
+        let start = buffer.epoch;
+        for tick in 0..10 {
+            walk(&mut buffer, start, tick as u16, tick);
+        }
+        let newest = 9.0 * TICK;
+        let sample = |ahead: f64| {
+            buffer
+                .sample(at(start, newest + ahead + buffer.delay()))
+                .unwrap()
+        };
+
+        let moving = sample(MAX_EXTRAPOLATION / 2.0);
+        assert!((moving.position.x - (270.0 + 900.0 * 0.1)).abs() < 0.01);
+        assert!(moving.velocity.x > 0.0);
+
+        let held = sample(MAX_EXTRAPOLATION * 5.0);
+        assert!((held.position.x - (270.0 + 900.0 * MAX_EXTRAPOLATION as f32)).abs() < 0.01);
+        assert_eq!(held.velocity, Vec3::ZERO);
+    }
+
+    #[test]
+    fn yaw_turns_the_short_way() {
+        assert!((shortest_arc(3.0, -3.0) - (TAU - 6.0)).abs() < 1e-5);
+        assert!((shortest_arc(-3.0, 3.0) + (TAU - 6.0)).abs() < 1e-5);
+    }
+}
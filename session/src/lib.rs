@@ -0,0 +1,46 @@
+//! Peer session logic shared by the game client and its tests.
+//!
+//! A `Session` keeps a room's worth of peer connections going: it follows the
+//! signaling server's room updates, negotiates a connection to every peer and
+//! routes messages to and from them. It only talks to the outside world
+//! through two traits:
+//!
+//! - `Signaling`: the connection to the signaling server (a WebSocket in the
+//!   browser)
+//! - `PeerConnector`/`PeerTransport`: peer-to-peer connections (WebRTC in the
+//!   browser)
+//!
+//! so the same logic runs against in-process implementations in native tests.
+//! The server clock estimate and the snapshot buffers remote players are
+//! rendered from live here for the same reason.
+
+mod clock;
+mod interpolation;
+mod link;
+mod netsim;
+mod queue;
+mod reception;
+mod session;
+mod signaling;
+mod transport;
+
+pub use clock::ClockSync;
+pub use interpolation::{HeadHistory, Pose, SnapshotBuffer};
+pub use netsim::{LinkConditions, NetworkConditions, NetworkSimulator};
+pub use queue::EventQueue;
+pub use reception::{ReceptionQuality, StateReception};
+pub use session::{Session, SessionEvent};
+pub use signaling::{Signaling, SignalingEvent};
+pub use transport::{IceCandidateData, IceState, Payload, PeerConnector, PeerEvent, PeerTransport};
+
+/// Unique identifier for a connected peer (the ID the server assigned it).
+pub type PeerId = protocol::ClientId;
+
+/// Log level for the network status lines shown to players.
+#[derive(Clone, Copy, Debug)]
+pub enum NetLogLevel {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
@@ -0,0 +1,247 @@
+//! Health of the direct connection to one peer.
+//!
+//! ICE reports `Disconnected` when a path stops working, which often heals by
+//! itself, and `Failed` when it has given up. `PeerLink` turns those reports
+//! into recovery steps: after a short grace period it asks for an ICE restart
+//! (new candidates on the same connection), and if restarts don't bring the
+//! connection back it asks for the connection to be rebuilt from scratch.
+
+use web_time::{Duration, Instant};
+
+use crate::transport::IceState;
+
+/// How long a disconnected path may take to come back by itself.
+const DISCONNECT_GRACE: Duration = Duration::from_secs(2);
+
+/// How long a new connection, restart or rebuild gets to connect.
+const RECOVERY_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// ICE restarts tried before rebuilding the connection.
+const MAX_ICE_RESTARTS: u32 = 2;
+
+/// Rebuilds tried before giving up on a direct connection.
+const MAX_REBUILDS: u32 = 2;
+
+/// A step towards getting a connection back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Renegotiate ICE on the existing connection.
+    RestartIce,
+    /// Close the connection and negotiate a new one.
+    Rebuild,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum LinkState {
+    /// Negotiating a new connection.
+    Connecting {
+        since: Instant,
+    },
+    Connected,
+    /// ICE lost the path and may yet find it again.
+    Disconnected {
+        since: Instant,
+    },
+    /// ICE gave up on the current candidates.
+    Failed,
+    /// Waiting for an ICE restart to take effect.
+    Restarting {
+        since: Instant,
+    },
+    /// Out of recovery attempts.
+    GaveUp,
+}
+
+pub struct PeerLink {
+    state: LinkState,
+    /// ICE restarts since the connection last worked.
+    restarts: u32,
+    /// Rebuilds since the connection last worked.
+    rebuilds: u32,
+    /// Set once recovery has started, until the connection works again.
+    lost: bool,
+}
+
+impl PeerLink {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            state: LinkState::Connecting { since: now },
+            restarts: 0,
+            rebuilds: 0,
+            lost: false,
+        }
+    }
+
+    /// Whether the peer has been unreachable for longer than a hiccup.
+    pub fn is_lost(&self) -> bool {
+        self.lost
+    }
+
+    /// Whether the last connection attempt ran out of retries.
+    pub fn gave_up(&self) -> bool {
+        matches!(self.state, LinkState::GaveUp)
+    }
+
+    /// Follow an ICE connection state change.
+    pub fn ice_state_changed(&mut self, state: IceState, now: Instant) {
+        match state {
+            IceState::Connected | IceState::Completed => {
+                self.state = LinkState::Connected;
+                self.restarts = 0;
+                self.rebuilds = 0;
+                self.lost = false;
+            }
+            IceState::Disconnected => {
+                if let LinkState::Connected = self.state {
+                    self.state = LinkState::Disconnected { since: now };
+                }
+            }
+            IceState::Failed => {
+                if !self.gave_up() {
+                    self.state = LinkState::Failed;
+                }
+            }
+            IceState::New | IceState::Checking | IceState::Closed => {}
+        }
+    }
+
+    /// The recovery step that is due, if any. The caller is expected to carry
+    /// it out (or leave a rebuild to the peer, if it's the polite side).
+    pub fn update(&mut self, now: Instant) -> Option<Recovery> {
+        let due = match self.state {
+            LinkState::Connected | LinkState::GaveUp => false,
+            LinkState::Failed => true,
+            LinkState::Disconnected { since } => now.duration_since(since) >= DISCONNECT_GRACE,
+            LinkState::Connecting { since } | LinkState::Restarting { since } => {
+                now.duration_since(since) >= RECOVERY_TIMEOUT
+            }
+        };
+        if !due {
+            return None;
+        }
+
+        self.lost = true;
+        if self.restarts < MAX_ICE_RESTARTS {
+            self.restarts += 1;
+            self.state = LinkState::Restarting { since: now };
+            Some(Recovery::RestartIce)
+        } else if self.rebuilds < MAX_REBUILDS {
+            self.rebuilds += 1;
+            self.restarts = 0;
+            self.state = LinkState::Connecting { since: now };
+            Some(Recovery::Rebuild)
+        } else {
+            self.state = LinkState::GaveUp;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connected_link(now: Instant) -> PeerLink {
+        let mut link = PeerLink::new(now);
+        link.ice_state_changed(IceState::Connected, now);
+        link
+    }
+
+    #[test]
+    fn short_disconnects_heal_by_themselves() {
+        let start = Instant::now();
+        let mut link = connected_link(start);
+        link.ice_state_changed(IceState::Disconnected, start);
+
+        assert_eq!(link.update(start + DISCONNECT_GRACE / 2), None);
+        assert!(!link.is_lost());
+        link.ice_state_changed(IceState::Connected, start + DISCONNECT_GRACE / 2);
+        assert_eq!(link.update(start + DISCONNECT_GRACE * 2), None);
+        assert!(!link.is_lost());
+    }
+
+    #[test]
+    fn long_disconnects_restart_ice() {
+        let start = Instant::now();
+        let mut link = connected_link(start);
+        link.ice_state_changed(IceState::Disconnected, start);
+
+        assert_eq!(
+            link.update(start + DISCONNECT_GRACE),
+            Some(Recovery::RestartIce)
+        );
+        assert!(link.is_lost());
+        // The restart gets its time before anything else is tried
+        assert_eq!(link.update(start + DISCONNECT_GRACE), None);
+
+        link.ice_state_changed(IceState::Connected, start + DISCONNECT_GRACE * 2);
+        assert!(!link.is_lost());
+    }
+
+    #[test]
+    fn failures_restart_ice_right_away() {
+        let start = Instant::now();
+        let mut link = connected_link(start);
+        link.ice_state_changed(IceState::Failed, start);
+        assert_eq!(link.update(start), Some(Recovery::RestartIce));
+    }
+
+    #[test]
+    fn restarts_then_rebuilds_then_gives_up() {
+        let start = Instant::now();
+        let mut link = connected_link(start);
+        link.ice_state_changed(IceState::Failed, start);
+
+        let mut now = start;
+        let mut steps = Vec::new();
+        while let Some(step) = link.update(now) {
+            steps.push(step);
+            assert!(!link.gave_up());
+            now += RECOVERY_TIMEOUT;
+        }
+
+        let round = [Recovery::RestartIce; MAX_ICE_RESTARTS as usize];
+        let mut expected = Vec::new();
+        for _ in 0..MAX_REBUILDS {
+            expected.extend(round);
+            expected.push(Recovery::Rebuild);
+        }
+        expected.extend(round);
+        assert_eq!(steps, expected);
+        assert!(link.gave_up());
+        assert!(link.is_lost());
+        assert_eq!(link.update(now + RECOVERY_TIMEOUT), None);
+
+        // Failures after giving up don't start over
+        link.ice_state_changed(IceState::Failed, now);
+        assert_eq!(link.update(now), None);
+    }
+
+    #[test]
+    fn connecting_again_resets_the_attempts() {
+        let start = Instant::now();
+        let mut link = connected_link(start);
+        link.ice_state_changed(IceState::Failed, start);
+        for _ in 0..MAX_ICE_RESTARTS {
+            assert_eq!(link.update(start), Some(Recovery::RestartIce));
+            link.ice_state_changed(IceState::Failed, start);
+        }
+
+        link.ice_state_changed(IceState::Connected, start);
+        link.ice_state_changed(IceState::Failed, start);
+        assert_eq!(link.update(start), Some(Recovery::RestartIce));
+    }
+
+    #[test]
+    fn new_connections_get_time_to_connect() {
+        let start = Instant::now();
+        let mut link = PeerLink::new(start);
+        // Disconnects before the first connection mean nothing
+        link.ice_state_changed(IceState::Disconnected, start);
+        assert_eq!(link.update(start + DISCONNECT_GRACE), None);
+        assert_eq!(
+            link.update(start + RECOVERY_TIMEOUT),
+            Some(Recovery::RestartIce)
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! Simulated network conditions, for reproducing netcode bugs on a good
+//! connection.
+//!
+//! A `NetworkSimulator` sits between the session and its peers: everything
+//! the session broadcasts and every peer message it receives is delayed,
+//! jittered, reordered or dropped according to the conditions for its
+//! channel. Both directions get the full conditions, so a 100 ms delay adds
+//! 200 ms to a round trip between two simulating clients, and 100 ms against
+//! one that isn't.
+//!
+//! The conditions apply to the events channel as given, even though WebRTC
+//! delivers it reliably and in order; drops or reordering there show what
+//! the game would make of that guarantee breaking.
+
+use std::fmt;
+use std::str::FromStr;
+
+use protocol::ChannelKind;
+use web_time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::PeerId;
+use crate::transport::Payload;
+
+/// How much longer than its normal delay a reordered message is held, so
+/// that messages sent after it overtake it.
+const REORDER_HOLD: Duration = Duration::from_millis(50);
+
+/// Longest delay or jitter the parser accepts, in milliseconds.
+const MAX_DELAY_MS: f64 = 60_000.0;
+
+/// Conditions on one channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinkConditions {
+    /// Delay every message gets, in milliseconds.
+    pub delay_ms: f64,
+    /// Up to this many milliseconds of random extra delay.
+    pub jitter_ms: f64,
+    /// Share of messages (0 to 1) held back for others to overtake.
+    pub reorder: f64,
+    /// Share of messages (0 to 1) lost.
+    pub drop: f64,
+}
+
+impl LinkConditions {
+    /// Whether these conditions leave messages alone.
+    pub fn is_perfect(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for LinkConditions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_perfect() {
+            return write!(f, "off");
+        }
+        write!(f, "{:.0}±{:.0}ms", self.delay_ms, self.jitter_ms)?;
+        if self.drop > 0.0 {
+            write!(f, ", drop {:.0}%", self.drop * 100.0)?;
+        }
+        if self.reorder > 0.0 {
+            write!(f, ", reorder {:.0}%", self.reorder * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Conditions for each channel, and the seed that makes a run repeatable.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NetworkConditions {
+    pub state: LinkConditions,
+    pub events: LinkConditions,
+    /// Seed for the random delays and drops; `None` picks one at random.
+    pub seed: Option<u64>,
+}
+
+impl NetworkConditions {
+    pub fn channel(&self, channel: ChannelKind) -> &LinkConditions {
+        match channel {
+            ChannelKind::State => &self.state,
+            ChannelKind::Events => &self.events,
+        }
+    }
+}
+
+/// Parses comma-separated `key:value` settings, such as
+/// `delay:120,jitter:40,state.drop:10`.
+///
+/// Keys are `delay` and `jitter` (milliseconds, up to a minute), `drop`
+/// and `reorder` (percent), each for both channels or prefixed with
+/// `state.` or `events.` for one, and `seed`.
+impl FromStr for NetworkConditions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut conditions = Self::default();
+        for setting in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = setting
+                .split_once(':')
+                .ok_or_else(|| format!("Expected key:value, got '{}'", setting))?;
+            if key == "seed" {
+                let seed = value
+                    .parse()
+                    .map_err(|_| format!("Invalid seed '{}'", value))?;
+                conditions.seed = Some(seed);
+                continue;
+            }
+
+            let value: f64 = value
+                .parse()
+                .ok()
+                .filter(|v: &f64| v.is_finite() && *v >= 0.0)
+                .ok_or_else(|| format!("Invalid value for {}: '{}'", key, value))?;
+            let (links, name) = match key.split_once('.') {
+                Some(("state", name)) => (vec![&mut conditions.state], name),
+                Some(("events", name)) => (vec![&mut conditions.events], name),
+                Some((channel, _)) => return Err(format!("Unknown channel '{}'", channel)),
+                None => (vec![&mut conditions.state, &mut conditions.events], key),
+            };
+            for link in links {
+                match name {
+                    "delay" | "jitter" if value > MAX_DELAY_MS => {
+                        return Err(format!(
+                            "{} must be at most {} ms, got {}",
+                            key, MAX_DELAY_MS, value
+                        ));
+                    }
+                    "delay" => link.delay_ms = value,
+                    "jitter" => link.jitter_ms = value,
+                    "drop" => link.drop = (value / 100.0).min(1.0),
+                    "reorder" => link.reorder = (value / 100.0).min(1.0),
+                    _ => return Err(format!("Unknown setting '{}'", name)),
+                }
+            }
+        }
+        Ok(conditions)
+    }
+}
+
+/// Messages waiting out their simulated delay.
+struct DelayQueue<T> {
+    pending: Vec<(Instant, u64, T)>,
+    /// Keeps messages due at the same instant in sending order.
+    next_order: u64,
+}
+
+impl<T> DelayQueue<T> {
+    fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            next_order: 0,
+        }
+    }
+
+    fn push(&mut self, due: Instant, item: T) {
+        self.pending.push((due, self.next_order, item));
+        self.next_order += 1;
+    }
+
+    /// Take the messages due by `now`, in the order they arrive.
+    fn take_due(&mut self, now: Instant) -> Vec<T> {
+        let (mut due, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(at, _, _)| *at <= now);
+        self.pending = pending;
+        due.sort_by_key(|(at, order, _)| (*at, *order));
+        due.into_iter().map(|(_, _, item)| item).collect()
+    }
+}
+
+/// Applies `NetworkConditions` to a session's peer messages.
+pub struct NetworkSimulator {
+    conditions: NetworkConditions,
+    seed: u64,
+    /// xorshift64* state.
+    rng: u64,
+    outgoing: DelayQueue<(ChannelKind, Payload)>,
+    incoming: DelayQueue<(PeerId, ChannelKind, Payload)>,
+    /// Messages lost so far, state channel first.
+    dropped: [u64; 2],
+}
+
+impl NetworkSimulator {
+    pub fn new(conditions: NetworkConditions) -> Self {
+        let seed = conditions.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |since| since.as_nanos() as u64)
+        });
+        Self {
+            conditions,
+            seed,
+            // xorshift gets stuck on zero
+            rng: seed.max(1),
+            outgoing: DelayQueue::new(),
+            incoming: DelayQueue::new(),
+            dropped: [0; 2],
+        }
+    }
+
+    pub fn conditions(&self) -> &NetworkConditions {
+        &self.conditions
+    }
+
+    /// The seed in use; pass it back in `NetworkConditions::seed` to repeat
+    /// the same run of delays and drops.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Messages lost on a channel so far, in both directions.
+    pub fn dropped(&self, channel: ChannelKind) -> u64 {
+        self.dropped[channel_index(channel)]
+    }
+
+    /// Hold back a message we're sending.
+    pub(crate) fn send(&mut self, channel: ChannelKind, data: &Payload, now: Instant) {
+        if let Some(due) = self.schedule(channel, now) {
+            self.outgoing.push(due, (channel, data.clone()));
+        }
+    }
+
+    /// Hold back a message we received.
+    pub(crate) fn receive(
+        &mut self,
+        from: PeerId,
+        channel: ChannelKind,
+        data: Payload,
+        now: Instant,
+    ) {
+        if let Some(due) = self.schedule(channel, now) {
+            self.incoming.push(due, (from, channel, data));
+        }
+    }
+
+    /// Messages to send now.
+    pub(crate) fn due_outgoing(&mut self, now: Instant) -> Vec<(ChannelKind, Payload)> {
+        self.outgoing.take_due(now)
+    }
+
+    /// Received messages to hand over now.
+    pub(crate) fn due_incoming(&mut self, now: Instant) -> Vec<(PeerId, ChannelKind, Payload)> {
+        self.incoming.take_due(now)
+    }
+
+    /// Drop messages still on their way from a peer that left.
+    pub(crate) fn forget_peer(&mut self, peer_id: PeerId) {
+        self.incoming
+            .pending
+            .retain(|(_, _, (from, _, _))| *from != peer_id);
+    }
+
+    /// When a message on `channel` sent at `now` gets through, if it does.
+    fn schedule(&mut self, channel: ChannelKind, now: Instant) -> Option<Instant> {
+        let link = *self.conditions.channel(channel);
+        if self.chance(link.drop) {
+            self.dropped[channel_index(channel)] += 1;
+            return None;
+        }
+        let mut delay =
+            Duration::from_secs_f64((link.delay_ms + link.jitter_ms * self.next_unit()) / 1000.0);
+        if self.chance(link.reorder) {
+            delay += REORDER_HOLD;
+        }
+        Some(now + delay)
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.next_unit() < probability
+    }
+
+    /// A random number in [0, 1).
+    fn next_unit(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        bits as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn channel_index(channel: ChannelKind) -> usize {
+    match channel {
+        ChannelKind::State => 0,
+        ChannelKind::Events => 1,
+    }
+}
@@ -0,0 +1,95 @@
+//! Loss and jitter of the state packets from one peer.
+
+/// Sequence numbers wrap around after this many packets.
+const SEQ_WRAP: i64 = 1 << 16;
+
+/// How well state packets from a peer arrive.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReceptionQuality {
+    /// Share of packets (0 to 1) lost; `None` if none were due.
+    pub loss: Option<f64>,
+    /// Smoothed variation in transit time, in milliseconds.
+    pub jitter_ms: Option<f64>,
+}
+
+/// Follows the state packets from one peer, working out loss from their
+/// sequence numbers and jitter from their timestamps the way RTP receiver
+/// reports do (RFC 3550).
+pub struct StateReception {
+    /// Only sequence numbers that are a multiple of this are due.
+    stride: u16,
+    /// Highest sequence number so far, counting on past wraps.
+    highest: Option<i64>,
+    /// `highest` as of the last report.
+    reported: i64,
+    /// Due packets received since the last report, late ones included.
+    received: i64,
+    /// Arrival minus send time of the last packet, in milliseconds.
+    last_transit: Option<f64>,
+    jitter_ms: f64,
+}
+
+impl StateReception {
+    /// Follow a peer whose packets the server may thin out to every
+    /// `stride`-th one (`Session::state_stride`). Only those count toward
+    /// loss, so packets held back on purpose don't.
+    pub fn new(stride: u16) -> Self {
+        Self {
+            stride: stride.max(1),
+            highest: None,
+            reported: 0,
+            received: 0,
+            last_transit: None,
+            jitter_ms: 0.0,
+        }
+    }
+
+    /// Note packet `seq`, stamped `sent_at_ms` by the sender and arriving
+    /// at `arrived_at_ms`, both on the server clock.
+    pub fn record(&mut self, seq: u16, sent_at_ms: f64, arrived_at_ms: f64) {
+        let due = seq.is_multiple_of(self.stride);
+        let seq = match self.highest {
+            // Count the first packet as due
+            None => {
+                self.reported = seq as i64 - 1;
+                seq as i64
+            }
+            Some(highest) => highest + seq.wrapping_sub(highest as u16) as i16 as i64,
+        };
+        self.highest = Some(self.highest.map_or(seq, |highest| highest.max(seq)));
+        if due {
+            self.received += 1;
+        }
+
+        // Clock offsets cancel out in the difference between two transits
+        let transit = arrived_at_ms - sent_at_ms;
+        if let Some(last) = self.last_transit {
+            self.jitter_ms += ((transit - last).abs() - self.jitter_ms) / 16.0;
+        }
+        self.last_transit = Some(transit);
+    }
+
+    /// Loss since the last report and the current jitter.
+    pub fn report(&mut self) -> ReceptionQuality {
+        let Some(highest) = self.highest else {
+            return ReceptionQuality::default();
+        };
+        let expected = self.due_through(highest) - self.due_through(self.reported);
+        let loss =
+            (expected > 0).then(|| (expected - self.received).max(0) as f64 / expected as f64);
+        self.reported = highest;
+        self.received = 0;
+        ReceptionQuality {
+            loss,
+            jitter_ms: Some(self.jitter_ms),
+        }
+    }
+
+    /// How many sequence numbers up to `seq` (counting on past wraps) are
+    /// due. Only differences between two of these mean anything.
+    fn due_through(&self, seq: i64) -> i64 {
+        let stride = self.stride as i64;
+        let per_wrap = (SEQ_WRAP + stride - 1) / stride;
+        seq.div_euclid(SEQ_WRAP) * per_wrap + seq.rem_euclid(SEQ_WRAP) / stride
+    }
+}
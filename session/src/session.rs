@@ -0,0 +1,909 @@
+//! Session manager for coordinating peer connections.
+//!
+//! This layer orchestrates:
+//! - Managing the collection of peer connections
+//! - Processing signaling messages and establishing connections
+//! - Routing messages to/from peers
+//!
+//! Peers whose direct connection failed are reached through the signaling
+//! server instead; callers see the same `PeerMessage` events either way.
+//! Meanwhile the connection is restarted, and rebuilt if that doesn't help
+//! (see `link`).
+//!
+//! For debugging, peer messages can be put through a `NetworkSimulator` in
+//! both directions (see `netsim`).
+//!
+//! In a `Topology::Star` room there are no peer connections at all: what we
+//! broadcast goes to the server once, and it passes it on to everyone else as
+//! relayed messages.
+//!
+//! Either peer may offer, so offers can collide. Roles for resolving that
+//! (see `transport`) follow peer IDs: the higher ID is polite and gives way.
+//! Rebuilding a connection is left to the impolite side alone.
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use protocol::{
+    ChannelKind, ClientMessage, GamePhase, GameRules, KillRejectReason, MovementViolation,
+    ServerMessage, Topology,
+};
+use std::collections::{HashMap, HashSet};
+use web_time::Instant;
+
+use crate::link::{PeerLink, Recovery};
+use crate::netsim::{NetworkConditions, NetworkSimulator};
+use crate::queue::EventQueue;
+use crate::signaling::{Signaling, SignalingEvent};
+use crate::transport::{
+    IceCandidateData, IceState, Payload, PeerConnector, PeerEvent, PeerTransport,
+};
+use crate::{NetLogLevel, PeerId};
+
+/// Events emitted by the session manager.
+#[derive(Debug, Clone)]
+pub enum SessionEvent {
+    /// We connected to the signaling server and received our ID.
+    Connected {
+        local_id: PeerId,
+        phase: GamePhase,
+        phase_time_remaining: f32,
+        rules: GameRules,
+    },
+    /// We got our old slot back after the signaling connection dropped.
+    ///
+    /// `peers` is the full current peer list; connections to them are
+    /// re-established without further `PeerJoined` events.
+    Reconnected {
+        phase: GamePhase,
+        phase_time_remaining: f32,
+        alive: bool,
+        kills: u32,
+        peers: Vec<PeerId>,
+    },
+    /// A peer joined the session.
+    PeerJoined { peer_id: PeerId },
+    /// A peer left the session.
+    PeerLeft { peer_id: PeerId },
+    /// Nothing gets through to a peer any more, directly or relayed.
+    PeerConnectionLost { peer_id: PeerId },
+    /// A peer we had lost is reachable again.
+    PeerConnectionRestored { peer_id: PeerId },
+    /// Game phase changed.
+    PhaseChanged {
+        phase: GamePhase,
+        time_remaining: f32,
+        /// The round ended in a draw.
+        draw: bool,
+    },
+    /// The server confirmed a kill.
+    PlayerKilled {
+        killer_id: PeerId,
+        victim_id: PeerId,
+    },
+    /// The server rejected one of our kill claims.
+    KillRejected {
+        victim_id: PeerId,
+        reason: KillRejectReason,
+    },
+    /// The server operators sent a notice.
+    Notice { message: String },
+    /// The server removed us from the game.
+    Kicked { reason: String },
+    /// Received a message from a peer.
+    PeerMessage {
+        from: PeerId,
+        channel: ChannelKind,
+        data: Payload,
+    },
+}
+
+/// What to do with a connection once the connector has created it.
+enum Setup {
+    /// Create the data channels and send our offer.
+    Offer,
+    /// Answer the offer the peer already sent.
+    Answer(String),
+    /// Wait for the peer's offer.
+    AwaitOffer,
+}
+
+/// Manages all peer connections and signaling.
+pub struct Session<S: Signaling, C: PeerConnector> {
+    local_id: Option<PeerId>,
+    /// How to reach peers, as the server told us when we joined.
+    topology: Topology,
+    /// Every how many state packets the server passes on from distant
+    /// players, as it told us when we joined.
+    state_stride: u16,
+    signaling: S,
+    connector: C,
+    peers: HashMap<PeerId, C::Peer>,
+    /// Connections the connector is still creating.
+    creating: HashMap<PeerId, Setup>,
+    /// Remote candidates for connections still being created.
+    pending_ice: HashMap<PeerId, Vec<IceCandidateData>>,
+    /// Peers we talk to through the server because the direct connection
+    /// failed.
+    relayed: HashSet<PeerId>,
+    /// Connection health, for every peer in `peers`.
+    links: HashMap<PeerId, PeerLink>,
+    /// Peers reported with `PeerConnectionLost` and not yet restored.
+    lost: HashSet<PeerId>,
+    events: EventQueue<SessionEvent>,
+    /// Where status lines for players go.
+    net_log: fn(NetLogLevel, &str),
+    /// Degrades peer messages when simulating a bad network.
+    netsim: Option<NetworkSimulator>,
+}
+
+impl<S: Signaling, C: PeerConnector> Session<S, C> {
+    /// Create a new session on a signaling connection; the connector makes
+    /// the peer connections.
+    pub fn new(signaling: S, connector: C) -> Self {
+        Self {
+            local_id: None,
+            topology: Topology::Mesh,
+            state_stride: 1,
+            signaling,
+            connector,
+            peers: HashMap::new(),
+            creating: HashMap::new(),
+            pending_ice: HashMap::new(),
+            relayed: HashSet::new(),
+            links: HashMap::new(),
+            lost: HashSet::new(),
+            events: EventQueue::new(),
+            net_log: log_status,
+            netsim: None,
+        }
+    }
+
+    /// Show status lines for players with `net_log` instead of only logging
+    /// them.
+    pub fn with_net_log(mut self, net_log: fn(NetLogLevel, &str)) -> Self {
+        self.net_log = net_log;
+        self
+    }
+
+    /// Put peer messages through a network simulator with `conditions`.
+    pub fn with_network_conditions(mut self, conditions: NetworkConditions) -> Self {
+        let netsim = NetworkSimulator::new(conditions);
+        log::info!(
+            "Simulating network conditions (seed {}): state {}, events {}",
+            netsim.seed(),
+            conditions.state,
+            conditions.events
+        );
+        self.netsim = Some(netsim);
+        self
+    }
+
+    /// The network simulator, if conditions are being simulated.
+    pub fn network_simulator(&self) -> Option<&NetworkSimulator> {
+        self.netsim.as_ref()
+    }
+
+    /// Get our local peer ID (None if not yet connected).
+    pub fn local_id(&self) -> Option<PeerId> {
+        self.local_id
+    }
+
+    /// How game traffic gets to peers.
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Every how many state packets from distant players reach us; see
+    /// `StateReception::new`.
+    pub fn state_stride(&self) -> u16 {
+        self.state_stride
+    }
+
+    /// Check if we're connected to the signaling server.
+    pub fn is_connected(&self) -> bool {
+        self.local_id.is_some()
+    }
+
+    /// Poll for session events. Call this each frame.
+    pub fn poll(&mut self) -> Vec<SessionEvent> {
+        self.adopt_created_peers();
+
+        // Process signaling events
+        for event in self.signaling.poll_events() {
+            self.handle_signaling_event(event);
+        }
+
+        // Collect peer events first (to avoid borrow issues)
+        let mut all_events: Vec<(PeerId, PeerEvent)> = Vec::new();
+        for (&peer_id, peer) in &self.peers {
+            for event in peer.poll_events() {
+                all_events.push((peer_id, event));
+            }
+        }
+
+        // Process collected events
+        for (peer_id, event) in all_events {
+            self.handle_peer_event(peer_id, event);
+        }
+
+        self.flush_simulated();
+        self.update_links();
+
+        self.events.drain()
+    }
+
+    /// Handle a signaling server event.
+    fn handle_signaling_event(&mut self, event: SignalingEvent) {
+        match event {
+            SignalingEvent::Connected => {
+                // The signaling connection sends the join itself
+            }
+            SignalingEvent::Disconnected | SignalingEvent::Error => {
+                // The signaling connection reconnects and resumes our slot on
+                // its own; peer connections keep running until the server
+                // says otherwise
+            }
+            SignalingEvent::Message(msg) => {
+                self.handle_signal_message(msg);
+            }
+        }
+    }
+
+    /// Handle a message from the signaling server.
+    fn handle_signal_message(&mut self, msg: ServerMessage) {
+        let net_log = self.net_log;
+        match msg {
+            ServerMessage::Welcome {
+                client_id,
+                room,
+                peers,
+                game_phase,
+                phase_ends_at,
+                rules,
+                resumed,
+                alive,
+                kills,
+                topology,
+                state_stride,
+                ..
+            } => {
+                let phase_time_remaining = self.time_until(phase_ends_at);
+                log::info!(
+                    "Welcome! I am client {} in room '{}', {} peers in game, phase: {:?}, resumed: {}, topology: {:?}",
+                    client_id,
+                    room,
+                    peers.len(),
+                    game_phase,
+                    resumed,
+                    topology
+                );
+
+                // Connections from before a drop are stale either way: peers
+                // of a resumed slot expect fresh offers, and after a fresh
+                // join everyone sees us as a new client
+                self.close_all_peers();
+                if topology != self.topology && topology == Topology::Star {
+                    net_log(NetLogLevel::Info, "Game traffic goes through the server");
+                }
+                self.topology = topology;
+                self.state_stride = state_stride.max(1);
+
+                if resumed {
+                    net_log(NetLogLevel::Success, &format!("Rejoined room '{}'", room));
+                    let peers: Vec<PeerId> = peers.iter().map(|p| p.id).collect();
+                    for &peer_id in &peers {
+                        self.connect_to_peer(peer_id);
+                    }
+                    self.events.push(SessionEvent::Reconnected {
+                        phase: game_phase,
+                        phase_time_remaining,
+                        alive,
+                        kills,
+                        peers,
+                    });
+                    return;
+                }
+
+                net_log(NetLogLevel::Info, &format!("Joined room '{}'", room));
+
+                self.local_id = Some(client_id);
+                self.events.push(SessionEvent::Connected {
+                    local_id: client_id,
+                    phase: game_phase,
+                    phase_time_remaining,
+                    rules,
+                });
+
+                // Initiate connections to existing peers
+                for peer_info in peers {
+                    self.initiate_connection(peer_info.id);
+                }
+            }
+            ServerMessage::PeerJoined { peer_id } => {
+                log::info!("Peer {} joined", peer_id);
+                net_log(NetLogLevel::Info, &format!("Peer {}: Joined", peer_id));
+
+                // Create peer connection (we'll wait for their offer)
+                if self.topology == Topology::Mesh {
+                    self.create_peer_responder(peer_id);
+                }
+                self.events.push(SessionEvent::PeerJoined { peer_id });
+            }
+            ServerMessage::PeerReconnected { peer_id } => {
+                log::info!("Peer {} reconnected", peer_id);
+                net_log(NetLogLevel::Info, &format!("Peer {}: Reconnected", peer_id));
+
+                // They will send a fresh offer; the game keeps its player state
+                self.forget_peer(peer_id);
+                if self.topology == Topology::Mesh {
+                    self.create_peer_responder(peer_id);
+                }
+            }
+            ServerMessage::PeerLeft { peer_id } => {
+                log::info!("Peer {} left", peer_id);
+                net_log(NetLogLevel::Warning, &format!("Peer {}: Left", peer_id));
+
+                self.forget_peer(peer_id);
+                self.events.push(SessionEvent::PeerLeft { peer_id });
+            }
+            ServerMessage::GamePhase {
+                phase,
+                ends_at,
+                draw,
+            } => {
+                let time_remaining = self.time_until(ends_at);
+                log::info!(
+                    "Game phase changed to {:?}, time: {}, draw: {}",
+                    phase,
+                    time_remaining,
+                    draw
+                );
+                self.events.push(SessionEvent::PhaseChanged {
+                    phase,
+                    time_remaining,
+                    draw,
+                });
+            }
+            ServerMessage::PlayerKilled {
+                killer_id,
+                victim_id,
+            } => {
+                self.events.push(SessionEvent::PlayerKilled {
+                    killer_id,
+                    victim_id,
+                });
+            }
+            ServerMessage::KillRejected { victim_id, reason } => {
+                net_log(
+                    NetLogLevel::Warning,
+                    &format!("Server rejected kill of peer {} ({:?})", victim_id, reason),
+                );
+                self.events
+                    .push(SessionEvent::KillRejected { victim_id, reason });
+            }
+            ServerMessage::Notice { message } => {
+                log::info!("Server notice: {}", message);
+                net_log(NetLogLevel::Warning, &format!("Server: {}", message));
+                self.events.push(SessionEvent::Notice { message });
+            }
+            ServerMessage::Kicked { reason } => {
+                log::warn!("Kicked by server: {}", reason);
+                net_log(NetLogLevel::Error, &format!("Kicked: {}", reason));
+                self.close_all_peers();
+                self.events.push(SessionEvent::Kicked { reason });
+            }
+            // Handled by the signaling connection's clock sync
+            ServerMessage::Pong { .. } => {}
+            ServerMessage::Offer {
+                from_id,
+                sdp,
+                ice_restart,
+            } => {
+                log::info!(
+                    "Received offer from peer {} (ICE restart: {})",
+                    from_id,
+                    ice_restart
+                );
+                self.handle_offer(from_id, sdp, ice_restart);
+            }
+            ServerMessage::Answer { from_id, sdp } => {
+                log::info!("Received answer from peer {}", from_id);
+                self.handle_answer(from_id, sdp);
+            }
+            ServerMessage::IceCandidate {
+                from_id,
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            } => {
+                log::info!(
+                    "Received ICE candidate from peer {}: {}",
+                    from_id,
+                    candidate
+                );
+                self.handle_ice_candidate(from_id, candidate, sdp_mid, sdp_m_line_index);
+            }
+            ServerMessage::Relay {
+                from_id,
+                channel,
+                data,
+                binary,
+            } => {
+                // They can't reach us directly, so answer the same way
+                if self.topology == Topology::Mesh && self.relayed.insert(from_id) {
+                    net_log(
+                        NetLogLevel::Warning,
+                        &format!("Peer {}: Using server relay", from_id),
+                    );
+                }
+                let data = if binary {
+                    match BASE64_STANDARD.decode(&data) {
+                        Ok(bytes) => Payload::Binary(bytes),
+                        Err(e) => {
+                            log::warn!("Bad binary relay from peer {}: {}", from_id, e);
+                            return;
+                        }
+                    }
+                } else {
+                    Payload::Text(data)
+                };
+                self.deliver(from_id, channel, data);
+            }
+        }
+    }
+
+    /// Close every peer connection, including ones still being set up.
+    fn close_all_peers(&mut self) {
+        for (_, peer) in self.peers.drain() {
+            peer.close();
+        }
+        // Connections still being created are closed when they arrive
+        self.creating.clear();
+        self.pending_ice.clear();
+        self.relayed.clear();
+        self.links.clear();
+        self.lost.clear();
+    }
+
+    /// Close and forget the connection to a peer.
+    fn forget_peer(&mut self, peer_id: PeerId) {
+        if let Some(peer) = self.peers.remove(&peer_id) {
+            peer.close();
+        }
+        self.creating.remove(&peer_id);
+        self.pending_ice.remove(&peer_id);
+        self.relayed.remove(&peer_id);
+        self.links.remove(&peer_id);
+        self.lost.remove(&peer_id);
+        if let Some(netsim) = &mut self.netsim {
+            netsim.forget_peer(peer_id);
+        }
+    }
+
+    /// Initiate a connection to an existing peer (we create offer).
+    fn initiate_connection(&mut self, peer_id: PeerId) {
+        if self.topology == Topology::Mesh {
+            self.connect_to_peer(peer_id);
+        }
+        self.events.push(SessionEvent::PeerJoined { peer_id });
+    }
+
+    /// Whether we give way to a peer when our offers collide.
+    fn is_polite(&self, peer_id: PeerId) -> bool {
+        self.local_id.is_some_and(|local_id| local_id > peer_id)
+    }
+
+    /// Create a peer connection and send it our offer.
+    fn connect_to_peer(&mut self, peer_id: PeerId) {
+        self.create_peer(peer_id, Setup::Offer);
+    }
+
+    /// Create a peer connection where we're the responder (waiting for offer).
+    fn create_peer_responder(&mut self, peer_id: PeerId) {
+        self.create_peer(peer_id, Setup::AwaitOffer);
+    }
+
+    /// Have the connector create a connection to a peer, then `setup` it.
+    /// If one is already being created, it gets the new setup instead.
+    fn create_peer(&mut self, peer_id: PeerId, setup: Setup) {
+        if self.creating.insert(peer_id, setup).is_none() {
+            let polite = self.is_polite(peer_id);
+            self.connector.connect(peer_id, polite);
+        }
+    }
+
+    /// Take over the connections the connector finished creating.
+    fn adopt_created_peers(&mut self) {
+        for (peer_id, mut peer) in self.connector.poll_created() {
+            let Some(setup) = self.creating.remove(&peer_id) else {
+                // The peer left, or we rejoined, while it was being created
+                log::info!("Dropping stale connection to peer {}", peer_id);
+                peer.close();
+                continue;
+            };
+            match setup {
+                Setup::Offer => {
+                    peer.create_data_channels();
+                    peer.create_offer();
+                }
+                // The answer comes back as a `PeerEvent::LocalAnswer`
+                Setup::Answer(sdp) => {
+                    peer.accept_offer(&sdp);
+                }
+                Setup::AwaitOffer => {}
+            }
+
+            // Apply any pending ICE candidates
+            for ice in self.pending_ice.remove(&peer_id).unwrap_or_default() {
+                peer.add_ice_candidate(ice);
+            }
+
+            if let Some(replaced) = self.peers.insert(peer_id, peer) {
+                replaced.close();
+            }
+            self.links
+                .entry(peer_id)
+                .or_insert_with(|| PeerLink::new(Instant::now()));
+        }
+    }
+
+    /// Handle an offer from a remote peer.
+    fn handle_offer(&mut self, from_id: PeerId, sdp: String, ice_restart: bool) {
+        // Connections that finished setting up negotiate in place
+        self.adopt_created_peers();
+
+        match self.peers.get(&from_id) {
+            // A renegotiation, the offer a waiting responder expects, or one
+            // that crossed our own; the answer comes back as a
+            // `PeerEvent::LocalAnswer`
+            Some(peer) if ice_restart || !peer.has_remote_description() => {
+                peer.accept_offer(&sdp);
+                return;
+            }
+            Some(_) => {
+                log::info!("Peer {} rebuilt our connection", from_id);
+                if let Some(stale) = self.peers.remove(&from_id) {
+                    stale.close();
+                }
+            }
+            None if ice_restart => {
+                // Without a connection to restart they will rebuild it
+                log::warn!("ICE restart from peer {} we have no connection to", from_id);
+                return;
+            }
+            None => {}
+        }
+
+        // A connection still being created answers instead of offering
+        self.create_peer(from_id, Setup::Answer(sdp));
+    }
+
+    /// Handle an answer from a remote peer.
+    fn handle_answer(&mut self, from_id: PeerId, sdp: String) {
+        if let Some(peer) = self.peers.get(&from_id) {
+            peer.accept_answer(&sdp);
+        } else {
+            log::warn!("Received answer from unknown peer {}", from_id);
+        }
+    }
+
+    /// Handle an ICE candidate from a remote peer.
+    fn handle_ice_candidate(
+        &mut self,
+        from_id: PeerId,
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    ) {
+        let ice_data = IceCandidateData {
+            candidate,
+            sdp_mid,
+            sdp_m_line_index,
+        };
+
+        if let Some(peer) = self.peers.get(&from_id) {
+            peer.add_ice_candidate(ice_data);
+        } else {
+            // Peer might still be being created
+            self.pending_ice.entry(from_id).or_default().push(ice_data);
+        }
+    }
+
+    /// Handle an event from a peer connection.
+    fn handle_peer_event(&mut self, peer_id: PeerId, event: PeerEvent) {
+        let net_log = self.net_log;
+        match event {
+            PeerEvent::ChannelOpened(_kind) => {
+                // Channel is ready
+            }
+            PeerEvent::Message { channel, data } => {
+                self.deliver(peer_id, channel, data);
+            }
+            PeerEvent::LocalIceCandidate {
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            } => {
+                // Send ICE candidate to peer via signaling
+                self.signaling.send(&ClientMessage::IceCandidate {
+                    target_id: peer_id,
+                    candidate,
+                    sdp_mid,
+                    sdp_m_line_index,
+                });
+            }
+            PeerEvent::IceStateChanged(state) => {
+                if let Some(link) = self.links.get_mut(&peer_id) {
+                    link.ice_state_changed(state, Instant::now());
+                }
+                match state {
+                    IceState::Failed if self.relayed.insert(peer_id) => {
+                        log::warn!("Relaying traffic for peer {} through the server", peer_id);
+                        net_log(
+                            NetLogLevel::Warning,
+                            &format!("Peer {}: Falling back to server relay", peer_id),
+                        );
+                    }
+                    IceState::Connected | IceState::Completed if self.relayed.remove(&peer_id) => {
+                        net_log(
+                            NetLogLevel::Success,
+                            &format!("Peer {}: Direct connection restored", peer_id),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            PeerEvent::LocalOffer { sdp, ice_restart } => {
+                self.signaling.send(&ClientMessage::Offer {
+                    target_id: peer_id,
+                    sdp,
+                    ice_restart,
+                });
+            }
+            PeerEvent::LocalAnswer(sdp) => {
+                self.signaling.send(&ClientMessage::Answer {
+                    target_id: peer_id,
+                    sdp,
+                });
+            }
+            PeerEvent::IceGatheringComplete => {
+                // All candidates gathered
+            }
+        }
+    }
+
+    /// Keep an eye on every connection's health: carry out recovery steps
+    /// that are due, and tell the game about peers that became unreachable
+    /// or came back.
+    fn update_links(&mut self) {
+        let Some(local_id) = self.local_id else {
+            return;
+        };
+        let now = Instant::now();
+        let net_log = self.net_log;
+
+        let mut due = Vec::new();
+        for (&peer_id, link) in &mut self.links {
+            let gave_up = link.gave_up();
+            let polite = local_id > peer_id;
+            match link.update(now) {
+                // Restarts from both ends are resolved like any colliding
+                // offers, but only one side may replace the connection
+                Some(Recovery::Rebuild) if polite => {}
+                Some(step) => due.push((peer_id, step)),
+                None if !gave_up && link.gave_up() => net_log(
+                    NetLogLevel::Error,
+                    &format!("Peer {}: Giving up on a direct connection", peer_id),
+                ),
+                None => {}
+            }
+        }
+        for (peer_id, step) in due {
+            self.recover(peer_id, step);
+        }
+
+        for (&peer_id, link) in &self.links {
+            let lost = link.is_lost() && !self.relayed.contains(&peer_id);
+            if lost && self.lost.insert(peer_id) {
+                net_log(
+                    NetLogLevel::Warning,
+                    &format!("Peer {}: Connection lost", peer_id),
+                );
+                self.events
+                    .push(SessionEvent::PeerConnectionLost { peer_id });
+            } else if !lost && self.lost.remove(&peer_id) {
+                self.events
+                    .push(SessionEvent::PeerConnectionRestored { peer_id });
+            }
+        }
+    }
+
+    /// Carry out a recovery step for the connection to a peer.
+    fn recover(&mut self, peer_id: PeerId, step: Recovery) {
+        match step {
+            Recovery::RestartIce => {
+                let Some(peer) = self.peers.get(&peer_id) else {
+                    return;
+                };
+                log::info!("Restarting ICE with peer {}", peer_id);
+                (self.net_log)(
+                    NetLogLevel::Info,
+                    &format!("Peer {}: Restarting ICE", peer_id),
+                );
+                peer.restart_ice();
+            }
+            Recovery::Rebuild => {
+                log::info!("Rebuilding connection to peer {}", peer_id);
+                (self.net_log)(
+                    NetLogLevel::Warning,
+                    &format!("Peer {}: Rebuilding connection", peer_id),
+                );
+                if let Some(peer) = self.peers.remove(&peer_id) {
+                    peer.close();
+                }
+                self.connect_to_peer(peer_id);
+            }
+        }
+    }
+
+    /// Hand a peer message to the caller, after the network simulator is
+    /// done with it if there is one.
+    fn deliver(&mut self, from: PeerId, channel: ChannelKind, data: Payload) {
+        match &mut self.netsim {
+            Some(netsim) => netsim.receive(from, channel, data, Instant::now()),
+            None => self.events.push(SessionEvent::PeerMessage {
+                from,
+                channel,
+                data,
+            }),
+        }
+    }
+
+    /// Send and deliver the messages the network simulator held back that
+    /// are now due.
+    fn flush_simulated(&mut self) {
+        let Some(netsim) = &mut self.netsim else {
+            return;
+        };
+        let now = Instant::now();
+        let outgoing = netsim.due_outgoing(now);
+        let incoming = netsim.due_incoming(now);
+        for (channel, data) in outgoing {
+            self.send_to_peers(channel, &data);
+        }
+        for (from, channel, data) in incoming {
+            self.events.push(SessionEvent::PeerMessage {
+                from,
+                channel,
+                data,
+            });
+        }
+    }
+
+    /// Send data to all connected peers on a channel.
+    pub fn broadcast(&mut self, channel: ChannelKind, data: &Payload) {
+        match &mut self.netsim {
+            Some(netsim) => netsim.send(channel, data, Instant::now()),
+            None => self.send_to_peers(channel, data),
+        }
+    }
+
+    fn send_to_peers(&self, channel: ChannelKind, data: &Payload) {
+        if self.topology == Topology::Star {
+            let (data, binary) = encode_payload(data);
+            self.signaling.send(&ClientMessage::Broadcast {
+                channel,
+                data,
+                binary,
+            });
+            return;
+        }
+
+        let mut sent = 0;
+        let mut skipped = Vec::new();
+
+        for (&peer_id, peer) in &self.peers {
+            if self.relayed.contains(&peer_id) {
+                continue;
+            }
+            match peer.send(channel, data) {
+                Ok(()) => sent += 1,
+                Err(reason) => skipped.push((peer_id, reason)),
+            }
+        }
+
+        for &peer_id in &self.relayed {
+            self.relay(peer_id, channel, data);
+        }
+
+        if sent > 0 || !skipped.is_empty() || !self.relayed.is_empty() {
+            log::debug!(
+                "Broadcast {:?}: sent to {} peers, relayed to {}, skipped: {:?}",
+                channel,
+                sent,
+                self.relayed.len(),
+                skipped
+            );
+        }
+    }
+
+    /// Relay a data channel payload to a peer through the server.
+    fn relay(&self, target_id: PeerId, channel: ChannelKind, data: &Payload) {
+        let (data, binary) = encode_payload(data);
+        self.signaling.send(&ClientMessage::Relay {
+            target_id,
+            channel,
+            data,
+            binary,
+        });
+    }
+
+    /// Send a pose sample to the server for kill validation.
+    pub fn send_pose(&self, position: [f32; 3], yaw: f32, pitch: f32, mask: u8) {
+        self.signaling.send(&ClientMessage::Pose {
+            position,
+            yaw,
+            pitch,
+            mask,
+        });
+    }
+
+    /// Our estimate of the server clock, in milliseconds.
+    pub fn server_time(&self) -> f64 {
+        self.signaling.server_time()
+    }
+
+    /// Seconds left until a phase deadline; 0 for phases without one.
+    fn time_until(&self, ends_at: Option<f64>) -> f32 {
+        ends_at.map_or(0.0, |at| self.signaling.seconds_until(at).max(0.0))
+    }
+
+    /// Claim a kill with the server; it answers with `PlayerKilled` or
+    /// `KillRejected`.
+    pub fn claim_kill(&self, victim_id: PeerId, killed_at: f64) {
+        self.signaling.send(&ClientMessage::KillClaim {
+            victim_id,
+            killed_at,
+        });
+    }
+
+    /// Report a peer whose movement keeps breaking the rules.
+    pub fn report_peer(&self, peer_id: PeerId, reason: MovementViolation) {
+        self.signaling
+            .send(&ClientMessage::ReportPeer { peer_id, reason });
+    }
+
+    /// Notify server that we died.
+    pub fn notify_death(&self) {
+        self.signaling.send(&ClientMessage::PlayerDied);
+    }
+
+    /// Disconnect from the session.
+    pub fn disconnect(&mut self) {
+        self.signaling.disconnect();
+        self.close_all_peers();
+    }
+
+    /// The peer connections, including ones still negotiating.
+    pub fn peers(&self) -> impl Iterator<Item = (PeerId, &C::Peer)> {
+        self.peers.iter().map(|(&id, peer)| (id, peer))
+    }
+}
+
+/// A payload as sent through the server: binary payloads travel as base64
+/// inside the JSON message.
+fn encode_payload(data: &Payload) -> (String, bool) {
+    match data {
+        Payload::Text(text) => (text.clone(), false),
+        Payload::Binary(bytes) => (BASE64_STANDARD.encode(bytes), true),
+    }
+}
+
+/// Default status sink: the log.
+fn log_status(level: NetLogLevel, msg: &str) {
+    match level {
+        NetLogLevel::Info | NetLogLevel::Success => log::info!("{}", msg),
+        NetLogLevel::Warning => log::warn!("{}", msg),
+        NetLogLevel::Error => log::error!("{}", msg),
+    }
+}
@@ -0,0 +1,43 @@
+//! What the session needs from its connection to the signaling server.
+
+use protocol::{ClientMessage, ServerMessage};
+
+/// Events from the signaling server.
+#[derive(Debug, Clone)]
+pub enum SignalingEvent {
+    /// Connection to signaling server opened.
+    Connected,
+    /// Connection to signaling server closed.
+    Disconnected,
+    /// Error on signaling connection.
+    Error,
+    /// Received a message from the signaling server.
+    Message(ServerMessage),
+}
+
+/// A connection to the signaling server.
+///
+/// Joining the room is up to the implementation, which knows the room and
+/// player name; so is getting back in after the connection drops.
+pub trait Signaling {
+    /// Events since the last call.
+    ///
+    /// Also drives the connection (reconnects, clock sync), so the session
+    /// calls this every frame.
+    fn poll_events(&mut self) -> Vec<SignalingEvent>;
+
+    /// Send a message to the server.
+    fn send(&self, msg: &ClientMessage);
+
+    /// Our estimate of the server clock, in milliseconds.
+    fn server_time(&self) -> f64;
+
+    /// Seconds from now until a server timestamp (negative once it has
+    /// passed).
+    fn seconds_until(&self, server_time: f64) -> f32 {
+        ((server_time - self.server_time()) / 1000.0) as f32
+    }
+
+    /// Leave the room and close the connection for good.
+    fn disconnect(&mut self);
+}
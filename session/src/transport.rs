@@ -0,0 +1,125 @@
+//! What the session needs from peer-to-peer connections.
+//!
+//! A transport only moves strings and byte buffers; it knows nothing about
+//! game messages. Negotiation follows WebRTC: offers, answers and ICE
+//! candidates that a connection produces come out of `poll_events` for the
+//! session to pass on through signaling, and those from the peer are handed
+//! back in.
+//!
+//! Either end of a connection may renegotiate it, so offers can cross. One
+//! peer is polite and gives way to the other's offer, the impolite one
+//! ignores the colliding offer and waits for the answer to its own (the
+//! "perfect negotiation" pattern).
+
+use protocol::ChannelKind;
+
+use crate::PeerId;
+
+/// A data channel message: JSON text, or a binary packet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Payload {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// Events from a peer connection.
+#[derive(Debug, Clone)]
+pub enum PeerEvent {
+    /// Data channel is now open and ready.
+    ChannelOpened(ChannelKind),
+    /// Received a message on a channel.
+    Message { channel: ChannelKind, data: Payload },
+    /// ICE connection state changed.
+    IceStateChanged(IceState),
+    /// Local ICE candidate generated (needs to be sent to peer via signaling).
+    LocalIceCandidate {
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+    /// ICE gathering complete.
+    IceGatheringComplete,
+    /// An offer to send to the peer (see `create_offer` and `restart_ice`).
+    LocalOffer { sdp: String, ice_restart: bool },
+    /// Our answer to an offer from the peer (see `accept_offer`).
+    LocalAnswer(String),
+}
+
+/// ICE connection state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IceState {
+    New,
+    Checking,
+    Connected,
+    Completed,
+    Failed,
+    Disconnected,
+    Closed,
+}
+
+/// A remote ICE candidate, as relayed by the signaling server.
+#[derive(Clone, Debug)]
+pub struct IceCandidateData {
+    pub candidate: String,
+    pub sdp_mid: Option<String>,
+    pub sdp_m_line_index: Option<u16>,
+}
+
+/// A connection to one peer, with an unreliable "state" channel and a
+/// reliable "events" channel.
+pub trait PeerTransport {
+    /// Create the data channels (when we're the side making the first offer;
+    /// the other side receives them).
+    fn create_data_channels(&mut self);
+
+    /// Start negotiating; the offer comes out as a `PeerEvent::LocalOffer`.
+    fn create_offer(&self);
+
+    /// Restart ICE on this connection, keeping its data channels. The new
+    /// offer comes out as a `PeerEvent::LocalOffer` with `ice_restart` set.
+    fn restart_ice(&self);
+
+    /// Apply an offer from the peer. Our answer comes out as a
+    /// `PeerEvent::LocalAnswer`.
+    ///
+    /// If the offer collides with one of ours, the impolite side ignores it
+    /// (returning false) and the polite side rolls its own offer back first.
+    fn accept_offer(&self, sdp: &str) -> bool;
+
+    /// Apply the peer's answer to our offer.
+    fn accept_answer(&self, sdp: &str);
+
+    /// Add a remote ICE candidate. Candidates may arrive before the remote
+    /// description; they are held until it has been applied.
+    fn add_ice_candidate(&self, ice: IceCandidateData);
+
+    /// Whether a remote description has been applied yet.
+    fn has_remote_description(&self) -> bool;
+
+    /// Send data on a channel.
+    fn send(&self, channel: ChannelKind, data: &Payload) -> Result<(), &'static str>;
+
+    /// Check if a channel is ready for sending.
+    fn channel_ready(&self, channel: ChannelKind) -> bool;
+
+    /// Poll for incoming events from this peer.
+    fn poll_events(&self) -> Vec<PeerEvent>;
+
+    /// Close the connection.
+    fn close(&self);
+}
+
+/// Creates peer connections.
+///
+/// Setting one up may take a while (the browser fetches TURN credentials
+/// first), so new connections are handed over through `poll_created`.
+pub trait PeerConnector {
+    type Peer: PeerTransport;
+
+    /// Start creating a connection to a peer. `polite` decides which side
+    /// gives way when offers collide; the two peers must disagree on it.
+    fn connect(&mut self, peer_id: PeerId, polite: bool);
+
+    /// Connections created since the last call.
+    fn poll_created(&mut self) -> Vec<(PeerId, Self::Peer)>;
+}
@@ -0,0 +1,104 @@
+//! Simulated network conditions.
+
+mod sim;
+
+use protocol::ChannelKind;
+use session::{LinkConditions, NetworkConditions, Payload};
+use sim::Sim;
+use std::time::{Duration, Instant};
+
+#[test]
+fn conditions_parse_per_channel() {
+    let conditions: NetworkConditions =
+        "delay:120, jitter:40,state.drop:10,events.reorder:5,seed:7"
+            .parse()
+            .unwrap();
+    assert_eq!(
+        conditions,
+        NetworkConditions {
+            state: LinkConditions {
+                delay_ms: 120.0,
+                jitter_ms: 40.0,
+                reorder: 0.0,
+                drop: 0.1,
+            },
+            events: LinkConditions {
+                delay_ms: 120.0,
+                jitter_ms: 40.0,
+                reorder: 0.05,
+                drop: 0.0,
+            },
+            seed: Some(7),
+        }
+    );
+    assert_eq!(conditions.state.to_string(), "120±40ms, drop 10%");
+
+    for bad in [
+        "delay",
+        "delay:-5",
+        "delay:1e300",
+        "events.jitter:60001",
+        "delay:soon",
+        "voice.drop:5",
+        "lag:5",
+        "seed:x",
+    ] {
+        assert!(bad.parse::<NetworkConditions>().is_err(), "{} parsed", bad);
+    }
+}
+
+#[test]
+fn dropped_channels_lose_messages_in_both_directions() {
+    let mut sim = Sim::new("sim-netsim-drop");
+    sim.join_simulating("ann", "state.drop:100".parse().unwrap());
+    sim.join("bob");
+    sim.wait_for_mesh();
+    let ids = sim.ids();
+
+    let state = Payload::Binary(vec![1]);
+    let hello = Payload::Text("hello".to_string());
+    for client in &mut sim.clients {
+        client.session.broadcast(ChannelKind::State, &state);
+        client.session.broadcast(ChannelKind::Events, &hello);
+    }
+
+    sim.run_until("events to arrive", |sim| {
+        !sim.clients[0].messages_from(ids[1]).is_empty()
+            && !sim.clients[1].messages_from(ids[0]).is_empty()
+    });
+    let events_only = vec![(ChannelKind::Events, hello)];
+    assert_eq!(sim.clients[0].messages_from(ids[1]), events_only);
+    assert_eq!(sim.clients[1].messages_from(ids[0]), events_only);
+    let netsim = sim.clients[0].session.network_simulator().unwrap();
+    assert_eq!(netsim.dropped(ChannelKind::State), 2);
+    assert_eq!(netsim.dropped(ChannelKind::Events), 0);
+}
+
+#[test]
+fn delayed_messages_arrive_late_and_in_order() {
+    let mut sim = Sim::new("sim-netsim-delay");
+    sim.join_simulating("ann", "events.delay:150".parse().unwrap());
+    sim.join("bob");
+    sim.wait_for_mesh();
+    let ids = sim.ids();
+
+    let sent_at = Instant::now();
+    let messages: Vec<_> = (0..3)
+        .map(|i| Payload::Text(format!("message {}", i)))
+        .collect();
+    for message in &messages {
+        sim.clients[0]
+            .session
+            .broadcast(ChannelKind::Events, message);
+    }
+
+    sim.run_until("the messages to arrive", |sim| {
+        sim.clients[1].messages_from(ids[0]).len() == messages.len()
+    });
+    assert!(sent_at.elapsed() >= Duration::from_millis(150));
+    let expected: Vec<_> = messages
+        .into_iter()
+        .map(|message| (ChannelKind::Events, message))
+        .collect();
+    assert_eq!(sim.clients[1].messages_from(ids[0]), expected);
+}
@@ -0,0 +1,115 @@
+//! Direct connections that drop and come back.
+
+mod sim;
+
+use protocol::ChannelKind;
+use session::{Payload, PeerTransport, SessionEvent};
+use sim::{MemoryPeer, Sim, SimClient};
+
+/// Whether `client` reported losing `peer_id`, and getting it back after.
+fn saw_loss_and_recovery(client: &SimClient, peer_id: u64) -> bool {
+    let lost = client.events.iter().position(
+        |event| matches!(event, SessionEvent::PeerConnectionLost { peer_id: id } if *id == peer_id),
+    );
+    let restored = client.events.iter().rposition(|event| {
+        matches!(event, SessionEvent::PeerConnectionRestored { peer_id: id } if *id == peer_id)
+    });
+    lost.zip(restored)
+        .is_some_and(|(lost, restored)| lost < restored)
+}
+
+/// The connection of a client in a room of two.
+fn peer(client: &SimClient) -> &MemoryPeer {
+    let (_, peer) = client.session.peers().next().expect("a connection");
+    peer
+}
+
+#[test]
+fn cut_connections_are_restored() {
+    let mut sim = Sim::full_mesh("sim-recovery", &["ann", "bob"]);
+    let ids = sim.ids();
+
+    peer(&sim.clients[0]).cut();
+    sim.run_until("both sides to lose the connection", |sim| {
+        sim.clients
+            .iter()
+            .all(|client| client.connected_peers().is_empty())
+    });
+
+    // ICE restarts after the grace period bring it back
+    sim.run_until("both sides to see the connection restored", |sim| {
+        saw_loss_and_recovery(&sim.clients[0], ids[1])
+            && saw_loss_and_recovery(&sim.clients[1], ids[0])
+    });
+    sim.wait_for_mesh();
+
+    let hello = Payload::Text("hello".to_string());
+    sim.clients[0]
+        .session
+        .broadcast(ChannelKind::Events, &hello);
+    sim.run_until("bob to hear ann again", |sim| {
+        sim.clients[1]
+            .messages_from(ids[0])
+            .contains(&(ChannelKind::Events, hello.clone()))
+    });
+}
+
+#[test]
+fn colliding_ice_restarts_are_resolved() {
+    let mut sim = Sim::full_mesh("sim-glare", &["ann", "bob"]);
+    let ids = sim.ids();
+
+    peer(&sim.clients[0]).cut();
+    let before: Vec<_> = sim
+        .clients
+        .iter()
+        .map(|client| peer(client).negotiation_counts())
+        .collect();
+    // Both sides offer before either hears from the other
+    for client in &sim.clients {
+        peer(client).restart_ice();
+    }
+    sim.run_until("both offers to be handled", |sim| {
+        let handled: usize = sim
+            .clients
+            .iter()
+            .map(|client| {
+                let (rolled_back, ignored, _) = peer(client).negotiation_counts();
+                rolled_back + ignored
+            })
+            .sum();
+        handled == before.iter().map(|(r, i, _)| r + i).sum::<usize>() + 2
+    });
+    sim.wait_for_mesh();
+
+    for (client, before) in sim.clients.iter().zip(before) {
+        let (rolled_back, ignored, answers) = peer(client).negotiation_counts();
+        let (rolled_back, ignored, answers) = (
+            rolled_back - before.0,
+            ignored - before.1,
+            answers - before.2,
+        );
+        if peer(client).is_polite() {
+            // Gives way: drops its offer and answers the other one
+            assert_eq!((rolled_back, ignored, answers), (1, 0, 1));
+        } else {
+            // Keeps its offer and waits for the answer
+            assert_eq!((rolled_back, ignored, answers), (0, 1, 0));
+        }
+    }
+    // Exactly one side was polite
+    assert_ne!(
+        peer(&sim.clients[0]).is_polite(),
+        peer(&sim.clients[1]).is_polite()
+    );
+
+    let hello = Payload::Text("hello".to_string());
+    sim.clients[1]
+        .session
+        .broadcast(ChannelKind::Events, &hello);
+    sim.run_until("ann to hear bob again", |sim| {
+        sim.clients[0]
+            .messages_from(ids[1])
+            .contains(&(ChannelKind::Events, hello.clone()))
+    });
+}
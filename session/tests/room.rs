@@ -0,0 +1,83 @@
+//! Sessions of several clients in one room, against a real server.
+
+mod sim;
+
+use protocol::ChannelKind;
+use session::{Payload, PeerTransport};
+use sim::Sim;
+
+#[test]
+fn clients_in_a_room_connect_to_each_other() {
+    let sim = Sim::full_mesh("sim-mesh", &["ann", "bob", "cat", "dan"]);
+
+    let ids = sim.ids();
+    for client in &sim.clients {
+        let others: Vec<_> = ids
+            .iter()
+            .copied()
+            .filter(|&id| id != client.id())
+            .collect();
+        assert_eq!(client.connected_peers(), others);
+        assert_eq!(client.joined_peers(), others);
+    }
+}
+
+#[test]
+fn broadcasts_reach_every_peer() {
+    let mut sim = Sim::full_mesh("sim-broadcast", &["ann", "bob", "cat"]);
+    let ids = sim.ids();
+
+    let hello = Payload::Text("hello".to_string());
+    let state = Payload::Binary(vec![1, 2, 3]);
+    sim.clients[0]
+        .session
+        .broadcast(ChannelKind::Events, &hello);
+    sim.clients[0].session.broadcast(ChannelKind::State, &state);
+    sim.clients[2]
+        .session
+        .broadcast(ChannelKind::Events, &hello);
+
+    sim.run_until("broadcasts to arrive", |sim| {
+        sim.clients[1].messages_from(ids[2]).len() == 1
+            && sim.clients[2].messages_from(ids[0]).len() == 2
+    });
+    let expected = vec![
+        (ChannelKind::Events, hello.clone()),
+        (ChannelKind::State, state),
+    ];
+    assert_eq!(sim.clients[1].messages_from(ids[0]), expected);
+    assert_eq!(sim.clients[2].messages_from(ids[0]), expected);
+    assert_eq!(
+        sim.clients[0].messages_from(ids[2]),
+        vec![(ChannelKind::Events, hello)]
+    );
+    // Nobody hears themselves
+    for (client, &id) in sim.clients.iter().zip(&ids) {
+        assert!(client.messages_from(id).is_empty());
+    }
+}
+
+#[test]
+fn leaving_clients_are_dropped_by_the_others() {
+    let mut sim = Sim::full_mesh("sim-leave", &["ann", "bob", "cat"]);
+    let ids = sim.ids();
+
+    sim.clients[2].session.disconnect();
+    assert_eq!(sim.clients[2].session.peers().count(), 0);
+
+    sim.run_until("the others to see cat leave", |sim| {
+        sim.clients[..2]
+            .iter()
+            .all(|client| client.saw_leave(ids[2]))
+    });
+    assert_eq!(sim.clients[0].connected_peers(), vec![ids[1]]);
+    assert_eq!(sim.clients[1].connected_peers(), vec![ids[0]]);
+    for client in &sim.clients[..2] {
+        assert!(
+            client
+                .session
+                .peers()
+                .all(|(id, peer)| id != ids[2] && peer.channel_ready(ChannelKind::State))
+        );
+    }
+}
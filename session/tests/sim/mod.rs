@@ -0,0 +1,224 @@
+//! Simulated clients for session tests.
+//!
+//! Each client runs a real `Session` with a WebSocket connection to a real
+//! server, which runs in-process on a background runtime. Peer connections
+//! go through an in-memory network instead of WebRTC.
+
+// Each test file uses only part of the harness
+#![allow(dead_code, unused_imports)]
+
+mod peer;
+mod signaling;
+
+use server::config::{self, Config};
+use session::{NetworkConditions, PeerId, PeerTransport, Session, SessionEvent};
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+
+pub use peer::{MemoryConnector, MemoryNetwork, MemoryPeer};
+pub use signaling::SimSignaling;
+
+/// How long `Sim::run_until` waits before failing the test.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The server all tests share. Tests keep out of each other's way by using
+/// different rooms.
+pub struct Server {
+    pub addr: SocketAddr,
+    pub runtime: Handle,
+}
+
+static SERVER: OnceLock<Server> = OnceLock::new();
+
+/// Configuration for the server, if a test file needs its own.
+static SERVER_CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Run the server with `config` instead of the defaults. Every test in a
+/// file has to pass the same configuration, before its first client joins.
+pub fn configure_server(config: Config) {
+    let _ = SERVER_CONFIG.set(config);
+}
+
+fn server() -> &'static Server {
+    SERVER.get_or_init(|| {
+        let (started, server) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().expect("test runtime");
+            runtime.block_on(async {
+                let config = config::init_with(SERVER_CONFIG.get().cloned().unwrap_or_default());
+                server::clock::start();
+                server::start_game();
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+                    .await
+                    .expect("bind test server");
+                let addr = listener.local_addr().expect("test server address");
+                let _ = started.send(Server {
+                    addr,
+                    runtime: Handle::current(),
+                });
+                axum::serve(listener, server::router(config))
+                    .await
+                    .expect("test server");
+            });
+        });
+        server.recv().expect("test server failed to start")
+    })
+}
+
+pub type SimSession = Session<SimSignaling, MemoryConnector>;
+
+pub struct SimClient {
+    pub session: SimSession,
+    /// Everything the session reported so far.
+    pub events: Vec<SessionEvent>,
+}
+
+impl SimClient {
+    pub fn id(&self) -> PeerId {
+        self.session.local_id().expect("client has not joined yet")
+    }
+
+    /// Peers with both data channels open, in ID order.
+    pub fn connected_peers(&self) -> Vec<PeerId> {
+        let mut peers: Vec<PeerId> = self
+            .session
+            .peers()
+            .filter(|(_, peer)| {
+                peer.channel_ready(protocol::ChannelKind::State)
+                    && peer.channel_ready(protocol::ChannelKind::Events)
+            })
+            .map(|(id, _)| id)
+            .collect();
+        peers.sort();
+        peers
+    }
+
+    /// Peers reported with `PeerJoined`, in ID order.
+    pub fn joined_peers(&self) -> Vec<PeerId> {
+        let mut peers: Vec<PeerId> = self
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                SessionEvent::PeerJoined { peer_id } => Some(*peer_id),
+                _ => None,
+            })
+            .collect();
+        peers.sort();
+        peers
+    }
+
+    /// Whether the session reported that `peer_id` left.
+    pub fn saw_leave(&self, peer_id: PeerId) -> bool {
+        self.events
+            .iter()
+            .any(|event| matches!(event, SessionEvent::PeerLeft { peer_id: id } if *id == peer_id))
+    }
+
+    /// Messages received from `from`, in order.
+    pub fn messages_from(&self, from: PeerId) -> Vec<(protocol::ChannelKind, session::Payload)> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                SessionEvent::PeerMessage {
+                    from: id,
+                    channel,
+                    data,
+                } if *id == from => Some((*channel, data.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A room full of simulated clients.
+pub struct Sim {
+    room: String,
+    network: MemoryNetwork,
+    pub clients: Vec<SimClient>,
+}
+
+impl Sim {
+    pub fn new(room: &str) -> Self {
+        Self {
+            room: room.to_string(),
+            network: MemoryNetwork::default(),
+            clients: Vec::new(),
+        }
+    }
+
+    /// Add a client to the room, and wait until the server has let it in.
+    pub fn join(&mut self, name: &str) -> &SimClient {
+        self.add_client(name, None)
+    }
+
+    /// Like `join`, but the client's peer messages go through a network
+    /// simulator.
+    pub fn join_simulating(&mut self, name: &str, conditions: NetworkConditions) -> &SimClient {
+        self.add_client(name, Some(conditions))
+    }
+
+    fn add_client(&mut self, name: &str, conditions: Option<NetworkConditions>) -> &SimClient {
+        let signaling = SimSignaling::connect(server(), &self.room, name);
+        let connector = MemoryConnector::new(&self.network);
+        let mut session = Session::new(signaling, connector);
+        if let Some(conditions) = conditions {
+            session = session.with_network_conditions(conditions);
+        }
+        self.clients.push(SimClient {
+            session,
+            events: Vec::new(),
+        });
+        self.run_until(&format!("{} to join", name), |sim| {
+            sim.clients.last().unwrap().session.is_connected()
+        });
+        self.clients.last().unwrap()
+    }
+
+    /// The clients' IDs, in joining order.
+    pub fn ids(&self) -> Vec<PeerId> {
+        self.clients.iter().map(SimClient::id).collect()
+    }
+
+    /// Poll every client once.
+    pub fn poll(&mut self) {
+        for client in &mut self.clients {
+            let events = client.session.poll();
+            client.events.extend(events);
+        }
+    }
+
+    /// Poll every client until `done` holds.
+    pub fn run_until(&mut self, what: &str, done: impl Fn(&Sim) -> bool) {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            self.poll();
+            if done(self) {
+                return;
+            }
+            assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// Join `names` and wait until everyone is connected to everyone.
+    pub fn full_mesh(room: &str, names: &[&str]) -> Self {
+        let mut sim = Self::new(room);
+        for name in names {
+            sim.join(name);
+        }
+        sim.wait_for_mesh();
+        sim
+    }
+
+    /// Wait until every client is connected to every other.
+    pub fn wait_for_mesh(&mut self) {
+        let others = self.clients.len() - 1;
+        self.run_until("every client to connect to every other", |sim| {
+            sim.clients
+                .iter()
+                .all(|client| client.connected_peers().len() == others)
+        });
+    }
+}
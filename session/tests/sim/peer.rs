@@ -0,0 +1,306 @@
+//! In-memory peer connections.
+//!
+//! Offers and answers carry the ID of the endpoint that made them, which is
+//! how the two ends of a connection find each other. A pair connects once
+//! each end has applied the other's description and at least one of its
+//! candidates; from then on both data channels are open, and messages show
+//! up on the other end's next poll. Negotiation keeps just enough state for
+//! offers to collide the way they do in a browser.
+
+use protocol::ChannelKind;
+use session::{
+    IceCandidateData, IceState, Payload, PeerConnector, PeerEvent, PeerId, PeerTransport,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Endpoints of every connection the simulated clients created.
+#[derive(Clone, Default)]
+pub struct MemoryNetwork {
+    inner: Rc<RefCell<Network>>,
+}
+
+#[derive(Default)]
+struct Network {
+    next_id: u64,
+    endpoints: HashMap<u64, Endpoint>,
+}
+
+#[derive(Default)]
+struct Endpoint {
+    polite: bool,
+    /// The other end, once a description from it has been applied.
+    remote: Option<u64>,
+    /// We sent an offer and await the answer.
+    have_local_offer: bool,
+    has_remote_description: bool,
+    /// Remote candidates that arrived before the remote description.
+    queued_candidates: Vec<IceCandidateData>,
+    applied_candidates: usize,
+    connected: bool,
+    closed: bool,
+    events: Vec<PeerEvent>,
+    /// Our offers rolled back for one that collided with them.
+    rolled_back: usize,
+    /// Colliding offers we ignored.
+    ignored_offers: usize,
+    /// Answers we made.
+    answers: usize,
+}
+
+impl Endpoint {
+    fn ready(&self) -> bool {
+        self.has_remote_description && self.applied_candidates > 0 && !self.closed
+    }
+
+    fn set_remote_description(&mut self, remote: u64) {
+        self.remote = Some(remote);
+        self.has_remote_description = true;
+        self.applied_candidates += std::mem::take(&mut self.queued_candidates).len();
+    }
+
+    /// Our local candidate, gathered after each description we make.
+    fn gather(&mut self, id: u64) {
+        self.events.push(PeerEvent::LocalIceCandidate {
+            candidate: format!("candidate {}", id),
+            sdp_mid: Some("0".to_string()),
+            sdp_m_line_index: Some(0),
+        });
+    }
+}
+
+/// The endpoint an offer or answer came from.
+fn endpoint_of(sdp: &str) -> Option<u64> {
+    sdp.split_whitespace().nth(1)?.parse().ok()
+}
+
+impl Network {
+    fn endpoint(&mut self, id: u64) -> &mut Endpoint {
+        self.endpoints.get_mut(&id).expect("endpoint exists")
+    }
+
+    /// Connect `id` to its remote end if both are ready.
+    fn try_connect(&mut self, id: u64) {
+        let local = &self.endpoints[&id];
+        let Some(remote_id) = local.remote else {
+            return;
+        };
+        let remote = &self.endpoints[&remote_id];
+        if local.connected || !local.ready() || !remote.ready() || remote.remote != Some(id) {
+            return;
+        }
+        for end in [id, remote_id] {
+            let endpoint = self.endpoint(end);
+            endpoint.connected = true;
+            endpoint.events.extend([
+                PeerEvent::IceStateChanged(IceState::Connected),
+                PeerEvent::ChannelOpened(ChannelKind::State),
+                PeerEvent::ChannelOpened(ChannelKind::Events),
+            ]);
+        }
+    }
+}
+
+/// One end of an in-memory connection.
+pub struct MemoryPeer {
+    network: MemoryNetwork,
+    id: u64,
+}
+
+impl MemoryPeer {
+    fn offer(&self, ice_restart: bool) {
+        let mut network = self.network.inner.borrow_mut();
+        let endpoint = network.endpoint(self.id);
+        endpoint.have_local_offer = true;
+        endpoint.events.push(PeerEvent::LocalOffer {
+            sdp: format!("offer {}", self.id),
+            ice_restart,
+        });
+        endpoint.gather(self.id);
+    }
+
+    /// Whether this end gives way when offers collide.
+    pub fn is_polite(&self) -> bool {
+        self.network.inner.borrow().endpoints[&self.id].polite
+    }
+
+    /// Offers of ours rolled back, colliding offers ignored and answers made
+    /// so far.
+    pub fn negotiation_counts(&self) -> (usize, usize, usize) {
+        let network = self.network.inner.borrow();
+        let endpoint = &network.endpoints[&self.id];
+        (
+            endpoint.rolled_back,
+            endpoint.ignored_offers,
+            endpoint.answers,
+        )
+    }
+
+    /// Break the path between the two ends without closing either, the
+    /// way a network change would; ICE reports both ends disconnected.
+    pub fn cut(&self) {
+        let mut network = self.network.inner.borrow_mut();
+        let Some(remote) = network.endpoints[&self.id].remote else {
+            return;
+        };
+        for end in [self.id, remote] {
+            let endpoint = network.endpoint(end);
+            if std::mem::take(&mut endpoint.connected) {
+                endpoint
+                    .events
+                    .push(PeerEvent::IceStateChanged(IceState::Disconnected));
+            }
+        }
+    }
+}
+
+impl PeerTransport for MemoryPeer {
+    fn create_data_channels(&mut self) {
+        // Both channels open with the connection
+    }
+
+    fn create_offer(&self) {
+        self.offer(false);
+    }
+
+    fn restart_ice(&self) {
+        self.offer(true);
+    }
+
+    fn accept_offer(&self, sdp: &str) -> bool {
+        let Some(remote) = endpoint_of(sdp) else {
+            log::warn!("Bad offer {:?}", sdp);
+            return false;
+        };
+        let mut network = self.network.inner.borrow_mut();
+        let endpoint = network.endpoint(self.id);
+        if endpoint.have_local_offer && !endpoint.polite {
+            endpoint.ignored_offers += 1;
+            return false;
+        }
+        // The polite side rolls its own offer back
+        if std::mem::take(&mut endpoint.have_local_offer) {
+            endpoint.rolled_back += 1;
+        }
+        endpoint.answers += 1;
+        endpoint.set_remote_description(remote);
+        endpoint
+            .events
+            .push(PeerEvent::LocalAnswer(format!("answer {}", self.id)));
+        endpoint.gather(self.id);
+        network.try_connect(self.id);
+        true
+    }
+
+    fn accept_answer(&self, sdp: &str) {
+        let Some(remote) = endpoint_of(sdp) else {
+            log::warn!("Bad answer {:?}", sdp);
+            return;
+        };
+        let mut network = self.network.inner.borrow_mut();
+        let endpoint = network.endpoint(self.id);
+        if !endpoint.have_local_offer {
+            log::warn!("Answer from endpoint {} without an offer", remote);
+            return;
+        }
+        endpoint.have_local_offer = false;
+        endpoint.set_remote_description(remote);
+        network.try_connect(self.id);
+    }
+
+    fn add_ice_candidate(&self, ice: IceCandidateData) {
+        let mut network = self.network.inner.borrow_mut();
+        let endpoint = network.endpoint(self.id);
+        if endpoint.has_remote_description {
+            endpoint.applied_candidates += 1;
+            network.try_connect(self.id);
+        } else {
+            endpoint.queued_candidates.push(ice);
+        }
+    }
+
+    fn has_remote_description(&self) -> bool {
+        self.network.inner.borrow().endpoints[&self.id].has_remote_description
+    }
+
+    fn send(&self, channel: ChannelKind, data: &Payload) -> Result<(), &'static str> {
+        let mut network = self.network.inner.borrow_mut();
+        let endpoint = &network.endpoints[&self.id];
+        let Some(remote) = endpoint.remote.filter(|_| endpoint.connected) else {
+            return Err("channel not open");
+        };
+        network.endpoint(remote).events.push(PeerEvent::Message {
+            channel,
+            data: data.clone(),
+        });
+        Ok(())
+    }
+
+    fn channel_ready(&self, _channel: ChannelKind) -> bool {
+        self.network.inner.borrow().endpoints[&self.id].connected
+    }
+
+    fn poll_events(&self) -> Vec<PeerEvent> {
+        std::mem::take(&mut self.network.inner.borrow_mut().endpoint(self.id).events)
+    }
+
+    fn close(&self) {
+        let mut network = self.network.inner.borrow_mut();
+        let endpoint = network.endpoint(self.id);
+        endpoint.closed = true;
+        if !std::mem::take(&mut endpoint.connected) {
+            return;
+        }
+        if let Some(remote) = endpoint.remote {
+            let remote = network.endpoint(remote);
+            remote.connected = false;
+            remote
+                .events
+                .push(PeerEvent::IceStateChanged(IceState::Disconnected));
+        }
+    }
+}
+
+/// Creates in-memory connections, handed over on the next poll.
+pub struct MemoryConnector {
+    network: MemoryNetwork,
+    created: Vec<(PeerId, MemoryPeer)>,
+}
+
+impl MemoryConnector {
+    pub fn new(network: &MemoryNetwork) -> Self {
+        Self {
+            network: network.clone(),
+            created: Vec::new(),
+        }
+    }
+}
+
+impl PeerConnector for MemoryConnector {
+    type Peer = MemoryPeer;
+
+    fn connect(&mut self, peer_id: PeerId, polite: bool) {
+        let mut network = self.network.inner.borrow_mut();
+        let id = network.next_id;
+        network.next_id += 1;
+        network.endpoints.insert(
+            id,
+            Endpoint {
+                polite,
+                ..Endpoint::default()
+            },
+        );
+        self.created.push((
+            peer_id,
+            MemoryPeer {
+                network: self.network.clone(),
+                id,
+            },
+        ));
+    }
+
+    fn poll_created(&mut self) -> Vec<(PeerId, MemoryPeer)> {
+        std::mem::take(&mut self.created)
+    }
+}
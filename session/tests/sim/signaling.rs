@@ -0,0 +1,116 @@
+//! Signaling over a real WebSocket to the test server.
+
+use futures_util::{SinkExt, StreamExt};
+use protocol::{ClientMessage, ServerMessage};
+use session::{Signaling, SignalingEvent};
+use std::time::Instant;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio_tungstenite::tungstenite::Message;
+
+use super::Server;
+
+/// A signaling connection whose socket is served by a task on the server's
+/// runtime; the session side just exchanges messages with it.
+pub struct SimSignaling {
+    /// Gone once we have disconnected, which closes the socket.
+    outgoing: Option<UnboundedSender<ClientMessage>>,
+    incoming: UnboundedReceiver<SignalingEvent>,
+    epoch: Instant,
+    /// Server clock minus ours, in milliseconds, as of the last `welcome`.
+    offset: f64,
+}
+
+impl SimSignaling {
+    /// Open a socket to the server and join `room` as `name`.
+    pub fn connect(server: &Server, room: &str, name: &str) -> Self {
+        let (outgoing, mut to_server) = mpsc::unbounded_channel::<ClientMessage>();
+        let (from_server, incoming) = mpsc::unbounded_channel();
+        let url = format!("ws://{}/ws", server.addr);
+        let join = ClientMessage::Join {
+            room: Some(room.to_string()),
+            name: Some(name.to_string()),
+            resume: None,
+        };
+        let _ = outgoing.send(join);
+
+        server.runtime.spawn(async move {
+            let (socket, _) = match tokio_tungstenite::connect_async(url).await {
+                Ok(connected) => connected,
+                Err(e) => {
+                    log::error!("Failed to connect to the test server: {}", e);
+                    let _ = from_server.send(SignalingEvent::Error);
+                    return;
+                }
+            };
+            let (mut sink, mut stream) = socket.split();
+            let _ = from_server.send(SignalingEvent::Connected);
+
+            loop {
+                tokio::select! {
+                    msg = to_server.recv() => {
+                        let Some(msg) = msg else {
+                            let _ = sink.close().await;
+                            break;
+                        };
+                        let json = serde_json::to_string(&msg).expect("client message");
+                        if sink.send(Message::text(json)).await.is_err() {
+                            break;
+                        }
+                    }
+                    msg = stream.next() => match msg {
+                        Some(Ok(Message::Text(text))) => {
+                            match serde_json::from_str::<ServerMessage>(text.as_str()) {
+                                Ok(msg) => {
+                                    let _ = from_server.send(SignalingEvent::Message(msg));
+                                }
+                                Err(e) => log::warn!("Bad server message {}: {}", text, e),
+                            }
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(_)) | None => break,
+                    }
+                }
+            }
+            let _ = from_server.send(SignalingEvent::Disconnected);
+        });
+
+        Self {
+            outgoing: Some(outgoing),
+            incoming,
+            epoch: Instant::now(),
+            offset: 0.0,
+        }
+    }
+
+    fn local_millis(&self) -> f64 {
+        self.epoch.elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+impl Signaling for SimSignaling {
+    fn poll_events(&mut self) -> Vec<SignalingEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = self.incoming.try_recv() {
+            if let SignalingEvent::Message(ServerMessage::Welcome { server_time, .. }) = &event {
+                self.offset = server_time - self.local_millis();
+            }
+            events.push(event);
+        }
+        events
+    }
+
+    fn send(&self, msg: &ClientMessage) {
+        if let Some(outgoing) = &self.outgoing {
+            let _ = outgoing.send(msg.clone());
+        }
+    }
+
+    fn server_time(&self) -> f64 {
+        self.local_millis() + self.offset
+    }
+
+    fn disconnect(&mut self) {
+        self.send(&ClientMessage::Leave);
+        self.outgoing = None;
+    }
+}
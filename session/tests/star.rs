@@ -0,0 +1,159 @@
+//! Star topology: no peer connections, the server passes all game traffic on.
+
+mod sim;
+
+use protocol::state::StatePacket;
+use protocol::{ChannelKind, Topology};
+use server::config::Config;
+use session::{Payload, StateReception};
+use sim::{Sim, SimClient};
+
+/// Players this far apart are out of each other's interest radius.
+const FAR: f32 = 5000.0;
+
+fn star(room: &str, names: &[&str]) -> Sim {
+    let mut config = Config::default();
+    config.network.topology = Topology::Star;
+    sim::configure_server(config);
+
+    let mut sim = Sim::new(room);
+    for name in names {
+        sim.join(name);
+    }
+    let others = names.len() - 1;
+    sim.run_until("everyone to hear about everyone", |sim| {
+        sim.clients
+            .iter()
+            .all(|client| client.joined_peers().len() == others)
+    });
+    sim
+}
+
+fn state(seq: u16, x: f32) -> Payload {
+    let packet = StatePacket {
+        seq,
+        sent_at_ms: 1000,
+        position: [x, 0.0, 0.0],
+        yaw: 0.0,
+        pitch: 0.0,
+        mask: 0,
+    };
+    Payload::Binary(packet.encode().to_vec())
+}
+
+/// Sequence numbers of the state packets `client` got from `from`.
+fn state_seqs(client: &SimClient, from: u64) -> Vec<u16> {
+    client
+        .messages_from(from)
+        .into_iter()
+        .filter_map(|(channel, data)| match (channel, data) {
+            (ChannelKind::State, Payload::Binary(bytes)) => StatePacket::decode(&bytes).ok(),
+            _ => None,
+        })
+        .map(|packet| packet.seq)
+        .collect()
+}
+
+#[test]
+fn server_passes_broadcasts_on() {
+    let mut sim = star("star-fan-out", &["Ann", "Bob", "Cat"]);
+    let ids = sim.ids();
+    for client in &sim.clients {
+        assert_eq!(client.session.topology(), Topology::Star);
+        assert_eq!(client.session.peers().count(), 0);
+    }
+
+    let hello = Payload::Text("{\"type\":\"introduction\",\"name\":\"Ann\"}".to_string());
+    sim.clients[0]
+        .session
+        .broadcast(ChannelKind::Events, &hello);
+    sim.clients[0]
+        .session
+        .broadcast(ChannelKind::State, &state(0, 0.0));
+    sim.run_until("Bob and Cat to get both messages", |sim| {
+        sim.clients[1..]
+            .iter()
+            .all(|client| client.messages_from(ids[0]).len() == 2)
+    });
+
+    for client in &sim.clients[1..] {
+        let messages = client.messages_from(ids[0]);
+        assert_eq!(messages[0], (ChannelKind::Events, hello.clone()));
+        assert_eq!(messages[1], (ChannelKind::State, state(0, 0.0)));
+    }
+    assert!(sim.clients[0].messages_from(ids[0]).is_empty());
+}
+
+#[test]
+fn distant_players_get_fewer_updates() {
+    let mut sim = star("star-interest", &["Near", "Far"]);
+    let ids = sim.ids();
+    let interval = Config::default().network.distant_update_interval;
+
+    // The server learns where Far is from their state
+    sim.clients[1]
+        .session
+        .broadcast(ChannelKind::State, &state(0, FAR));
+    sim.run_until("Near to learn where Far is", |sim| {
+        !state_seqs(&sim.clients[0], ids[1]).is_empty()
+    });
+
+    for seq in 0..2 * interval {
+        sim.clients[0]
+            .session
+            .broadcast(ChannelKind::State, &state(seq, 0.0));
+    }
+    // Moving close puts Near back in range
+    sim.clients[0]
+        .session
+        .broadcast(ChannelKind::State, &state(2 * interval + 1, FAR - 100.0));
+    // Events always get through, and arrive after the state sent before them
+    let done = Payload::Text("done".to_string());
+    sim.clients[0].session.broadcast(ChannelKind::Events, &done);
+    sim.run_until("Far to get the end marker", |sim| {
+        sim.clients[1]
+            .messages_from(ids[0])
+            .contains(&(ChannelKind::Events, done.clone()))
+    });
+
+    assert_eq!(
+        state_seqs(&sim.clients[1], ids[0]),
+        [0, interval, 2 * interval + 1]
+    );
+}
+
+#[test]
+fn held_back_updates_are_not_lost() {
+    let mut sim = star("star-reception", &["Near", "Far"]);
+    let ids = sim.ids();
+    let interval = Config::default().network.distant_update_interval;
+    assert_eq!(sim.clients[0].session.state_stride(), interval);
+
+    sim.clients[0]
+        .session
+        .broadcast(ChannelKind::State, &state(0, 0.0));
+    sim.run_until("Far to learn where Near is", |sim| {
+        !state_seqs(&sim.clients[1], ids[0]).is_empty()
+    });
+
+    for seq in 0..4 * interval {
+        sim.clients[1]
+            .session
+            .broadcast(ChannelKind::State, &state(seq, FAR));
+    }
+    let done = Payload::Text("done".to_string());
+    sim.clients[1].session.broadcast(ChannelKind::Events, &done);
+    sim.run_until("Near to get the end marker", |sim| {
+        sim.clients[0]
+            .messages_from(ids[1])
+            .contains(&(ChannelKind::Events, done.clone()))
+    });
+
+    let seqs = state_seqs(&sim.clients[0], ids[1]);
+    assert!(seqs.len() < 4 * interval as usize);
+    let mut reception = StateReception::new(sim.clients[0].session.state_stride());
+    for seq in seqs {
+        reception.record(seq, 1000.0, 1050.0);
+    }
+    assert_eq!(reception.report().loss, Some(0.0));
+}